@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_entries_key_version;
+ALTER TABLE entries DROP COLUMN IF EXISTS key_version;
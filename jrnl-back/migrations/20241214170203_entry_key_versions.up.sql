@@ -0,0 +1,4 @@
+ALTER TABLE entries
+    ADD COLUMN IF NOT EXISTS key_version INT NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS idx_entries_key_version ON entries (key_version);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_entries_legacy_envelope;
+
+-- any rows already upgraded to v2 can't be represented as v1 anymore
+ALTER TABLE entries
+    ALTER COLUMN nonce SET NOT NULL;
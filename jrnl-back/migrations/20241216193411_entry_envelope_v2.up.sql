@@ -0,0 +1,5 @@
+-- v2 envelopes carry their own nonces inline, only legacy v1 rows keep one here
+ALTER TABLE entries
+    ALTER COLUMN nonce DROP NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_entries_legacy_envelope ON entries (id) WHERE nonce IS NOT NULL;
@@ -0,0 +1,8 @@
+-- entries wrapped by a user key are unreadable once this runs
+DELETE FROM entries WHERE key_version IS NULL;
+
+ALTER TABLE entries
+    ALTER COLUMN key_version SET DEFAULT 1,
+    ALTER COLUMN key_version SET NOT NULL;
+
+DROP TABLE IF EXISTS user_keys;
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS user_keys
+(
+    user_id     UUID PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+    wrapped_key BYTEA       NOT NULL,
+    key_version INT         NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_keys_key_version ON user_keys (key_version);
+
+-- content keys wrapped by a user key don't depend on any master key version directly
+ALTER TABLE entries
+    ALTER COLUMN key_version DROP NOT NULL,
+    ALTER COLUMN key_version DROP DEFAULT;
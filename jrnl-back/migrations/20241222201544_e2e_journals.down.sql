@@ -0,0 +1,7 @@
+ALTER TABLE active_entries DROP COLUMN IF EXISTS ciphertext;
+
+DELETE FROM entries WHERE client_encrypted;
+ALTER TABLE entries DROP COLUMN IF EXISTS client_encrypted;
+
+DROP TABLE IF EXISTS e2e_key_material;
+ALTER TABLE users DROP COLUMN IF EXISTS e2e_enabled;
@@ -0,0 +1,21 @@
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS e2e_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- everything a client needs to re-derive its passphrase key, the server can't use any of it on its own
+CREATE TABLE IF NOT EXISTS e2e_key_material
+(
+    user_id              UUID PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+    kdf_algorithm        TEXT        NOT NULL,
+    kdf_params           JSONB       NOT NULL,
+    salt                 BYTEA       NOT NULL,
+    wrapped_data_key     BYTEA       NOT NULL,
+    wrapped_recovery_key BYTEA       NOT NULL,
+    updated_at           TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- client encrypted rows keep the uploaded blob in encrypted_content and have no content key
+ALTER TABLE entries
+    ADD COLUMN IF NOT EXISTS client_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE active_entries
+    ADD COLUMN IF NOT EXISTS ciphertext BYTEA;
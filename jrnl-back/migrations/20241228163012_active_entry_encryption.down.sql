@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS idx_active_entries_plaintext;
+
+-- sealed rows can't be turned back into plaintext here
+DELETE FROM active_entries WHERE encrypted_content IS NOT NULL;
+
+ALTER TABLE active_entries
+    DROP COLUMN IF EXISTS content_key,
+    DROP COLUMN IF EXISTS encrypted_content;
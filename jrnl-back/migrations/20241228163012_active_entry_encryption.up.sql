@@ -0,0 +1,7 @@
+-- today's entry is sealed with the same envelope as entries, so rollover can move it without re-encrypting.
+-- text is only kept around for rows written before this, the maintenance task seals and clears them
+ALTER TABLE active_entries
+    ADD COLUMN IF NOT EXISTS encrypted_content BYTEA,
+    ADD COLUMN IF NOT EXISTS content_key       BYTEA;
+
+CREATE INDEX IF NOT EXISTS idx_active_entries_plaintext ON active_entries (id) WHERE text IS NOT NULL;
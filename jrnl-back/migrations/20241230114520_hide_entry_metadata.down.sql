@@ -0,0 +1,15 @@
+-- hidden scales can't be recovered without the application, so those rows are dropped
+DELETE FROM active_entries WHERE emotion_scale IS NULL;
+DELETE FROM entries WHERE emotion_scale IS NULL;
+
+ALTER TABLE active_entries
+    DROP CONSTRAINT IF EXISTS active_entries_emotion_scale_present,
+    DROP COLUMN IF EXISTS encrypted_emotion_scale,
+    ALTER COLUMN emotion_scale SET NOT NULL;
+
+ALTER TABLE entries
+    DROP CONSTRAINT IF EXISTS entries_emotion_scale_present,
+    DROP COLUMN IF EXISTS encrypted_emotion_scale,
+    ALTER COLUMN emotion_scale SET NOT NULL;
+
+ALTER TABLE users DROP COLUMN IF EXISTS hide_metadata;
@@ -0,0 +1,15 @@
+-- opt-in, see "Metadata hiding" in the readme for what keeps working
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS hide_metadata BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- hidden entries keep emotion_scale null and the sealed scale in encrypted_emotion_scale,
+-- their content is padded so its length only reveals a size bucket
+ALTER TABLE entries
+    ALTER COLUMN emotion_scale DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS encrypted_emotion_scale BYTEA,
+    ADD CONSTRAINT entries_emotion_scale_present CHECK (emotion_scale IS NOT NULL OR encrypted_emotion_scale IS NOT NULL);
+
+ALTER TABLE active_entries
+    ALTER COLUMN emotion_scale DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS encrypted_emotion_scale BYTEA,
+    ADD CONSTRAINT active_entries_emotion_scale_present CHECK (emotion_scale IS NOT NULL OR encrypted_emotion_scale IS NOT NULL);
@@ -0,0 +1,3 @@
+ALTER TABLE entries DROP COLUMN IF EXISTS search_key_version;
+
+DROP TABLE IF EXISTS entry_search_tokens;
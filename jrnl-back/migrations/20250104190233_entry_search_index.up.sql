@@ -0,0 +1,15 @@
+-- blind index tokens, a keyed hash of every stemmed word in an entry. the key is derived from the author's user key
+-- and the master key version wrapping it, so the index is rebuilt whenever that version changes
+CREATE TABLE IF NOT EXISTS entry_search_tokens
+(
+    entry_id UUID  NOT NULL REFERENCES entries ON DELETE CASCADE,
+    author   UUID  NOT NULL REFERENCES users ON DELETE CASCADE,
+    token    BYTEA NOT NULL,
+    PRIMARY KEY (entry_id, token)
+);
+
+CREATE INDEX IF NOT EXISTS idx_entry_search_tokens_author_token ON entry_search_tokens (author, token);
+
+-- master key version the tokens were derived under, null until the entry is indexed
+ALTER TABLE entries
+    ADD COLUMN IF NOT EXISTS search_key_version INT;
@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN IF EXISTS revision_limit;
+
+DROP TABLE IF EXISTS entry_revisions;
@@ -0,0 +1,24 @@
+-- previous versions of an entry, copied over with the envelope they were sealed with. every save seals with a fresh
+-- content key so no two revisions share one. entry_id can point at either entries or active_entries, the id is kept
+-- when today's entry rolls over
+CREATE TABLE IF NOT EXISTS entry_revisions
+(
+    id                      UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    entry_id                UUID        NOT NULL,
+    author                  UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    date                    DATE        NOT NULL,
+    emotion_scale           FLOAT4,
+    encrypted_emotion_scale BYTEA,
+    encrypted_content       BYTEA       NOT NULL,
+    content_key             BYTEA       NOT NULL,
+    nonce                   BYTEA,
+    key_version             INT,
+    client_encrypted        BOOLEAN     NOT NULL DEFAULT FALSE,
+    created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_entry_revisions_entry_created ON entry_revisions (entry_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_entry_revisions_author ON entry_revisions (author);
+
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS revision_limit INT NOT NULL DEFAULT 20 CHECK (revision_limit >= 0 AND revision_limit <= 100);
@@ -0,0 +1,21 @@
+-- names are sealed with the author's user key and padded, so neither the name nor its length is stored
+CREATE TABLE IF NOT EXISTS tags
+(
+    id             UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    author         UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    encrypted_name BYTEA       NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tags_author ON tags (author);
+
+-- entry_id can point at either entries or active_entries, the id is kept when today's entry rolls over
+CREATE TABLE IF NOT EXISTS entry_tags
+(
+    entry_id UUID NOT NULL,
+    tag_id   UUID NOT NULL REFERENCES tags ON DELETE CASCADE,
+    author   UUID NOT NULL REFERENCES users ON DELETE CASCADE,
+    PRIMARY KEY (entry_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_entry_tags_tag ON entry_tags (tag_id);
@@ -0,0 +1,39 @@
+-- the blob itself lives in blob storage under storage_key, sealed in segments with its own key.
+-- that key is wrapped with the author's user key like an entry's content_key
+CREATE TABLE IF NOT EXISTS attachments
+(
+    id           UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- entry_id can point at either entries or active_entries, the id is kept when today's entry rolls over
+    entry_id     UUID        NOT NULL,
+    author       UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    content_type TEXT        NOT NULL,
+    size         BIGINT      NOT NULL,
+    blob_key     BYTEA       NOT NULL,
+    storage_key  TEXT        NOT NULL UNIQUE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_entry ON attachments (entry_id);
+CREATE INDEX IF NOT EXISTS idx_attachments_author ON attachments (author);
+
+-- blob storage isn't transactional, so deleted rows queue their blob up here and a background task
+-- removes it later. the trigger also catches rows that go away through the cascade on users
+CREATE TABLE IF NOT EXISTS attachment_blob_deletions
+(
+    storage_key TEXT PRIMARY KEY,
+    queued_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION queue_attachment_blob_deletion() RETURNS TRIGGER AS
+$$
+BEGIN
+    INSERT INTO attachment_blob_deletions (storage_key) VALUES (OLD.storage_key) ON CONFLICT DO NOTHING;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER attachments_queue_blob_deletion
+    AFTER DELETE
+    ON attachments
+    FOR EACH ROW
+EXECUTE FUNCTION queue_attachment_blob_deletion();
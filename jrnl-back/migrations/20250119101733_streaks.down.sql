@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS streak_skipped_days;
+
+ALTER TABLE users DROP COLUMN IF EXISTS streak_freezes;
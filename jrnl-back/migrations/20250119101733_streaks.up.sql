@@ -0,0 +1,11 @@
+-- missed days bridged per calendar month before a streak breaks
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS streak_freezes INT NOT NULL DEFAULT 0 CHECK (streak_freezes >= 0 AND streak_freezes <= 4);
+
+-- local dates a timezone change jumped past, they neither count towards a streak nor break it
+CREATE TABLE IF NOT EXISTS streak_skipped_days
+(
+    user_id UUID NOT NULL REFERENCES users ON DELETE CASCADE,
+    date    DATE NOT NULL,
+    PRIMARY KEY (user_id, date)
+);
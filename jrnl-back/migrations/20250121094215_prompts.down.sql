@@ -0,0 +1,3 @@
+ALTER TABLE entries DROP COLUMN IF EXISTS prompt_id;
+ALTER TABLE active_entries DROP COLUMN IF EXISTS prompt_id;
+DROP TABLE IF EXISTS prompts;
@@ -0,0 +1,18 @@
+-- built in prompts ship with the app, only the ones users write themselves are stored. their text is sealed
+-- with the author's user key and padded, like tag names
+CREATE TABLE IF NOT EXISTS prompts
+(
+    id             UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    author         UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    encrypted_text BYTEA       NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_prompts_author ON prompts (author);
+
+-- either a built in prompt or one of the author's, so there's no foreign key. cleared when a user prompt is deleted
+ALTER TABLE active_entries
+    ADD COLUMN IF NOT EXISTS prompt_id UUID;
+
+ALTER TABLE entries
+    ADD COLUMN IF NOT EXISTS prompt_id UUID;
@@ -0,0 +1,9 @@
+-- fails while any day still holds more than one entry
+DROP INDEX IF EXISTS idx_active_entries_author_date;
+
+ALTER TABLE active_entries DROP COLUMN IF EXISTS created_at;
+ALTER TABLE active_entries ADD CONSTRAINT active_entries_author_date_key UNIQUE (author, date);
+
+DROP INDEX IF EXISTS idx_entries_author_date_created_at;
+ALTER TABLE entries DROP COLUMN IF EXISTS created_at;
+ALTER TABLE entries ADD CONSTRAINT entries_author_date_key UNIQUE (author, date);
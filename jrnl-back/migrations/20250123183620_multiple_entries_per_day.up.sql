@@ -0,0 +1,34 @@
+-- a day can hold any number of entries, each written at its own time. entries from before this were the only
+-- one of their day, so they're all taken as written at the start of it
+ALTER TABLE entries
+    DROP CONSTRAINT IF EXISTS entries_author_date_key;
+
+ALTER TABLE entries
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+
+UPDATE entries SET created_at = date::timestamptz WHERE created_at IS NULL;
+
+ALTER TABLE entries
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN created_at SET DEFAULT NOW();
+
+-- takes over from the unique constraint's index, in the order lists walk a day's entries
+CREATE INDEX IF NOT EXISTS idx_entries_author_date_created_at ON entries (author, date, created_at, id);
+
+ALTER TABLE active_entries
+    DROP CONSTRAINT IF EXISTS active_entries_author_date_key;
+
+ALTER TABLE active_entries
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+
+UPDATE active_entries SET created_at = date::timestamptz WHERE created_at IS NULL;
+
+ALTER TABLE active_entries
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN created_at SET DEFAULT NOW();
+
+-- the unique constraint was the only index active entries had, the ones meant for them were created on entries
+DROP INDEX IF EXISTS idx_active_entries_author_date;
+DROP INDEX IF EXISTS idx_active_entries_author_date_id;
+
+CREATE INDEX IF NOT EXISTS idx_active_entries_author_date ON active_entries (author, date, created_at);
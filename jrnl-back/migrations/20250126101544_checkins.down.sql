@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_checkins_author_recorded_at;
+DROP TABLE IF EXISTS checkins;
@@ -0,0 +1,12 @@
+-- quick mood check-ins, kept apart from entries. the scale and note are sealed together with the author's user key
+-- and padded, so they're sealed the same whether or not the author hides entry metadata
+CREATE TABLE IF NOT EXISTS checkins
+(
+    id             UUID PRIMARY KEY,
+    author         UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    encrypted_data BYTEA       NOT NULL,
+    recorded_at    TIMESTAMPTZ NOT NULL,
+    created_at     TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_checkins_author_recorded_at ON checkins (author, recorded_at);
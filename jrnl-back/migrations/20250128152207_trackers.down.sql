@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_tracker_values_author_date;
+DROP TABLE IF EXISTS tracker_values;
+
+DROP INDEX IF EXISTS idx_trackers_author;
+DROP TABLE IF EXISTS trackers;
@@ -0,0 +1,24 @@
+-- user defined trackers. the name, kind and unit are sealed together with the author's user key and padded,
+-- so nothing about what's being tracked is readable
+CREATE TABLE IF NOT EXISTS trackers
+(
+    id                   UUID PRIMARY KEY,
+    author               UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    encrypted_definition BYTEA       NOT NULL,
+    created_at           TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_trackers_author ON trackers (author);
+
+-- one value per tracker and day, kept by date next to that day's entries rather than on any one of them
+CREATE TABLE IF NOT EXISTS tracker_values
+(
+    tracker_id      UUID        NOT NULL REFERENCES trackers ON DELETE CASCADE,
+    author          UUID        NOT NULL REFERENCES users ON DELETE CASCADE,
+    date            DATE        NOT NULL,
+    encrypted_value BYTEA       NOT NULL,
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (tracker_id, date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tracker_values_author_date ON tracker_values (author, date);
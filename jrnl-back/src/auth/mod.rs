@@ -8,7 +8,7 @@ pub mod jwt;
 pub mod providers;
 
 pub async fn clean_expired_sessions(pool: PgPool) {
-    let mut ticker = interval(Duration::from_secs(60 * 15));
+    let mut ticker = interval(Duration::from_mins(15));
     loop {
         ticker.tick().await;
 
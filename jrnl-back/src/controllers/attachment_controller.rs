@@ -0,0 +1,275 @@
+use crate::{
+    crypto::blob::BlobSealer,
+    error::{DatabaseError, JrnlError, JrnlResult},
+    schemas::{
+        attachment::{Attachment, EncryptedAttachment},
+        user::User,
+    },
+    services::{attachment_service::AttachmentService, user_key_service::UserKeyService},
+    storage::BlobWriter,
+    AppState,
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+// uploads stream straight from the request into blob storage, so they're routed outside of the global
+// timeout and body limit, with the size limit enforced while reading instead
+pub fn attachments_controller() -> Router<AppState> {
+    Router::new()
+        .route("/", post(upload_attachment))
+        .route("/:id", get(download_attachment).delete(delete_attachment))
+}
+
+const MAX_ATTACHMENT_SIZE: usize = 1024 * 1024 * 25;
+const MAX_ENTRY_ATTACHMENTS: i64 = 10;
+
+#[derive(Deserialize)]
+struct UploadAttachmentParams {
+    entry_id: Uuid,
+}
+
+// only the essence is kept, e.g. `audio/mp4; codecs=mp4a` is stored as `audio/mp4`
+fn attachment_content_type(headers: &HeaderMap) -> JrnlResult<String> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .ok_or(JrnlError::UnsupportedAttachmentType)?;
+
+    let valid = content_type.split_once('/').is_some_and(|(kind, subtype)| {
+        matches!(kind, "image" | "audio")
+            && !subtype.is_empty()
+            && subtype
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+
+    if !valid {
+        return Err(JrnlError::UnsupportedAttachmentType);
+    }
+
+    Ok(content_type)
+}
+
+async fn upload_attachment(
+    user: User,
+    Query(params): Query<UploadAttachmentParams>,
+    attachment_service: AttachmentService,
+    user_key_service: UserKeyService,
+    State(AppState {
+        key_provider,
+        blob_storage,
+        ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> JrnlResult<Json<Attachment>> {
+    // attachments are sealed by the server, which an e2e journal has no business handing plaintext to
+    if user.e2e_enabled {
+        return Err(JrnlError::PlaintextNotAllowed);
+    }
+
+    let content_type = attachment_content_type(&headers)?;
+
+    let declared_size = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+
+    if declared_size.is_some_and(|size| size > MAX_ATTACHMENT_SIZE) {
+        return Err(JrnlError::AttachmentTooLarge);
+    }
+
+    let attachment_count = attachment_service
+        .get_entry_attachment_count(&user, &params.entry_id)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?;
+
+    if attachment_count >= MAX_ENTRY_ATTACHMENTS {
+        return Err(JrnlError::TooManyEntryAttachments);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let id = Uuid::new_v4();
+    let (sealer, blob_key) =
+        EncryptedAttachment::sealer(&id, &params.entry_id, &user.id, &user_key)
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let storage_key = EncryptedAttachment::storage_key(&id, &user.id);
+    let mut writer = blob_storage
+        .create(&storage_key)
+        .await
+        .map_err(JrnlError::AttachmentStorageFailed)?;
+
+    let size = match write_blob(&mut *writer, sealer, body).await {
+        Ok(size) => size,
+        Err(why) => {
+            if let Err(abort_why) = writer.abort().await {
+                error!(
+                    "Failed to abort attachment blob {}: {}",
+                    storage_key, abort_why
+                );
+            }
+
+            return Err(why);
+        }
+    };
+
+    writer
+        .finish()
+        .await
+        .map_err(JrnlError::AttachmentStorageFailed)?;
+
+    let attachment = EncryptedAttachment {
+        id,
+        entry_id: params.entry_id,
+        author: user.id,
+        content_type,
+        size: i64::try_from(size).map_err(|why| JrnlError::Other(why.into()))?,
+        blob_key,
+        storage_key,
+        created_at: Utc::now(),
+    };
+
+    match attachment_service.create_attachment(&attachment).await {
+        Ok(attachment) => Ok(Json(attachment.into())),
+        Err(why) => {
+            if let Err(delete_why) = blob_storage.delete(&attachment.storage_key).await {
+                error!(
+                    "Failed to delete orphaned attachment blob {}: {}",
+                    attachment.storage_key, delete_why
+                );
+            }
+
+            Err(DatabaseError(why).into())
+        }
+    }
+}
+
+// seals the body a segment at a time as it comes in, returns the plaintext size
+async fn write_blob(
+    writer: &mut dyn BlobWriter,
+    mut sealer: BlobSealer,
+    body: Body,
+) -> JrnlResult<usize> {
+    let mut size = 0;
+    let mut chunks = body.into_data_stream();
+
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|why| JrnlError::AttachmentStorageFailed(why.into()))?;
+
+        size += chunk.len();
+        if size > MAX_ATTACHMENT_SIZE {
+            return Err(JrnlError::AttachmentTooLarge);
+        }
+
+        let segments = sealer
+            .push(&chunk)
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+        writer
+            .write(&segments)
+            .await
+            .map_err(JrnlError::AttachmentStorageFailed)?;
+    }
+
+    if size == 0 {
+        return Err(JrnlError::EmptyAttachment);
+    }
+
+    let segments = sealer.finish().map_err(JrnlError::EntryEncryptionFailed)?;
+    writer
+        .write(&segments)
+        .await
+        .map_err(JrnlError::AttachmentStorageFailed)?;
+
+    Ok(size)
+}
+
+async fn download_attachment(
+    user: User,
+    Path(id): Path<Uuid>,
+    attachment_service: AttachmentService,
+    user_key_service: UserKeyService,
+    State(AppState {
+        key_provider,
+        blob_storage,
+        ..
+    }): State<AppState>,
+) -> JrnlResult<impl IntoResponse> {
+    let attachment = attachment_service
+        .get_attachment(&user, &id)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let opener = attachment
+        .opener(&user_key)
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let blob = blob_storage
+        .open(&attachment.storage_key)
+        .await
+        .map_err(JrnlError::AttachmentStorageFailed)?;
+
+    // the response has already started by the time a segment fails to open, all that's left is to cut it off
+    let plaintext = stream::try_unfold((blob, Some(opener)), |(mut blob, opener)| async move {
+        let Some(mut opener) = opener else {
+            return Ok(None);
+        };
+
+        match blob.try_next().await? {
+            Some(chunk) => Ok(Some((
+                Bytes::from(opener.push(&chunk)?),
+                (blob, Some(opener)),
+            ))),
+            None => Ok(Some((Bytes::from(opener.finish()?), (blob, None)))),
+        }
+    })
+    .inspect_err(move |why: &anyhow::Error| {
+        error!("Failed to read attachment {}: {}", id, why);
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (header::CONTENT_LENGTH, attachment.size.to_string()),
+            (header::CACHE_CONTROL, "private, no-store".to_string()),
+        ],
+        Body::from_stream(plaintext),
+    ))
+}
+
+async fn delete_attachment(
+    user: User,
+    Path(id): Path<Uuid>,
+    attachment_service: AttachmentService,
+) -> JrnlResult<StatusCode> {
+    if attachment_service.delete_attachment(&user, &id).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
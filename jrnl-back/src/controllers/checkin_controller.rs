@@ -0,0 +1,226 @@
+use crate::{
+    crypto::key_provider::KeyProvider,
+    error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
+    schemas::{
+        checkin::{Checkin, EncryptedCheckin},
+        user::User,
+    },
+    services::{
+        checkin_service::{decrypt_checkins, CheckinPatterns, CheckinService},
+        user_key_service::UserKeyService,
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, Days, NaiveDate, TimeDelta, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub fn checkins_controller() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_checkins).post(create_checkin))
+        .route("/patterns", get(get_checkin_patterns))
+        .route("/:id", delete(delete_checkin))
+}
+
+const MAX_DAILY_CHECKINS: i64 = 50;
+const MAX_CHECKIN_NOTE_LEN: usize = 280;
+const MAX_CHECKIN_RANGE_DAYS: i64 = 366;
+const DEFAULT_CHECKIN_RANGE_DAYS: u64 = 30;
+
+// a device clock running a little ahead still records "now"
+const CHECKIN_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+#[derive(Deserialize)]
+struct CheckinRangeParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+// inclusive days in the user's timezone, the last 30 days up to today unless told otherwise
+fn checkin_range(user: &User, params: &CheckinRangeParams) -> JrnlResult<(NaiveDate, NaiveDate)> {
+    let to = params.to.unwrap_or_else(|| user.current_date_by_timezone());
+    let from = match params.from {
+        Some(from) => from,
+        None => to
+            .checked_sub_days(Days::new(DEFAULT_CHECKIN_RANGE_DAYS - 1))
+            .ok_or(JrnlError::InvalidDateRange)?,
+    };
+
+    if from > to {
+        return Err(JrnlError::InvalidDateRange);
+    }
+
+    if (to - from).num_days() >= MAX_CHECKIN_RANGE_DAYS {
+        return Err(JrnlError::InvalidCheckinRange);
+    }
+
+    Ok((from, to))
+}
+
+async fn checkins_between(
+    user: &User,
+    from: NaiveDate,
+    to: NaiveDate,
+    checkin_service: &CheckinService,
+    user_key_service: &UserKeyService,
+    key_provider: &dyn KeyProvider,
+) -> JrnlResult<Vec<Checkin>> {
+    let end = to.succ_opt().unwrap_or(to);
+    let checkins = checkin_service
+        .get_checkins(user, user.start_of_day(from), user.start_of_day(end))
+        .await
+        .map_err(DatabaseError)?;
+
+    if checkins.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(user, key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    decrypt_checkins(checkins, user_key)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+// oldest first, a time series of every check-in in the range
+async fn get_checkins(
+    user: User,
+    Query(params): Query<CheckinRangeParams>,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<Checkin>>> {
+    let (from, to) = checkin_range(&user, &params)?;
+
+    checkins_between(
+        &user,
+        from,
+        to,
+        &checkin_service,
+        &user_key_service,
+        &*key_provider,
+    )
+    .await
+    .map(Json)
+}
+
+async fn get_checkin_patterns(
+    user: User,
+    Query(params): Query<CheckinRangeParams>,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<CheckinPatterns>> {
+    let (from, to) = checkin_range(&user, &params)?;
+    let checkins = checkins_between(
+        &user,
+        from,
+        to,
+        &checkin_service,
+        &user_key_service,
+        &*key_provider,
+    )
+    .await?;
+
+    Ok(Json(CheckinPatterns::new(
+        from,
+        to,
+        &checkins,
+        user.timezone(),
+    )))
+}
+
+#[derive(Deserialize)]
+struct CheckinPayload {
+    emotion_scale: f32,
+    #[serde(default)]
+    note: Option<String>,
+    // now when left out
+    recorded_at: Option<DateTime<Utc>>,
+}
+
+async fn create_checkin(
+    user: User,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<CheckinPayload>,
+) -> JrnlResult<Json<Checkin>> {
+    if !(0.0..=10.0).contains(&payload.emotion_scale) {
+        return Err(JrnlError::InvalidEmotionScale);
+    }
+
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|note| !note.is_empty());
+    if note.is_some_and(|note| note.chars().count() > MAX_CHECKIN_NOTE_LEN) {
+        return Err(JrnlError::InvalidCheckinNote);
+    }
+
+    let now = Utc::now();
+    let recorded_at = payload.recorded_at.unwrap_or(now);
+    if recorded_at > now + CHECKIN_CLOCK_SKEW {
+        return Err(JrnlError::InvalidCheckinTime);
+    }
+    let recorded_at = recorded_at.min(now);
+
+    // counted per day in the user's timezone, the day the check-in lands on
+    let date = recorded_at.with_timezone(&user.timezone()).date_naive();
+    let existing_checkins = checkin_service
+        .get_checkins_count(
+            &user,
+            user.start_of_day(date),
+            user.start_of_day(date.succ_opt().unwrap_or(date)),
+        )
+        .await
+        .map_err(DatabaseError)?;
+
+    if existing_checkins >= MAX_DAILY_CHECKINS {
+        return Err(JrnlError::CannotCreateMoreCheckins);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let id = Uuid::new_v4();
+    let encrypted_data =
+        EncryptedCheckin::seal_data(id, user.id, payload.emotion_scale, note, &user_key)
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    checkin_service
+        .create_checkin(&user, id, &encrypted_data, recorded_at)
+        .await
+        .map_err(DatabaseError)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn delete_checkin(
+    user: User,
+    Path(id): Path<Uuid>,
+    checkin_service: CheckinService,
+) -> JrnlResult<StatusCode> {
+    if checkin_service
+        .delete_checkin(&user, &id)
+        .await
+        .map_err(DatabaseError)?
+    {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
@@ -1,21 +1,55 @@
 use crate::{
+    crypto::{blind_index::SearchIndexKey, key_provider::KeyProvider},
     error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
-    schemas::{active_entry::ActiveEntry, entry::DecryptedEntry, user::User},
-    services::entry_service::{EntryService, StrippedEntry},
-    web::cursor::{Cursor, CursorPaginatedResponse, CursorParams},
+    import::{
+        self, CsvMapping, ImportConflict, ImportFormat, ImportReport, ImportResult, ImportStatus,
+    },
+    schemas::{
+        active_entry::ActiveEntry,
+        attachment::Attachment,
+        entry::{DecryptedEntry, EncryptedEntry},
+        entry_revision::{DecryptedEntryRevision, EntryRevisionSummary},
+        prompt::Prompt,
+        user::User,
+        user_key::UserKey,
+    },
+    services::{
+        attachment_service::AttachmentService,
+        checkin_service::{decrypt_checkins, CheckinService},
+        entry_revision_service::EntryRevisionService,
+        entry_service::{
+            decrypt_entries, EntryDateKey, EntryFilter, EntryService, EntrySort, EntrySortKey,
+            EntryUploadStatus, StrippedEntry, StrippedEntryRow,
+        },
+        export_service::{ExportFormat, ExportService},
+        import_service::ImportService,
+        prompt_service::PromptService,
+        stats_service::{day_summary, Calendar, MoodStats, ScaleRow, StatsRange, StatsService},
+        tag_service::TagService,
+        user_key_service::UserKeyService,
+    },
+    web::{
+        base64_bytes,
+        cursor::{Cursor, CursorList, CursorPaginatedResponse, CursorParams},
+    },
     AppState,
 };
-use aes_gcm::{Aes256Gcm, Key};
 use axum::{
-    extract::{Path, Query, State},
-    routing::get,
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post, put},
     Json, Router,
 };
-use chrono::{Duration, NaiveDate, Utc};
-use serde::Deserialize;
+use chrono::{Duration, Months, NaiveDate, Utc};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use tokio::task::spawn_blocking;
 use tracing::error;
-use uuid::Uuid;
+use uuid::{Builder, Uuid};
 
 pub fn entries_controller() -> Router<AppState> {
     Router::new()
@@ -23,14 +57,48 @@ pub fn entries_controller() -> Router<AppState> {
             "/",
             get(get_trimmed_entries_paginated).put(put_local_mobile_entries),
         )
-        .route("/:id", get(get_entry))
-        .route("/today", get(get_today_entry).put(update_today_entry))
+        .route("/search", get(search_entries))
+        .route("/stats", get(get_stats))
+        .route("/calendar", get(get_calendar))
+        .route("/on-this-day", get(get_on_this_day_entries))
+        .route("/memory", get(get_random_memory))
+        .route("/export", get(export_entries))
+        .route("/bulk-delete", post(bulk_delete_entries))
+        .route(
+            "/:id",
+            get(get_entry).patch(patch_entry).delete(delete_entry),
+        )
+        .route("/:id/tags", get(get_entry_tags).put(put_entry_tags))
+        .route("/:id/attachments", get(get_entry_attachments))
+        .route("/:id/revisions", get(get_entry_revisions))
+        .route("/:id/revisions/:revision_id", get(get_entry_revision))
+        .route(
+            "/:id/revisions/:revision_id/restore",
+            post(restore_entry_revision),
+        )
+        .route(
+            "/today",
+            get(get_today_entry)
+                .put(update_today_entry)
+                .post(create_today_entry),
+        )
+        .route("/today/all", get(get_today_entries))
+        .route("/today/:id", put(update_today_entry_by_id))
+}
+
+// nested on its own, imports need a longer timeout than the rest of the entry routes
+pub fn entry_imports_controller() -> Router<AppState> {
+    Router::new().route(
+        "/",
+        post(import_entries).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+    )
 }
 
 async fn encrypt_active_entries_except_today(
     user: &User,
     entry_service: &EntryService,
-    master_key: Key<Aes256Gcm>,
+    user_key_service: &UserKeyService,
+    key_provider: &dyn KeyProvider,
 ) -> anyhow::Result<()> {
     let (mut transaction, entries) = entry_service
         .create_entry_migration_transaction_without_today(user)
@@ -40,11 +108,16 @@ async fn encrypt_active_entries_except_today(
         return Ok(());
     }
 
+    let user_key = user_key_service
+        .get_or_create_user_key(user, key_provider)
+        .await?;
+
+    let hide_metadata = user.hide_metadata;
     let encrypted_entries = match spawn_blocking(move || -> anyhow::Result<_> {
         let encrypted_entries = entries
             .into_iter()
             .filter(|entry| !entry.ephemeral)
-            .map(|entry| ActiveEntry::encrypt(&entry, &master_key))
+            .map(|entry| entry.into_entry(&user_key, hide_metadata))
             .collect::<anyhow::Result<Vec<_>>>()
             .map_err(JrnlError::EntryEncryptionFailed)?;
 
@@ -76,53 +149,694 @@ async fn encrypt_active_entries_except_today(
     transaction.commit().await.map_err(Into::into)
 }
 
+#[derive(Deserialize)]
+struct EntryFilterParams {
+    tag: Option<Uuid>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    min_scale: Option<f32>,
+    max_scale: Option<f32>,
+    has_text: Option<bool>,
+    #[serde(default)]
+    sort: EntrySort,
+}
+
+impl EntryFilterParams {
+    fn filter(&self) -> JrnlResult<EntryFilter> {
+        if matches!((self.from, self.to), (Some(from), Some(to)) if from > to) {
+            return Err(JrnlError::InvalidDateRange);
+        }
+
+        let valid_scale =
+            |scale: Option<f32>| scale.is_none_or(|scale| (0.0..=10.0).contains(&scale));
+        if !valid_scale(self.min_scale) || !valid_scale(self.max_scale) {
+            return Err(JrnlError::InvalidEmotionScale);
+        }
+
+        if matches!((self.min_scale, self.max_scale), (Some(min), Some(max)) if min > max) {
+            return Err(JrnlError::InvalidScaleRange);
+        }
+
+        Ok(EntryFilter {
+            tag: self.tag,
+            from: self.from,
+            to: self.to,
+            min_scale: self.min_scale,
+            max_scale: self.max_scale,
+            has_text: self.has_text,
+        })
+    }
+}
+
+// how many entries are opened at a time when filtering on hidden scales, and how many batches a request may open
+const SCALE_FILTER_BATCH: u32 = 500;
+const SCALE_FILTER_MAX_BATCHES: usize = 10;
+
 async fn get_trimmed_entries_paginated(
     user: User,
     Query(params): Query<CursorParams>,
+    Query(filter_params): Query<EntryFilterParams>,
+    entry_service: EntryService,
+    user_key_service: UserKeyService,
+    State(AppState {
+        key_provider,
+        cursor_signer,
+        ..
+    }): State<AppState>,
+) -> JrnlResult<Json<CursorPaginatedResponse<StrippedEntry>>> {
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let filter = filter_params.filter()?;
+    let sort = filter_params.sort;
+
+    let scope = cursor_signer.scope(user.id, CursorList::Entries);
+    let cursor = scope.open::<EntrySortKey>(params.cursor.as_deref())?;
+    let cursor = match (cursor, params.at) {
+        (Some(_), Some(_)) => return Err(JrnlError::InvalidJump),
+        (None, Some(at)) => Some(sort.cursor_at(at).ok_or(JrnlError::InvalidJump)?),
+        (cursor, None) => cursor,
+    };
+
+    if cursor.is_some_and(|cursor| !sort.accepts(cursor.key)) {
+        return Err(JrnlError::InvalidCursor);
+    }
+
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    // hidden scales can't be filtered or sorted on in sql. sorting on them would mean opening every one of them,
+    // filtering on them walks the list a batch at a time until the page is full
+    let in_app = (sort.by_scale() || filter.by_scale())
+        && entry_service
+            .has_hidden_scales(&user)
+            .await
+            .map_err(DatabaseError)?;
+
+    if in_app && sort.by_scale() {
+        return Err(JrnlError::HiddenScaleSort);
+    }
+
+    let (entries, scan_stopped_at) = if in_app {
+        let user_key = user_key_service
+            .get_or_create_user_key(&user, &*key_provider)
+            .await
+            .map_err(JrnlError::EntryDecryptionFailed)?;
+
+        scale_filtered_entries(
+            &user,
+            &filter,
+            sort,
+            cursor,
+            limit,
+            &entry_service,
+            user_key,
+        )
+        .await?
+    } else {
+        let rows = entry_service
+            .get_paginated_trimmed_entries(
+                &user,
+                &filter,
+                sort,
+                cursor.as_ref(),
+                Some(i64::from(limit)),
+            )
+            .await
+            .map_err(DatabaseError)?;
+
+        // only hidden scales need the user key
+        let user_key = if rows.iter().any(StrippedEntryRow::metadata_hidden) {
+            let user_key = user_key_service
+                .get_or_create_user_key(&user, &*key_provider)
+                .await
+                .map_err(JrnlError::EntryDecryptionFailed)?;
+            Some(user_key)
+        } else {
+            None
+        };
+
+        (reveal_stripped_entries(rows, user_key).await?, None)
+    };
+
+    let response =
+        CursorPaginatedResponse::new(entries, limit as usize, cursor.as_ref(), &scope, |entry| {
+            (sort.key(entry), entry.id)
+        });
+
+    Ok(Json(match scan_stopped_at {
+        Some(scan_stopped_at) => response.continue_from(&scan_stopped_at, &scope),
+        None => response,
+    }))
+}
+
+// a page of entries whose (possibly hidden) scale is within the filter, walking the list from the cursor a batch at
+// a time. only ever used when ordering by date. if the batch limit runs out before the page fills, the page is
+// returned short along with a cursor at the last entry that was looked at
+async fn scale_filtered_entries(
+    user: &User,
+    filter: &EntryFilter,
+    sort: EntrySort,
+    cursor: Option<Cursor<EntrySortKey>>,
+    limit: u32,
+    entry_service: &EntryService,
+    user_key: UserKey,
+) -> JrnlResult<(Vec<StrippedEntry>, Option<Cursor<EntrySortKey>>)> {
+    let backward = cursor.is_some_and(|cursor| cursor.is_backward());
+    let mut batch_cursor = cursor;
+    let mut entries = Vec::new();
+    for _ in 0..SCALE_FILTER_MAX_BATCHES {
+        let mut rows = entry_service
+            .get_paginated_trimmed_entries(
+                user,
+                &EntryFilter {
+                    min_scale: None,
+                    max_scale: None,
+                    ..*filter
+                },
+                sort,
+                batch_cursor.as_ref(),
+                Some(i64::from(SCALE_FILTER_BATCH)),
+            )
+            .await
+            .map_err(DatabaseError)?;
+
+        let exhausted = rows.len() <= SCALE_FILTER_BATCH as usize;
+        rows.truncate(SCALE_FILTER_BATCH as usize);
+        batch_cursor = rows.last().map(|row| {
+            let key = EntrySortKey::Date(EntryDateKey {
+                date: row.date,
+                created_at: row.created_at,
+            });
+
+            if backward {
+                Cursor::backward(key, row.id)
+            } else {
+                Cursor::forward(key, row.id)
+            }
+        });
+
+        entries.extend(
+            reveal_stripped_entries(rows, Some(user_key.clone()))
+                .await?
+                .into_iter()
+                .filter(|entry| filter.matches_scale(entry.emotion_scale)),
+        );
+
+        if exhausted || entries.len() > limit as usize {
+            entries.truncate(limit as usize + 1);
+            return Ok((entries, None));
+        }
+    }
+
+    Ok((entries, batch_cursor))
+}
+
+// hidden scales are all opened in one go
+async fn reveal_stripped_entries(
+    rows: Vec<StrippedEntryRow>,
+    user_key: Option<UserKey>,
+) -> JrnlResult<Vec<StrippedEntry>> {
+    spawn_blocking(move || {
+        rows.into_iter()
+            .map(|row| row.reveal(user_key.as_ref()))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+    cursor: Option<String>,
+    limit: Option<u32>,
+}
+
+// today's entry isn't indexed until it rolls over, and end to end encrypted entries are never indexed
+async fn search_entries(
+    user: User,
+    Query(params): Query<SearchParams>,
     entry_service: EntryService,
-    State(AppState { master_key, .. }): State<AppState>,
+    user_key_service: UserKeyService,
+    State(AppState {
+        key_provider,
+        cursor_signer,
+        ..
+    }): State<AppState>,
 ) -> JrnlResult<Json<CursorPaginatedResponse<StrippedEntry>>> {
     let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let scope = cursor_signer.scope(user.id, CursorList::Search);
+    let cursor = scope.open::<EntryDateKey>(params.cursor.as_deref())?;
+
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
 
-    let cursor = params.cursor.unwrap_or_default();
-    encrypt_active_entries_except_today(&user, &entry_service, master_key).await?;
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
 
-    let mut entries = entry_service
-        .get_paginated_trimmed_entries(&user, &cursor, i64::from(limit))
+    let index_key = SearchIndexKey::derive(&user_key)?;
+    let tokens = index_key.query_tokens(&params.q);
+    if tokens.is_empty() {
+        return Ok(Json(CursorPaginatedResponse::empty()));
+    }
+
+    let rows = entry_service
+        .search_entries(
+            &user,
+            index_key.version,
+            &tokens,
+            &cursor.unwrap_or(Cursor::forward(EntryDateKey::LATEST, Uuid::max())),
+            i64::from(limit),
+        )
         .await
         .map_err(DatabaseError)?;
 
-    let has_more = entries.len() > limit as usize;
-    if has_more {
-        entries.pop();
+    let entries = reveal_stripped_entries(rows, Some(user_key)).await?;
+
+    Ok(Json(CursorPaginatedResponse::new(
+        entries,
+        limit as usize,
+        cursor.as_ref(),
+        &scope,
+        |entry| (EntryDateKey::of(entry), entry.id),
+    )))
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    format: Option<ExportFormat>,
+}
+
+async fn export_entries(
+    user: User,
+    Query(params): Query<ExportParams>,
+    entry_service: EntryService,
+    export_service: ExportService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<impl IntoResponse> {
+    let format = params.format.unwrap_or(ExportFormat::Json);
+
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let disposition = format!(
+        "attachment; filename=\"jrnl-export-{}.{}\"",
+        Utc::now().date_naive(),
+        format.extension()
+    );
+    let body: Body = export_service.export_entries(user, user_key, key_provider, format);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+#[derive(Deserialize)]
+struct StatsParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+// the range ends today in the user's timezone unless told otherwise, without a start it covers the whole journal
+async fn get_stats(
+    user: User,
+    Query(params): Query<StatsParams>,
+    stats_service: StatsService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<MoodStats>> {
+    let today = user.current_date_by_timezone();
+    let range = StatsRange::new(params.from, params.to.map_or(today, |to| to.min(today)))?;
+
+    if !stats_service
+        .has_hidden_scales(&user, range)
+        .await
+        .map_err(DatabaseError)?
+    {
+        return Ok(Json(
+            stats_service
+                .get_mood_stats(&user, range)
+                .await
+                .map_err(DatabaseError)?,
+        ));
     }
 
-    let next_cursor = match (has_more, entries.last()) {
-        (true, Some(last_entry)) => Some(Cursor {
-            id: last_entry.id,
-            date: last_entry.date,
-        }),
-        _ => None,
+    let rows = stats_service
+        .get_scale_rows(&user, range.window_start, range.to)
+        .await
+        .map_err(DatabaseError)?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    spawn_blocking(move || {
+        rows.iter()
+            .map(|row| Ok((row.date, row.reveal(Some(&user_key))?)))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|scales| MoodStats::from_scales(range, scales))
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map(Json)
+    .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+#[derive(Deserialize)]
+struct CalendarParams {
+    year: i32,
+    month: Option<u32>,
+}
+
+// inclusive, the whole year when there's no month
+fn calendar_period(year: i32, month: Option<u32>) -> Option<(NaiveDate, NaiveDate)> {
+    let Some(month) = month else {
+        return Some((
+            NaiveDate::from_ymd_opt(year, 1, 1)?,
+            NaiveDate::from_ymd_opt(year, 12, 31)?,
+        ));
     };
 
-    Ok(Json(CursorPaginatedResponse {
-        items: entries,
-        next_cursor,
-        has_more,
-    }))
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let end = start.checked_add_months(Months::new(1))?.pred_opt()?;
+
+    Some((start, end))
+}
+
+// every day of the period in one go, enough for a heatmap without paging through the whole journal
+async fn get_calendar(
+    user: User,
+    Query(params): Query<CalendarParams>,
+    stats_service: StatsService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Calendar>> {
+    let (start, end) =
+        calendar_period(params.year, params.month).ok_or(JrnlError::InvalidCalendarPeriod)?;
+
+    let rows = stats_service
+        .get_scale_rows(&user, Some(start), end)
+        .await
+        .map_err(DatabaseError)?;
+
+    // only hidden scales need the user key
+    let user_key = if rows.iter().any(ScaleRow::metadata_hidden) {
+        let user_key = user_key_service
+            .get_or_create_user_key(&user, &*key_provider)
+            .await
+            .map_err(JrnlError::EntryDecryptionFailed)?;
+        Some(user_key)
+    } else {
+        None
+    };
+
+    spawn_blocking(move || {
+        rows.iter()
+            .map(|row| Ok((row.date, row.id, row.reveal(user_key.as_ref())?)))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|days| Calendar::new(start, end, days))
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map(Json)
+    .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+// the same month and day in every earlier year, today being the user's today
+async fn get_on_this_day_entries(
+    user: User,
+    entry_service: EntryService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<DecryptedEntry>>> {
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    let entries = entry_service
+        .get_entries_on_this_day(&user, user.current_date_by_timezone())
+        .await
+        .map_err(DatabaseError)?;
+
+    if entries.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    decrypt_entries(entries, &*key_provider, &user_key)
+        .await
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+#[derive(Deserialize)]
+struct MemoryParams {
+    min_scale: Option<f32>,
+}
+
+// one past entry picked at random, `min_scale` keeps it to the better days
+async fn get_random_memory(
+    user: User,
+    Query(params): Query<MemoryParams>,
+    entry_service: EntryService,
+    stats_service: StatsService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Option<DecryptedEntry>>> {
+    if let Some(min_scale) = params.min_scale {
+        validate_emotion_scale(min_scale)?;
+    }
+
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    // hidden scales are compared after they're opened, never in sql
+    let entry = match params.min_scale {
+        Some(min_scale)
+            if entry_service
+                .has_hidden_scales(&user)
+                .await
+                .map_err(DatabaseError)? =>
+        {
+            let Some(id) =
+                random_hidden_scale_entry_id(&user, min_scale, &stats_service, &user_key).await?
+            else {
+                return Ok(Json(None));
+            };
+
+            entry_service.get_entry_maybe(&user, &id).await?
+        }
+        min_scale => entry_service
+            .get_random_entry(&user, min_scale)
+            .await
+            .map_err(DatabaseError)?,
+    };
+
+    let Some(entry) = entry else {
+        return Ok(Json(None));
+    };
+
+    decrypt_entries(vec![entry], &*key_provider, &user_key)
+        .await
+        .map(|entries| Json(entries.into_iter().next()))
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+// how many random batches are opened looking for a memory at or above a hidden scale
+const MEMORY_SCAN_BATCHES: usize = 4;
+
+// every batch is a fresh random sample and the pick is random among its matches, so every match is equally likely.
+// matches too rare to turn up in a few batches get no memory rather than a scan of the whole journal
+async fn random_hidden_scale_entry_id(
+    user: &User,
+    min_scale: f32,
+    stats_service: &StatsService,
+    user_key: &UserKey,
+) -> JrnlResult<Option<Uuid>> {
+    for _ in 0..MEMORY_SCAN_BATCHES {
+        let rows = stats_service
+            .get_random_scale_rows(user, min_scale, i64::from(SCALE_FILTER_BATCH))
+            .await
+            .map_err(DatabaseError)?;
+        let exhausted = rows.len() < SCALE_FILTER_BATCH as usize;
+
+        let scales_key = user_key.clone();
+        let ids = spawn_blocking(move || {
+            rows.iter()
+                .filter_map(|row| match row.reveal(Some(&scales_key)) {
+                    Ok(emotion_scale) if emotion_scale >= min_scale => Some(Ok(row.id)),
+                    Ok(_) => None,
+                    Err(why) => Some(Err(why)),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(JrnlError::EntryDecryptionFailed)?
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+        if let Some(id) = ids.choose(&mut rand::thread_rng()) {
+            return Ok(Some(*id));
+        }
+
+        if exhausted {
+            break;
+        }
+    }
+
+    Ok(None)
+}
+
+// whole journals from other apps, well past the global body limit
+const IMPORT_BODY_LIMIT: usize = 1024 * 1024 * 16;
+const MAX_IMPORT_RECORDS: usize = 20_000;
+const DEFAULT_IMPORT_EMOTION_SCALE: f32 = 5.0;
+
+#[derive(Deserialize)]
+struct ImportParams {
+    format: ImportFormat,
+    #[serde(default)]
+    conflict: ImportConflict,
+    #[serde(default)]
+    dry_run: bool,
+    // used for records without a scale of their own
+    default_emotion_scale: Option<f32>,
+    date_column: Option<String>,
+    text_column: Option<String>,
+    emotion_scale_column: Option<String>,
+    date_format: Option<String>,
+    #[serde(default)]
+    text_is_html: bool,
+}
+
+async fn import_entries(
+    user: User,
+    Query(params): Query<ImportParams>,
+    entry_service: EntryService,
+    import_service: ImportService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    body: Bytes,
+) -> JrnlResult<Json<ImportReport>> {
+    // imported text is plaintext by definition, end to end encrypted journals have to import on the client
+    if user.e2e_enabled {
+        return Err(JrnlError::PlaintextNotAllowed);
+    }
+
+    let default_emotion_scale = params
+        .default_emotion_scale
+        .unwrap_or(DEFAULT_IMPORT_EMOTION_SCALE);
+    validate_emotion_scale(default_emotion_scale)?;
+
+    let input =
+        String::from_utf8(body.to_vec()).map_err(|why| JrnlError::InvalidImportFile(why.into()))?;
+    let mapping = CsvMapping {
+        date_column: params.date_column,
+        text_column: params.text_column,
+        emotion_scale_column: params.emotion_scale_column,
+        date_format: params.date_format,
+        text_is_html: params.text_is_html,
+    };
+    let timezone = user.timezone();
+    let records = spawn_blocking(move || import::parse(params.format, &input, timezone, &mapping))
+        .await
+        .map_err(Into::<anyhow::Error>::into)?
+        .map_err(JrnlError::InvalidImportFile)?;
+
+    if records.len() > MAX_IMPORT_RECORDS {
+        return Err(JrnlError::TooManyEntries);
+    }
+
+    let today = user.current_date_by_timezone();
+    let mut results = Vec::new();
+    let mut importable = Vec::with_capacity(records.len());
+    for record in records {
+        match record {
+            Ok(record) if record.date >= today => results.push(ImportResult {
+                positions: vec![record.position],
+                date: Some(record.date),
+                status: ImportStatus::Skipped,
+                entry_id: None,
+                reason: Some("only past days can be imported".to_string()),
+                excerpt: None,
+            }),
+            Ok(record) => importable.push(record),
+            Err(why) => results.push(ImportResult::failed(why)),
+        }
+    }
+
+    // past days still waiting in active entries would otherwise not count as conflicts
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let entries = import::import_entries(importable, default_emotion_scale);
+    results.extend(
+        import_service
+            .import_entries(&user, &user_key, entries, params.conflict, params.dry_run)
+            .await?,
+    );
+
+    Ok(Json(ImportReport::new(params.dry_run, results)))
 }
 
 async fn get_entry(
     user: User,
     Path(id): Path<Uuid>,
     entry_service: EntryService,
-    State(AppState { master_key, .. }): State<AppState>,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
 ) -> JrnlResult<Json<Option<DecryptedEntry>>> {
     let Some(encrypted_entry) = entry_service.get_entry_maybe(&user, &id).await? else {
         return Ok(Json(None));
     };
 
-    let decrypted_entry = spawn_blocking(move || encrypted_entry.decrypt(&master_key))
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    if encrypted_entry.client_encrypted {
+        return encrypted_entry
+            .into_client_encrypted(&user_key)
+            .map(|entry| Json(Some(entry)))
+            .map_err(JrnlError::EntryDecryptionFailed);
+    }
+
+    let content_key = encrypted_entry
+        .unwrap_content_key(&*key_provider, &user_key)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let decrypted_entry = spawn_blocking(move || encrypted_entry.decrypt(&content_key, &user_key))
         .await
         .map_err(Into::<anyhow::Error>::into)
         .map_err(JrnlError::EntryDecryptionFailed)??;
@@ -133,21 +847,94 @@ async fn get_entry(
 async fn get_today_entry(
     user: User,
     entry_service: EntryService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
 ) -> JrnlResult<Json<Option<ActiveEntry>>> {
-    entry_service
-        .get_user_daily_entry_maybe(&user)
+    let Some(encrypted_entry) = entry_service.get_user_daily_entry_maybe(&user).await? else {
+        return Ok(Json(None));
+    };
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
         .await
-        .map(Json)
-        .map_err(Into::into)
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let entry = spawn_blocking(move || encrypted_entry.decrypt(&user_key))
+        .await
+        .map_err(Into::<anyhow::Error>::into)
+        .map_err(JrnlError::EntryDecryptionFailed)?
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    Ok(Json(Some(entry)))
+}
+
+// oldest first
+async fn get_today_entries(
+    user: User,
+    entry_service: EntryService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<ActiveEntry>>> {
+    let encrypted_entries = entry_service.get_user_daily_entries(&user).await?;
+    if encrypted_entries.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    spawn_blocking(move || {
+        encrypted_entries
+            .iter()
+            .map(|entry| entry.decrypt(&user_key))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map(Json)
+    .map_err(JrnlError::EntryDecryptionFailed)
 }
 
 #[derive(Deserialize)]
 struct UpdateEntryPayload {
-    emotion_scale: f32,
+    #[serde(default)]
+    emotion_scale: Option<f32>,
+    // takes the scale from today's check-ins instead, see `today_checkin_scale`
+    #[serde(default)]
+    scale_from_checkins: bool,
     #[serde(default, deserialize_with = "sanitize_html_string")]
     text: Option<String>,
+    #[serde(default, with = "base64_bytes::option")]
+    ciphertext: Option<Vec<u8>>,
     #[serde(default)]
     ephemeral: bool,
+    // the daily prompt being answered, if any
+    prompt_id: Option<Uuid>,
+}
+
+// e2e journals must never send plaintext, everyone else has no business sending ciphertext
+const fn validate_entry_body(
+    user: &User,
+    text: Option<&String>,
+    ciphertext: Option<&Vec<u8>>,
+) -> JrnlResult<()> {
+    match (user.e2e_enabled, text, ciphertext) {
+        (true, Some(_), _) => Err(JrnlError::PlaintextNotAllowed),
+        (false, _, Some(_)) => Err(JrnlError::E2eNotEnabled),
+        _ => Ok(()),
+    }
+}
+
+// hidden scales never reach the table's check constraint, so the range is checked here for everyone
+fn validate_emotion_scale(emotion_scale: f32) -> JrnlResult<()> {
+    if (0.0..=10.0).contains(&emotion_scale) {
+        Ok(())
+    } else {
+        Err(JrnlError::InvalidEmotionScale)
+    }
 }
 
 #[allow(clippy::unnecessary_wraps)]
@@ -167,61 +954,718 @@ fn sanitize_html_string<'de, D: serde::Deserializer<'de>>(
     Ok(Some(cleaned))
 }
 
+const MAX_DAILY_ENTRIES: i64 = 20;
+
+// which of today's entries a write goes to
+enum DailyEntryTarget {
+    // the most recently written one, or a new one if there's none yet
+    Latest,
+    New,
+    Existing(Uuid),
+}
+
 async fn update_today_entry(
     user: User,
     entry_service: EntryService,
+    prompt_service: PromptService,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
     JsonExtractor(payload): JsonExtractor<UpdateEntryPayload>,
 ) -> JrnlResult<Json<ActiveEntry>> {
-    entry_service
-        .update_or_create_daily_entry(
-            &user,
-            payload.emotion_scale,
-            payload.text,
-            payload.ephemeral,
+    save_today_entry(
+        user,
+        DailyEntryTarget::Latest,
+        &entry_service,
+        &prompt_service,
+        &checkin_service,
+        &user_key_service,
+        &*key_provider,
+        payload,
+    )
+    .await
+    .map(Json)
+}
+
+async fn create_today_entry(
+    user: User,
+    entry_service: EntryService,
+    prompt_service: PromptService,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<UpdateEntryPayload>,
+) -> JrnlResult<Json<ActiveEntry>> {
+    save_today_entry(
+        user,
+        DailyEntryTarget::New,
+        &entry_service,
+        &prompt_service,
+        &checkin_service,
+        &user_key_service,
+        &*key_provider,
+        payload,
+    )
+    .await
+    .map(Json)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn update_today_entry_by_id(
+    user: User,
+    Path(id): Path<Uuid>,
+    entry_service: EntryService,
+    prompt_service: PromptService,
+    checkin_service: CheckinService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<UpdateEntryPayload>,
+) -> JrnlResult<Json<ActiveEntry>> {
+    save_today_entry(
+        user,
+        DailyEntryTarget::Existing(id),
+        &entry_service,
+        &prompt_service,
+        &checkin_service,
+        &user_key_service,
+        &*key_provider,
+        payload,
+    )
+    .await
+    .map(Json)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn save_today_entry(
+    user: User,
+    target: DailyEntryTarget,
+    entry_service: &EntryService,
+    prompt_service: &PromptService,
+    checkin_service: &CheckinService,
+    user_key_service: &UserKeyService,
+    key_provider: &dyn KeyProvider,
+    payload: UpdateEntryPayload,
+) -> JrnlResult<ActiveEntry> {
+    validate_entry_body(&user, payload.text.as_ref(), payload.ciphertext.as_ref())?;
+    match (payload.emotion_scale, payload.scale_from_checkins) {
+        (Some(emotion_scale), false) => validate_emotion_scale(emotion_scale)?,
+        (None, true) => {}
+        _ => return Err(JrnlError::InvalidEmotionScaleSource),
+    }
+
+    if let Some(prompt_id) = payload.prompt_id {
+        let prompt_exists = Prompt::is_built_in(&prompt_id)
+            || prompt_service
+                .get_prompt(&user, &prompt_id)
+                .await
+                .map_err(DatabaseError)?
+                .is_some();
+
+        if !prompt_exists {
+            return Err(JrnlError::InvalidPrompt);
+        }
+    }
+
+    // the envelope is bound to the row id, so an existing entry keeps its own
+    let existing = match target {
+        DailyEntryTarget::Latest => entry_service.get_user_daily_entry_maybe(&user).await?,
+        DailyEntryTarget::New => None,
+        DailyEntryTarget::Existing(id) => Some(
+            entry_service
+                .get_user_daily_entry_by_id(&user, &id)
+                .await?
+                .ok_or(JrnlError::NoResultsFound)?,
+        ),
+    };
+
+    if existing.is_none()
+        && entry_service
+            .get_user_daily_entries_count(&user)
+            .await
+            .map_err(DatabaseError)?
+            >= MAX_DAILY_ENTRIES
+    {
+        return Err(JrnlError::CannotCreateMoreDailyEntries);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let emotion_scale = match payload.emotion_scale {
+        Some(emotion_scale) => emotion_scale,
+        None => today_checkin_scale(&user, checkin_service, &user_key).await?,
+    };
+
+    let entry = ActiveEntry {
+        id: existing
+            .as_ref()
+            .map_or_else(Uuid::new_v4, |entry| entry.id),
+        author: user.id,
+        date: user.current_date_by_timezone(),
+        emotion_scale,
+        text: payload.text,
+        ciphertext: payload.ciphertext,
+        expiry: EntryService::daily_entry_expiry(&user),
+        ephemeral: payload.ephemeral,
+        prompt_id: payload.prompt_id,
+        created_at: existing.map_or_else(Utc::now, |entry| entry.created_at),
+    };
+
+    let (entry, encrypted_entry) = spawn_blocking(move || {
+        let encrypted_entry = entry.encrypt_active(&user_key, user.hide_metadata)?;
+        anyhow::Ok((entry, encrypted_entry))
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryEncryptionFailed)?
+    .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    // only misses when the entry stopped being today's while it was being sealed
+    let saved = entry_service
+        .update_or_create_daily_entry(&encrypted_entry)
+        .await?
+        .ok_or(JrnlError::NoResultsFound)?;
+
+    Ok(ActiveEntry {
+        expiry: saved.expiry,
+        created_at: saved.created_at,
+        ..entry
+    })
+}
+
+// summarized the same way a day's entries are. it's taken when the entry is saved, later check-ins don't change it
+async fn today_checkin_scale(
+    user: &User,
+    checkin_service: &CheckinService,
+    user_key: &UserKey,
+) -> JrnlResult<f32> {
+    let today = user.current_date_by_timezone();
+    let checkins = checkin_service
+        .get_checkins(
+            user,
+            user.start_of_day(today),
+            user.start_of_day(today.succ_opt().unwrap_or(today)),
         )
         .await
+        .map_err(DatabaseError)?;
+
+    let scales = decrypt_checkins(checkins, user_key.clone())
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?
+        .iter()
+        .map(|checkin| checkin.emotion_scale)
+        .collect::<Vec<_>>();
+
+    day_summary(&scales).ok_or(JrnlError::NoCheckinsToday)
+}
+
+// an empty text clears it, leaving it out keeps the current one
+#[allow(clippy::option_option)]
+fn sanitize_html_string_patch<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<String>>, D::Error> {
+    sanitize_html_string(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[allow(clippy::option_option)]
+struct PatchEntryPayload {
+    #[serde(default)]
+    emotion_scale: Option<f32>,
+    #[serde(default, deserialize_with = "sanitize_html_string_patch")]
+    text: Option<Option<String>>,
+    #[serde(default, with = "base64_bytes::option")]
+    ciphertext: Option<Vec<u8>>,
+}
+
+async fn patch_entry(
+    user: User,
+    Path(id): Path<Uuid>,
+    entry_service: EntryService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<PatchEntryPayload>,
+) -> JrnlResult<Json<DecryptedEntry>> {
+    validate_entry_body(
+        &user,
+        payload.text.as_ref().and_then(Option::as_ref),
+        payload.ciphertext.as_ref(),
+    )?;
+    if let Some(emotion_scale) = payload.emotion_scale {
+        validate_emotion_scale(emotion_scale)?;
+    }
+
+    // an earlier day's entry might still be waiting in the active table
+    encrypt_active_entries_except_today(&user, &entry_service, &user_key_service, &*key_provider)
+        .await?;
+
+    let existing = entry_service
+        .get_entry_maybe(&user, &id)
+        .await?
+        .ok_or(JrnlError::NoResultsFound)?;
+
+    // clearing the text counts too, an end to end encrypted entry only ever gets new ciphertext
+    if payload.ciphertext.is_none()
+        && payload.text.is_some()
+        && (user.e2e_enabled || existing.client_encrypted)
+    {
+        return Err(JrnlError::PlaintextNotAllowed);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let content_key = if existing.client_encrypted {
+        None
+    } else {
+        let content_key = existing
+            .unwrap_content_key(&*key_provider, &user_key)
+            .await
+            .map_err(JrnlError::EntryDecryptionFailed)?;
+        Some(content_key)
+    };
+
+    let (entry, search_tokens, decrypted_entry) = spawn_blocking(move || -> JrnlResult<_> {
+        let current = content_key
+            .as_ref()
+            .map_or_else(
+                || existing.clone().into_client_encrypted(&user_key),
+                |content_key| existing.decrypt(content_key, &user_key),
+            )
+            .map_err(JrnlError::EntryDecryptionFailed)?;
+
+        let emotion_scale = payload.emotion_scale.unwrap_or(current.emotion_scale);
+
+        // sending ciphertext or text switches the entry over, otherwise it keeps whatever it had
+        let (text, ciphertext) = match (payload.ciphertext, payload.text) {
+            (Some(ciphertext), _) => (None, Some(ciphertext)),
+            (None, Some(text)) => (text, None),
+            (None, None) => (current.text, current.ciphertext),
+        };
+
+        let entry = match &ciphertext {
+            Some(ciphertext) => EncryptedEntry::client_encrypted(
+                existing.id,
+                existing.author,
+                existing.date,
+                emotion_scale,
+                ciphertext.clone(),
+                &user_key,
+                user.hide_metadata,
+            ),
+            None => EncryptedEntry::seal(
+                existing.id,
+                existing.author,
+                existing.date,
+                emotion_scale,
+                text.as_deref(),
+                &user_key,
+                user.hide_metadata,
+            ),
+        }
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+        let search_tokens = if ciphertext.is_none() {
+            let index_key = SearchIndexKey::derive(&user_key)?;
+            Some(index_key.entry_tokens(text.as_deref().unwrap_or_default()))
+        } else {
+            None
+        };
+
+        let decrypted_entry = DecryptedEntry {
+            id: existing.id,
+            author: existing.author,
+            date: existing.date,
+            emotion_scale,
+            text,
+            ciphertext,
+            prompt_id: existing.prompt_id,
+            created_at: existing.created_at,
+        };
+
+        Ok((entry, search_tokens, decrypted_entry))
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)??;
+
+    if !entry_service
+        .update_entry(&user, &entry, search_tokens.as_ref())
+        .await?
+    {
+        return Err(JrnlError::NoResultsFound);
+    }
+
+    Ok(Json(decrypted_entry))
+}
+
+async fn delete_entry(
+    user: User,
+    Path(id): Path<Uuid>,
+    entry_service: EntryService,
+) -> JrnlResult<StatusCode> {
+    if entry_service.delete_entry(&user, &id).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
+
+const MAX_ENTRY_TAGS: usize = 20;
+
+#[derive(Deserialize)]
+struct EntryTagsPayload {
+    tag_ids: Vec<Uuid>,
+}
+
+async fn get_entry_tags(
+    user: User,
+    Path(id): Path<Uuid>,
+    tag_service: TagService,
+) -> JrnlResult<Json<Vec<Uuid>>> {
+    tag_service
+        .get_entry_tag_ids(&user, &id)
+        .await
         .map(Json)
         .map_err(Into::into)
 }
 
+// uploading, downloading and deleting attachments lives in the attachment controller
+async fn get_entry_attachments(
+    user: User,
+    Path(id): Path<Uuid>,
+    attachment_service: AttachmentService,
+) -> JrnlResult<Json<Vec<Attachment>>> {
+    let attachments = attachment_service
+        .get_entry_attachments(&user, &id)
+        .await
+        .map_err(DatabaseError)?;
+
+    Ok(Json(attachments.into_iter().map(Into::into).collect()))
+}
+
+// replaces the entry's tags, works for today's entry as well as past ones
+async fn put_entry_tags(
+    user: User,
+    Path(id): Path<Uuid>,
+    tag_service: TagService,
+    JsonExtractor(payload): JsonExtractor<EntryTagsPayload>,
+) -> JrnlResult<Json<Vec<Uuid>>> {
+    let mut tag_ids = payload.tag_ids;
+    tag_ids.sort_unstable();
+    tag_ids.dedup();
+
+    if tag_ids.len() > MAX_ENTRY_TAGS {
+        return Err(JrnlError::TooManyEntryTags);
+    }
+
+    if !tag_service.set_entry_tags(&user, &id, &tag_ids).await? {
+        return Err(JrnlError::NoResultsFound);
+    }
+
+    Ok(Json(tag_ids))
+}
+
+async fn get_entry_revisions(
+    user: User,
+    Path(id): Path<Uuid>,
+    entry_revision_service: EntryRevisionService,
+) -> JrnlResult<Json<Vec<EntryRevisionSummary>>> {
+    entry_revision_service
+        .get_revisions(&user, &id)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+async fn get_entry_revision(
+    user: User,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+    entry_revision_service: EntryRevisionService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Option<DecryptedEntryRevision>>> {
+    let Some(revision) = entry_revision_service
+        .get_revision_maybe(&user, &id, &revision_id)
+        .await?
+    else {
+        return Ok(Json(None));
+    };
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let encrypted_entry = revision.as_entry();
+    let content_key = if encrypted_entry.client_encrypted {
+        None
+    } else {
+        let content_key = encrypted_entry
+            .unwrap_content_key(&*key_provider, &user_key)
+            .await
+            .map_err(JrnlError::EntryDecryptionFailed)?;
+        Some(content_key)
+    };
+
+    let decrypted_entry = spawn_blocking(move || {
+        content_key.as_ref().map_or_else(
+            || encrypted_entry.clone().into_client_encrypted(&user_key),
+            |content_key| encrypted_entry.decrypt(content_key, &user_key),
+        )
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    Ok(Json(Some(DecryptedEntryRevision {
+        id: revision.id,
+        entry_id: revision.entry_id,
+        date: decrypted_entry.date,
+        emotion_scale: decrypted_entry.emotion_scale,
+        text: decrypted_entry.text,
+        ciphertext: decrypted_entry.ciphertext,
+        created_at: revision.created_at,
+    })))
+}
+
+async fn restore_entry_revision(
+    user: User,
+    Path((id, revision_id)): Path<(Uuid, Uuid)>,
+    entry_revision_service: EntryRevisionService,
+) -> JrnlResult<StatusCode> {
+    if entry_revision_service
+        .restore_revision(&user, &id, &revision_id)
+        .await?
+    {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
+
+#[derive(Deserialize)]
+struct BulkDeletePayload {
+    ids: Vec<Uuid>,
+}
+
+#[derive(Serialize)]
+struct BulkDeleteResponse {
+    deleted: u64,
+}
+
+async fn bulk_delete_entries(
+    user: User,
+    entry_service: EntryService,
+    JsonExtractor(payload): JsonExtractor<BulkDeletePayload>,
+) -> JrnlResult<Json<BulkDeleteResponse>> {
+    if payload.ids.len() > 365 {
+        return Err(JrnlError::TooManyEntries);
+    }
+
+    let deleted = entry_service.delete_entries(&user, &payload.ids).await?;
+    Ok(Json(BulkDeleteResponse { deleted }))
+}
+
 #[derive(Deserialize)]
 struct MobilePastEntry {
+    // echoed back with the entry's result, so the app can match results to its local entries
+    #[serde(default)]
+    client_id: Option<String>,
     date: NaiveDate,
     emotion_scale: f32,
     #[serde(default, deserialize_with = "sanitize_html_string")]
     text: Option<String>,
+    #[serde(default, with = "base64_bytes::option")]
+    ciphertext: Option<Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+struct PutMobileEntriesParams {
+    // nothing is written unless every entry can be
+    #[serde(default)]
+    atomic: bool,
+}
+
+#[derive(Serialize)]
+struct MobileEntryResult {
+    client_id: Option<String>,
+    index: usize,
+    date: NaiveDate,
+    status: EntryUploadStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PutMobileEntriesResponse {
+    inserted: usize,
+    results: Vec<MobileEntryResult>,
+}
+
+// the same local entry always gets the same id, so uploading it again is caught as a duplicate instead of
+// being written twice. entries sent without a client id are known by their day and content instead
+fn uploaded_entry_id(user: &User, entry: &MobilePastEntry) -> Uuid {
+    let digest = entry
+        .client_id
+        .as_ref()
+        .map_or_else(
+            || {
+                let (kind, content) = match (&entry.text, &entry.ciphertext) {
+                    (_, Some(ciphertext)) => (b'c', ciphertext.as_slice()),
+                    (text, None) => (b't', text.as_deref().unwrap_or_default().as_bytes()),
+                };
+
+                Sha256::new()
+                    .chain_update(b"jrnl:uploaded_entry_content:")
+                    .chain_update(user.id.as_bytes())
+                    .chain_update(entry.date.to_string())
+                    .chain_update([kind])
+                    .chain_update(content)
+            },
+            |client_id| {
+                Sha256::new()
+                    .chain_update(b"jrnl:uploaded_entry:")
+                    .chain_update(user.id.as_bytes())
+                    .chain_update(client_id.as_bytes())
+            },
+        )
+        .finalize();
+
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+fn validate_mobile_entry(
+    user: &User,
+    entry: &MobilePastEntry,
+    id: Uuid,
+    today: NaiveDate,
+    ids: &mut HashSet<Uuid>,
+) -> Result<(), (EntryUploadStatus, String)> {
+    if entry.date >= today {
+        return Err((
+            EntryUploadStatus::Invalid,
+            "only past days can be uploaded".to_string(),
+        ));
+    }
+
+    validate_entry_body(user, entry.text.as_ref(), entry.ciphertext.as_ref())
+        .and_then(|()| validate_emotion_scale(entry.emotion_scale))
+        .map_err(|why| (EntryUploadStatus::Invalid, why.to_string()))?;
+
+    if !ids.insert(id) {
+        return Err((
+            EntryUploadStatus::Duplicate,
+            "another entry in this batch has the same id".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
+// every entry gets a result in the order it was sent, invalid ones no longer fail the whole batch
 async fn put_local_mobile_entries(
     user: User,
+    Query(params): Query<PutMobileEntriesParams>,
     entry_service: EntryService,
-    State(AppState { master_key, .. }): State<AppState>,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
     JsonExtractor(entries): JsonExtractor<Vec<MobilePastEntry>>,
-) -> JrnlResult<()> {
+) -> JrnlResult<Json<PutMobileEntriesResponse>> {
+    if entries.len() > 365 {
+        return Err(JrnlError::TooManyEntries);
+    }
+
     let today = user.current_date_by_timezone();
+    let mut ids = HashSet::new();
+    let mut results = Vec::with_capacity(entries.len());
+    let mut accepted = Vec::with_capacity(entries.len());
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let id = uploaded_entry_id(&user, &entry);
+        let rejection = validate_mobile_entry(&user, &entry, id, today, &mut ids).err();
 
-    let entries = entries
-        .into_iter()
-        .filter(|entry| entry.date < today)
-        .map(|entry| ActiveEntry {
-            id: Uuid::new_v4(),
-            author: user.id,
+        results.push(MobileEntryResult {
+            client_id: entry.client_id,
+            index,
             date: entry.date,
-            emotion_scale: entry.emotion_scale,
-            text: entry.text,
-            // this should never get hit
-            expiry: Utc::now() + Duration::days(30),
-            ephemeral: false,
-        })
-        .collect::<Vec<_>>();
+            status: rejection
+                .as_ref()
+                .map_or(EntryUploadStatus::Inserted, |(status, _)| *status),
+            entry_id: rejection.is_none().then_some(id),
+            reason: rejection.map(|(_, reason)| reason),
+        });
 
-    if entries.is_empty() {
-        return Ok(());
+        if results[index].entry_id.is_some() {
+            accepted.push((
+                index,
+                ActiveEntry {
+                    id,
+                    author: user.id,
+                    date: entry.date,
+                    emotion_scale: entry.emotion_scale,
+                    text: entry.text,
+                    ciphertext: entry.ciphertext,
+                    // this should never get hit
+                    expiry: Utc::now() + Duration::days(30),
+                    ephemeral: false,
+                    prompt_id: None,
+                    created_at: Utc::now(),
+                },
+            ));
+        }
     }
 
-    if entries.len() > 365 {
-        return Err(JrnlError::TooManyEntries);
+    let rejected = accepted.len() != results.len();
+    let statuses = if accepted.is_empty() {
+        Vec::new()
+    } else if params.atomic && rejected {
+        vec![EntryUploadStatus::Aborted; accepted.len()]
+    } else {
+        let user_key = user_key_service
+            .get_or_create_user_key(&user, &*key_provider)
+            .await
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+        entry_service
+            .insert_many_entries(
+                &user,
+                accepted.iter().map(|(_, entry)| entry.clone()).collect(),
+                user_key,
+                params.atomic,
+            )
+            .await?
+    };
+
+    for ((index, _), status) in accepted.iter().zip(statuses) {
+        let result = &mut results[*index];
+        result.status = status;
+
+        if status != EntryUploadStatus::Inserted {
+            result.entry_id = None;
+            result.reason = match status {
+                EntryUploadStatus::Duplicate => Some("this entry was already uploaded"),
+                EntryUploadStatus::EncryptionFailed => Some("failed to encrypt entry"),
+                EntryUploadStatus::Aborted => Some("another entry in this atomic batch failed"),
+                EntryUploadStatus::Inserted | EntryUploadStatus::Invalid => None,
+            }
+            .map(ToString::to_string);
+        }
     }
 
-    entry_service.insert_many_entries(entries, master_key).await
+    Ok(Json(PutMobileEntriesResponse {
+        inserted: results
+            .iter()
+            .filter(|result| result.status == EntryUploadStatus::Inserted)
+            .count(),
+        results,
+    }))
 }
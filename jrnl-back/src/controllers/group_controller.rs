@@ -6,18 +6,18 @@ use crate::{
         entry_service::EntryService,
         group_service::{GetGroupAndMembersBody, GroupService, SelfGroup},
     },
+    web::cursor::{Cursor, CursorList, CursorPaginatedResponse},
     AppState,
 };
 use axum::{
-    extract::{Path, Query},
+    extract::{Path, Query, State},
     http::StatusCode,
     routing::{delete, get, post},
     Json, Router,
 };
-use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::{Duration, NaiveDate};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{cmp::Reverse, collections::HashMap};
 use uuid::Uuid;
 
 pub fn groups_controller() -> Router<AppState> {
@@ -178,38 +178,35 @@ struct DayData {
 #[derive(Debug, Deserialize)]
 struct GetDaysDataParams {
     day_limit: Option<i64>,
-    #[serde(default, deserialize_with = "deserialize_base_date")]
-    before: Option<NaiveDate>,
-}
-
-fn deserialize_base_date<'de, D: serde::Deserializer<'de>>(
-    deserializer: D,
-) -> Result<Option<NaiveDate>, D::Error> {
-    let Some(encoded_date) = Option::<String>::deserialize(deserializer)? else {
-        return Ok(None);
-    };
-
-    let decoded_bytes = STANDARD
-        .decode(encoded_date)
-        .map_err(serde::de::Error::custom)?;
-    let date_string = String::from_utf8(decoded_bytes).map_err(serde::de::Error::custom)?;
-
-    NaiveDate::parse_from_str(&date_string, "%m/%d/%Y")
-        .map(Some)
-        .map_err(serde::de::Error::custom)
+    cursor: Option<String>,
 }
 
+// pages are runs of days rather than entries, so a cursor only holds the day its page stops short of.
+// going forward goes back in time, as long as some member wrote anything before the page
 async fn get_days_data_paginated(
     user: User,
     Query(params): Query<GetDaysDataParams>,
     Path(code): Path<String>,
     group_service: GroupService,
     entry_service: EntryService,
-) -> JrnlResult<Json<Vec<DayData>>> {
+    State(AppState { cursor_signer, .. }): State<AppState>,
+) -> JrnlResult<Json<CursorPaginatedResponse<DayData>>> {
     let day_limit = params.day_limit.unwrap_or(7).clamp(1, 30);
-    let before_date = params
-        .before
-        .unwrap_or_else(|| chrono::Utc::now().naive_utc().date());
+    let today = user.current_date_by_timezone();
+
+    let scope = cursor_signer.scope(user.id, CursorList::Groups);
+    let cursor = scope.open::<NaiveDate>(params.cursor.as_deref())?;
+    let (start_date, end_date) = match cursor {
+        None => (today - Duration::days(day_limit - 1), today),
+        Some(cursor) if cursor.is_backward() => (
+            cursor.key + Duration::days(1),
+            (cursor.key + Duration::days(day_limit)).min(today),
+        ),
+        Some(cursor) => (
+            cursor.key - Duration::days(day_limit),
+            cursor.key - Duration::days(1),
+        ),
+    };
 
     let group = group_service
         .get_joined_group_by_code(&user, &code)
@@ -224,13 +221,12 @@ async fn get_days_data_paginated(
         .map(|user| user.id)
         .collect::<Vec<_>>();
 
-    let start_date = before_date - Duration::days(day_limit - 1);
     let all_entries = entry_service
-        .get_multiple_users_entries_between_dates(&group_member_ids, &start_date, &before_date)
+        .get_multiple_users_entries_between_dates(&group_member_ids, &start_date, &end_date)
         .await
         .map_err(DatabaseError)?;
 
-    let entries_grouped_by_day = all_entries
+    let mut entries_grouped_by_day = all_entries
         .into_iter()
         .fold(HashMap::<NaiveDate, Vec<f32>>::new(), |mut acc, entry| {
             acc.entry(entry.date).or_default().push(entry.emotion_scale);
@@ -240,7 +236,20 @@ async fn get_days_data_paginated(
         .map(|(day, scales)| DayData { scales, day })
         .collect::<Vec<_>>();
 
-    Ok(Json(entries_grouped_by_day))
+    entries_grouped_by_day.sort_by_key(|day_data| Reverse(day_data.day));
+
+    let has_more = entry_service
+        .has_multiple_users_entries_before_date(&group_member_ids, &start_date)
+        .await
+        .map_err(DatabaseError)?;
+
+    Ok(Json(CursorPaginatedResponse {
+        items: entries_grouped_by_day,
+        next_cursor: has_more.then(|| scope.seal(&Cursor::forward(start_date, Uuid::nil()))),
+        prev_cursor: (end_date < today)
+            .then(|| scope.seal(&Cursor::backward(end_date, Uuid::nil()))),
+        has_more,
+    }))
 }
 
 async fn joined_groups(
@@ -1,4 +1,9 @@
+pub mod attachment_controller;
 pub mod auth_controller;
+pub mod checkin_controller;
 pub mod entry_controller;
 pub mod group_controller;
+pub mod prompt_controller;
+pub mod tag_controller;
+pub mod tracker_controller;
 pub mod user_controller;
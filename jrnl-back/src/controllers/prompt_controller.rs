@@ -0,0 +1,197 @@
+use crate::{
+    crypto::key_provider::KeyProvider,
+    error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
+    schemas::{
+        prompt::{EncryptedPrompt, Prompt},
+        user::User,
+    },
+    services::{
+        entry_service::EntryService, prompt_service::PromptService,
+        user_key_service::UserKeyService,
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, patch},
+    Json, Router,
+};
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+pub fn prompts_controller() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_prompts).post(create_prompt))
+        .route("/today", get(get_today_prompt))
+        .route("/:id", patch(update_prompt).delete(delete_prompt))
+}
+
+const MAX_PROMPTS: i64 = 100;
+const MAX_PROMPT_TEXT_LEN: usize = 280;
+
+#[derive(Deserialize)]
+struct PromptPayload {
+    text: String,
+}
+
+fn validate_prompt_text(text: &str) -> JrnlResult<&str> {
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > MAX_PROMPT_TEXT_LEN {
+        return Err(JrnlError::InvalidPromptText);
+    }
+
+    Ok(text)
+}
+
+// built in prompts first, then the user's own from oldest to newest
+async fn all_prompts(
+    user: &User,
+    prompt_service: &PromptService,
+    user_key_service: &UserKeyService,
+    key_provider: &dyn KeyProvider,
+) -> JrnlResult<Vec<Prompt>> {
+    let encrypted_prompts = prompt_service
+        .get_prompts(user)
+        .await
+        .map_err(DatabaseError)?;
+
+    let mut prompts = Prompt::built_in().collect::<Vec<_>>();
+    if encrypted_prompts.is_empty() {
+        return Ok(prompts);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(user, key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    let user_prompts = spawn_blocking(move || {
+        encrypted_prompts
+            .iter()
+            .map(|prompt| prompt.decrypt(&user_key))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    prompts.extend(user_prompts);
+    Ok(prompts)
+}
+
+async fn get_prompts(
+    user: User,
+    prompt_service: PromptService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<Prompt>>> {
+    all_prompts(&user, &prompt_service, &user_key_service, &*key_provider)
+        .await
+        .map(Json)
+}
+
+// once one of today's entries answers a prompt that's the one that sticks, the latest answer if there's several.
+// otherwise it's picked for the user and their date
+async fn get_today_prompt(
+    user: User,
+    entry_service: EntryService,
+    prompt_service: PromptService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Option<Prompt>>> {
+    let answered_prompt_id = entry_service
+        .get_user_daily_entries(&user)
+        .await?
+        .into_iter()
+        .rev()
+        .find_map(|entry| entry.prompt_id);
+
+    let prompts = all_prompts(&user, &prompt_service, &user_key_service, &*key_provider).await?;
+
+    let prompt = answered_prompt_id
+        .and_then(|id| prompts.iter().find(|prompt| prompt.id == id))
+        .or_else(|| Prompt::daily(user.id, user.current_date_by_timezone(), &prompts))
+        .cloned();
+
+    Ok(Json(prompt))
+}
+
+async fn create_prompt(
+    user: User,
+    prompt_service: PromptService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<PromptPayload>,
+) -> JrnlResult<Json<Prompt>> {
+    let text = validate_prompt_text(&payload.text)?;
+
+    let existing_prompts = prompt_service
+        .get_prompts_count(&user)
+        .await
+        .map_err(DatabaseError)?;
+
+    if existing_prompts >= MAX_PROMPTS {
+        return Err(JrnlError::CannotCreateMorePrompts);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let id = Uuid::new_v4();
+    let encrypted_text = EncryptedPrompt::seal_text(id, user.id, text, &user_key)
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    prompt_service
+        .create_prompt(&user, id, &encrypted_text)
+        .await
+        .map_err(DatabaseError)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+// built in prompts can't be changed, they're not found among the user's own
+async fn update_prompt(
+    user: User,
+    Path(id): Path<Uuid>,
+    prompt_service: PromptService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<PromptPayload>,
+) -> JrnlResult<Json<Prompt>> {
+    let text = validate_prompt_text(&payload.text)?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let encrypted_text = EncryptedPrompt::seal_text(id, user.id, text, &user_key)
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    prompt_service
+        .update_prompt(&user, &id, &encrypted_text)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn delete_prompt(
+    user: User,
+    Path(id): Path<Uuid>,
+    prompt_service: PromptService,
+) -> JrnlResult<StatusCode> {
+    if prompt_service.delete_prompt(&user, &id).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
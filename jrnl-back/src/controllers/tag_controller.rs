@@ -0,0 +1,145 @@
+use crate::{
+    error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
+    schemas::{
+        tag::{EncryptedTag, Tag},
+        user::User,
+    },
+    services::{tag_service::TagService, user_key_service::UserKeyService},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, patch},
+    Json, Router,
+};
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+pub fn tags_controller() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_tags).post(create_tag))
+        .route("/:id", patch(rename_tag).delete(delete_tag))
+}
+
+const MAX_TAGS: i64 = 100;
+const MAX_TAG_NAME_LEN: usize = 64;
+
+#[derive(Deserialize)]
+struct TagPayload {
+    name: String,
+}
+
+fn validate_tag_name(name: &str) -> JrnlResult<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TAG_NAME_LEN {
+        return Err(JrnlError::InvalidTagName);
+    }
+
+    Ok(name)
+}
+
+async fn get_tags(
+    user: User,
+    tag_service: TagService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<Tag>>> {
+    let tags = tag_service.get_tags(&user).await.map_err(DatabaseError)?;
+    if tags.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    spawn_blocking(move || {
+        tags.iter()
+            .map(|tag| tag.decrypt(&user_key))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })
+    .await
+    .map_err(Into::<anyhow::Error>::into)
+    .map_err(JrnlError::EntryDecryptionFailed)?
+    .map(Json)
+    .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn create_tag(
+    user: User,
+    tag_service: TagService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<TagPayload>,
+) -> JrnlResult<Json<Tag>> {
+    let name = validate_tag_name(&payload.name)?;
+
+    let existing_tags = tag_service
+        .get_tags_count(&user)
+        .await
+        .map_err(DatabaseError)?;
+
+    if existing_tags >= MAX_TAGS {
+        return Err(JrnlError::CannotCreateMoreTags);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let id = Uuid::new_v4();
+    let encrypted_name = EncryptedTag::seal_name(id, user.id, name, &user_key)
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    tag_service
+        .create_tag(&user, id, &encrypted_name)
+        .await
+        .map_err(DatabaseError)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn rename_tag(
+    user: User,
+    Path(id): Path<Uuid>,
+    tag_service: TagService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<TagPayload>,
+) -> JrnlResult<Json<Tag>> {
+    let name = validate_tag_name(&payload.name)?;
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let encrypted_name = EncryptedTag::seal_name(id, user.id, name, &user_key)
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    tag_service
+        .rename_tag(&user, &id, &encrypted_name)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn delete_tag(
+    user: User,
+    Path(id): Path<Uuid>,
+    tag_service: TagService,
+) -> JrnlResult<StatusCode> {
+    if tag_service.delete_tag(&user, &id).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    crypto::key_provider::KeyProvider,
+    error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
+    schemas::{
+        tracker::{
+            EncryptedTracker, EncryptedTrackerValue, Tracker, TrackerDayValue, TrackerKind,
+            TrackerValue,
+        },
+        user::User,
+        user_key::UserKey,
+    },
+    services::{
+        tracker_service::{
+            decrypt_tracker_values, decrypt_trackers, TrackerHistory, TrackerService,
+        },
+        user_key_service::UserKeyService,
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, patch, put},
+    Json, Router,
+};
+use chrono::{Days, NaiveDate};
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub fn trackers_controller() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_trackers).post(create_tracker))
+        .route("/values/:date", get(get_day_values))
+        .route("/:id", patch(update_tracker).delete(delete_tracker))
+        .route("/:id/history", get(get_tracker_history))
+        .route(
+            "/:id/values/:date",
+            put(set_tracker_value).delete(delete_tracker_value),
+        )
+}
+
+const MAX_TRACKERS: i64 = 50;
+const MAX_TRACKER_NAME_LEN: usize = 64;
+const MAX_TRACKER_UNIT_LEN: usize = 16;
+const MAX_TRACKER_RANGE_DAYS: i64 = 366;
+const DEFAULT_TRACKER_RANGE_DAYS: u64 = 30;
+
+#[derive(Deserialize)]
+struct CreateTrackerPayload {
+    name: String,
+    kind: TrackerKind,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+// the kind is fixed once created, values already recorded are read by it
+#[derive(Deserialize)]
+struct UpdateTrackerPayload {
+    name: String,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+fn validate_tracker_name(name: &str) -> JrnlResult<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TRACKER_NAME_LEN {
+        return Err(JrnlError::InvalidTrackerName);
+    }
+
+    Ok(name)
+}
+
+fn validate_tracker_unit(kind: TrackerKind, unit: Option<&str>) -> JrnlResult<Option<&str>> {
+    let unit = unit.map(str::trim).filter(|unit| !unit.is_empty());
+    match unit {
+        Some(unit)
+            if kind != TrackerKind::Numeric || unit.chars().count() > MAX_TRACKER_UNIT_LEN =>
+        {
+            Err(JrnlError::InvalidTrackerUnit)
+        }
+        _ => Ok(unit),
+    }
+}
+
+async fn decrypted_tracker(
+    user: &User,
+    id: &Uuid,
+    tracker_service: &TrackerService,
+    user_key: &UserKey,
+) -> JrnlResult<Tracker> {
+    tracker_service
+        .get_tracker(user, id)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?
+        .decrypt(user_key)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn all_trackers(
+    user: &User,
+    tracker_service: &TrackerService,
+    user_key: UserKey,
+) -> JrnlResult<Vec<Tracker>> {
+    let trackers = tracker_service
+        .get_trackers(user)
+        .await
+        .map_err(DatabaseError)?;
+
+    decrypt_trackers(trackers, user_key)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn get_user_key(
+    user: &User,
+    user_key_service: &UserKeyService,
+    key_provider: &dyn KeyProvider,
+) -> JrnlResult<UserKey> {
+    user_key_service
+        .get_or_create_user_key(user, key_provider)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn get_trackers(
+    user: User,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<Tracker>>> {
+    let user_key = get_user_key(&user, &user_key_service, &*key_provider).await?;
+
+    all_trackers(&user, &tracker_service, user_key)
+        .await
+        .map(Json)
+}
+
+async fn create_tracker(
+    user: User,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<CreateTrackerPayload>,
+) -> JrnlResult<Json<Tracker>> {
+    let name = validate_tracker_name(&payload.name)?;
+    let unit = validate_tracker_unit(payload.kind, payload.unit.as_deref())?;
+
+    let existing_trackers = tracker_service
+        .get_trackers_count(&user)
+        .await
+        .map_err(DatabaseError)?;
+
+    if existing_trackers >= MAX_TRACKERS {
+        return Err(JrnlError::CannotCreateMoreTrackers);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    let id = Uuid::new_v4();
+    let encrypted_definition =
+        EncryptedTracker::seal_definition(id, user.id, name, payload.kind, unit, &user_key)
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    tracker_service
+        .create_tracker(&user, id, &encrypted_definition)
+        .await
+        .map_err(DatabaseError)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn update_tracker(
+    user: User,
+    Path(id): Path<Uuid>,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<UpdateTrackerPayload>,
+) -> JrnlResult<Json<Tracker>> {
+    let name = validate_tracker_name(&payload.name)?;
+
+    let user_key = get_user_key(&user, &user_key_service, &*key_provider).await?;
+    let tracker = decrypted_tracker(&user, &id, &tracker_service, &user_key).await?;
+    let unit = validate_tracker_unit(tracker.kind, payload.unit.as_deref())?;
+
+    let encrypted_definition =
+        EncryptedTracker::seal_definition(id, user.id, name, tracker.kind, unit, &user_key)
+            .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    tracker_service
+        .update_tracker(&user, &id, &encrypted_definition)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?
+        .decrypt(&user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn delete_tracker(
+    user: User,
+    Path(id): Path<Uuid>,
+    tracker_service: TrackerService,
+) -> JrnlResult<StatusCode> {
+    if tracker_service
+        .delete_tracker(&user, &id)
+        .await
+        .map_err(DatabaseError)?
+    {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
+
+#[derive(Deserialize)]
+struct TrackerRangeParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+// inclusive days, the last 30 days up to today unless told otherwise
+async fn get_tracker_history(
+    user: User,
+    Path(id): Path<Uuid>,
+    Query(params): Query<TrackerRangeParams>,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<TrackerHistory>> {
+    let to = params.to.unwrap_or_else(|| user.current_date_by_timezone());
+    let from = match params.from {
+        Some(from) => from,
+        None => to
+            .checked_sub_days(Days::new(DEFAULT_TRACKER_RANGE_DAYS - 1))
+            .ok_or(JrnlError::InvalidDateRange)?,
+    };
+
+    if from > to {
+        return Err(JrnlError::InvalidDateRange);
+    }
+
+    if (to - from).num_days() >= MAX_TRACKER_RANGE_DAYS {
+        return Err(JrnlError::InvalidTrackerRange);
+    }
+
+    let user_key = get_user_key(&user, &user_key_service, &*key_provider).await?;
+    let tracker = decrypted_tracker(&user, &id, &tracker_service, &user_key).await?;
+
+    let values = tracker_service
+        .get_tracker_values(&user, &id, from, to)
+        .await
+        .map_err(DatabaseError)?;
+    let values = decrypt_tracker_values(values, HashMap::from([(id, tracker.kind)]), user_key)
+        .await
+        .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    Ok(Json(TrackerHistory::new(tracker, from, to, values)))
+}
+
+// every tracker's value for the day, in the order the trackers were added
+async fn get_day_values(
+    user: User,
+    Path(date): Path<NaiveDate>,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+) -> JrnlResult<Json<Vec<TrackerDayValue>>> {
+    let values = tracker_service
+        .get_day_values(&user, date)
+        .await
+        .map_err(DatabaseError)?;
+
+    if values.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+
+    let user_key = get_user_key(&user, &user_key_service, &*key_provider).await?;
+    let trackers = all_trackers(&user, &tracker_service, user_key.clone()).await?;
+
+    let mut values = decrypt_tracker_values(
+        values,
+        trackers
+            .iter()
+            .map(|tracker| (tracker.id, tracker.kind))
+            .collect(),
+        user_key,
+    )
+    .await
+    .map_err(JrnlError::EntryDecryptionFailed)?;
+
+    values.sort_by_key(|value| {
+        trackers
+            .iter()
+            .position(|tracker| tracker.id == value.tracker_id)
+    });
+
+    Ok(Json(values))
+}
+
+#[derive(Deserialize)]
+struct TrackerValuePayload {
+    value: TrackerValue,
+}
+
+async fn set_tracker_value(
+    user: User,
+    Path((id, date)): Path<(Uuid, NaiveDate)>,
+    tracker_service: TrackerService,
+    user_key_service: UserKeyService,
+    State(AppState { key_provider, .. }): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<TrackerValuePayload>,
+) -> JrnlResult<Json<TrackerDayValue>> {
+    if date > user.current_date_by_timezone() {
+        return Err(JrnlError::InvalidTrackerDate);
+    }
+
+    let user_key = user_key_service
+        .get_or_create_user_key(&user, &*key_provider)
+        .await
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+    let tracker = decrypted_tracker(&user, &id, &tracker_service, &user_key).await?;
+
+    let value = tracker
+        .kind
+        .validate(payload.value)
+        .ok_or(JrnlError::InvalidTrackerValue)?;
+
+    let encrypted_value = EncryptedTrackerValue::seal_value(id, user.id, date, value, &user_key)
+        .map_err(JrnlError::EntryEncryptionFailed)?;
+
+    tracker_service
+        .set_tracker_value(&user, &id, date, &encrypted_value)
+        .await
+        .map_err(DatabaseError)?
+        .ok_or(JrnlError::NoResultsFound)?
+        .decrypt(tracker.kind, &user_key)
+        .map(Json)
+        .map_err(JrnlError::EntryDecryptionFailed)
+}
+
+async fn delete_tracker_value(
+    user: User,
+    Path((id, date)): Path<(Uuid, NaiveDate)>,
+    tracker_service: TrackerService,
+) -> JrnlResult<StatusCode> {
+    if tracker_service
+        .delete_tracker_value(&user, &id, date)
+        .await
+        .map_err(DatabaseError)?
+    {
+        Ok(StatusCode::OK)
+    } else {
+        Err(JrnlError::NoResultsFound)
+    }
+}
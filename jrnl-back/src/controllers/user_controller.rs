@@ -1,22 +1,39 @@
 use crate::{
-    error::{JrnlResult, JsonExtractor},
-    schemas::user::User,
-    services::user_service::UserService,
+    error::{DatabaseError, JrnlError, JrnlResult, JsonExtractor},
+    schemas::{e2e_key_material::E2eKeyMaterial, user::User},
+    services::{
+        stats_service::{StatsService, Streaks},
+        user_service::{UserService, UserUpdate},
+    },
     web::deserialize_empty_string,
     AppState,
 };
-use axum::{routing::get, Json, Router};
+use axum::{http::StatusCode, routing::get, Json, Router};
 use chrono_tz::Tz;
 use serde::{Deserialize, Deserializer};
 
 pub fn users_controller() -> Router<AppState> {
-    Router::new().route("/me", get(get_self_user).patch(update_self_user))
+    Router::new()
+        .route(
+            "/me",
+            get(get_self_user)
+                .patch(update_self_user)
+                .delete(delete_self_user),
+        )
+        .route("/me/streak", get(get_streak))
+        .route(
+            "/me/e2e",
+            get(get_e2e_key_material).put(set_e2e_key_material),
+        )
 }
 
 async fn get_self_user(user: User) -> Json<User> {
     Json(user)
 }
 
+const MAX_REVISION_LIMIT: i32 = 100;
+const MAX_STREAK_FREEZES: i32 = 4;
+
 #[derive(Debug, Deserialize)]
 struct UpdateSelfPayload {
     // Intl.DateTimeFormat().resolvedOptions().timeZone
@@ -25,12 +42,22 @@ struct UpdateSelfPayload {
 
     #[serde(default, deserialize_with = "deserialize_empty_string")]
     theme: Option<String>,
-    
+
     #[serde(default)]
     has_had_tour: Option<bool>,
-    
+
     #[serde(default)]
     has_seen_app_push: Option<bool>,
+
+    // existing entries are hidden or revealed by the maintenance task
+    #[serde(default)]
+    hide_metadata: Option<bool>,
+
+    #[serde(default)]
+    revision_limit: Option<i32>,
+
+    #[serde(default)]
+    streak_freezes: Option<i32>,
 }
 
 fn deserialize_tz<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Tz>, D::Error> {
@@ -52,15 +79,90 @@ async fn update_self_user(
     user_service: UserService,
     JsonExtractor(payload): JsonExtractor<UpdateSelfPayload>,
 ) -> JrnlResult<Json<User>> {
+    if payload
+        .revision_limit
+        .is_some_and(|limit| !(0..=MAX_REVISION_LIMIT).contains(&limit))
+    {
+        return Err(JrnlError::InvalidRevisionLimit);
+    }
+
+    if payload
+        .streak_freezes
+        .is_some_and(|freezes| !(0..=MAX_STREAK_FREEZES).contains(&freezes))
+    {
+        return Err(JrnlError::InvalidStreakFreezes);
+    }
+
+    let tz = payload.tz.as_ref().map(Tz::to_string);
     user_service
         .update_user(
             &user,
-            payload.theme.as_deref(),
-            payload.tz.as_ref().map(Tz::to_string).as_deref(),
-            payload.has_had_tour,
-            payload.has_seen_app_push
+            &UserUpdate {
+                theme: payload.theme.as_deref(),
+                tz: tz.as_deref(),
+                has_had_tour: payload.has_had_tour,
+                has_seen_app_push: payload.has_seen_app_push,
+                hide_metadata: payload.hide_metadata,
+                revision_limit: payload.revision_limit,
+                streak_freezes: payload.streak_freezes,
+            },
         )
         .await
         .map(Json)
         .map_err(Into::into)
 }
+
+// days are the user's local dates, so today is whatever date it currently is in their timezone
+async fn get_streak(user: User, stats_service: StatsService) -> JrnlResult<Json<Streaks>> {
+    let dates = stats_service
+        .get_entry_dates(&user)
+        .await
+        .map_err(DatabaseError)?;
+
+    let skipped_days = stats_service
+        .get_streak_skipped_days(&user)
+        .await
+        .map_err(DatabaseError)?;
+
+    Ok(Json(Streaks::compute(
+        &dates,
+        &skipped_days,
+        user.streak_freezes,
+        user.current_date_by_timezone(),
+    )))
+}
+
+async fn delete_self_user(user: User, user_service: UserService) -> JrnlResult<StatusCode> {
+    user_service.delete_user(&user).await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn get_e2e_key_material(
+    user: User,
+    user_service: UserService,
+) -> JrnlResult<Json<Option<E2eKeyMaterial>>> {
+    user_service
+        .get_e2e_key_material(&user)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
+
+// enabling is one way, the server can never turn client ciphertext back into something it can read.
+// entries written before this keep their server side encryption
+async fn set_e2e_key_material(
+    user: User,
+    user_service: UserService,
+    JsonExtractor(key_material): JsonExtractor<E2eKeyMaterial>,
+) -> JrnlResult<Json<E2eKeyMaterial>> {
+    key_material
+        .validate()
+        .map_err(JrnlError::InvalidE2eKeyMaterial)?;
+
+    user_service
+        .set_e2e_key_material(&user, &key_material)
+        .await
+        .map(Json)
+        .map_err(Into::into)
+}
@@ -0,0 +1,178 @@
+use crate::schemas::user_key::UserKey;
+use hmac::{Hmac, Mac};
+use rust_stemmers::{Algorithm, Stemmer};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use unicode_segmentation::UnicodeSegmentation;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// truncated hmac output, plenty to keep collisions within one user's entries negligible
+const TOKEN_LEN: usize = 16;
+const MIN_WORD_LEN: usize = 2;
+pub const MAX_QUERY_TERMS: usize = 16;
+
+pub struct EntrySearchTokens {
+    pub version: i32,
+    pub tokens: Vec<Vec<u8>>,
+}
+
+// keyed per user and per master key version, the same word never produces the same token for two users
+// and all tokens change whenever the user key moves to a new master key
+pub struct SearchIndexKey {
+    mac: HmacSha256,
+    pub version: i32,
+}
+
+impl SearchIndexKey {
+    pub fn derive(user_key: &UserKey) -> anyhow::Result<Self> {
+        let key = <HmacSha256 as Mac>::new_from_slice(&user_key.key)?
+            .chain_update(b"jrnl:search_index:")
+            .chain_update(user_key.key_version.to_le_bytes())
+            .finalize()
+            .into_bytes();
+
+        Ok(Self {
+            mac: <HmacSha256 as Mac>::new_from_slice(&Zeroizing::new(key.to_vec()))?,
+            version: user_key.key_version,
+        })
+    }
+
+    // one token per distinct stemmed word, so term frequency isn't leaked either
+    pub fn entry_tokens(&self, text: &str) -> EntrySearchTokens {
+        EntrySearchTokens {
+            version: self.version,
+            tokens: self.tokens(&strip_html(text)),
+        }
+    }
+
+    pub fn query_tokens(&self, query: &str) -> Vec<Vec<u8>> {
+        let mut tokens = self.tokens(query);
+        tokens.truncate(MAX_QUERY_TERMS);
+        tokens
+    }
+
+    fn tokens(&self, text: &str) -> Vec<Vec<u8>> {
+        let stemmer = Stemmer::create(Algorithm::English);
+
+        text.unicode_words()
+            .map(str::to_lowercase)
+            .filter(|word| word.chars().count() >= MIN_WORD_LEN)
+            .map(|word| stemmer.stem(&word).into_owned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|word| self.token(&word))
+            .collect()
+    }
+
+    fn token(&self, word: &str) -> Vec<u8> {
+        self.mac
+            .clone()
+            .chain_update(word.as_bytes())
+            .finalize()
+            .into_bytes()[..TOKEN_LEN]
+            .to_vec()
+    }
+}
+
+// entry text is sanitized html, tags and entities shouldn't end up as words
+fn strip_html(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    let mut in_entity = false;
+
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                stripped.push(' ');
+            }
+            '&' if !in_tag => in_entity = true,
+            ';' if in_entity => {
+                in_entity = false;
+                stripped.push(' ');
+            }
+            _ if in_entity && !c.is_ascii_alphanumeric() && c != '#' => {
+                in_entity = false;
+                stripped.push(c);
+            }
+            _ if !in_tag && !in_entity => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::{aead::OsRng, Aes256Gcm, KeyInit};
+    use uuid::Uuid;
+
+    fn user_key(key_version: i32) -> UserKey {
+        UserKey {
+            user_id: Uuid::new_v4(),
+            key: Aes256Gcm::generate_key(OsRng),
+            key_version,
+        }
+    }
+
+    #[test]
+    fn strip_html_leaves_only_text() {
+        let stripped = strip_html("<p>fish &amp; chips</p><p>tea&nbsp;<b>time</b></p>");
+        assert_eq!(
+            stripped.split_whitespace().collect::<Vec<_>>(),
+            ["fish", "chips", "tea", "time"]
+        );
+
+        // tags and entities split words instead of gluing them together
+        let stripped = strip_html("one<br>two&#39;three");
+        assert_eq!(
+            stripped.split_whitespace().collect::<Vec<_>>(),
+            ["one", "two", "three"]
+        );
+    }
+
+    #[test]
+    fn tokens_match_stemmed_words_in_any_case() {
+        let key = SearchIndexKey::derive(&user_key(1)).unwrap();
+
+        let entry = key.entry_tokens("<p>Walked the dog, then walking again. A walk!</p>");
+        assert_eq!(entry.version, 1);
+        // walk, the, dog, then, again. single letters are dropped
+        assert_eq!(entry.tokens.len(), 5);
+        assert!(entry.tokens.contains(&key.query_tokens("WALKS")[0]));
+        assert!(key.query_tokens("a I").is_empty());
+    }
+
+    #[test]
+    fn tokens_differ_between_users_and_key_versions() {
+        let original = user_key(1);
+        let key = SearchIndexKey::derive(&original).unwrap();
+        let other_user = SearchIndexKey::derive(&user_key(1)).unwrap();
+        let rotated = SearchIndexKey::derive(&UserKey {
+            key_version: 2,
+            ..original
+        })
+        .unwrap();
+
+        let tokens = key.query_tokens("diary");
+        assert_eq!(tokens, key.query_tokens("diary"));
+        assert_ne!(tokens, rotated.query_tokens("diary"));
+        assert_ne!(tokens, other_user.query_tokens("diary"));
+    }
+
+    #[test]
+    fn query_tokens_are_capped() {
+        let key = SearchIndexKey::derive(&user_key(1)).unwrap();
+        let query = (0..MAX_QUERY_TERMS + 4)
+            .map(|n| format!("word{n}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        assert_eq!(key.query_tokens(&query).len(), MAX_QUERY_TERMS);
+    }
+}
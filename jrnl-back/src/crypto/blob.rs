@@ -0,0 +1,209 @@
+use crate::crypto::envelope::EnvelopeBinding;
+use aes_gcm::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, Aes256Gcm, Key, KeyInit, Nonce,
+};
+use anyhow::{anyhow, bail, Context};
+
+// attachments are too big to seal in one go, so blobs are laid out as `version || segment...` and read and
+// written a segment at a time. every segment is `last (u8) || nonce || ciphertext`, and its associated data
+// holds its index and the last flag, so segments can't be reordered, dropped or cut off at the end
+pub const BLOB_V1: u8 = 1;
+
+// plaintext per segment, every segment but the last one holds exactly this much
+pub const SEGMENT_LEN: usize = 64 * 1024;
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SEALED_SEGMENT_LEN: usize = 1 + NONCE_LEN + SEGMENT_LEN + TAG_LEN;
+
+struct SegmentCipher {
+    cipher: Aes256Gcm,
+    aad: Vec<u8>,
+    index: u32,
+}
+
+impl SegmentCipher {
+    fn new(key: &Key<Aes256Gcm>, binding: &(impl EnvelopeBinding + ?Sized)) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(key),
+            aad: binding.associated_data(BLOB_V1),
+            index: 0,
+        }
+    }
+
+    fn associated_data(&self, last: bool) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(self.aad.len() + 5);
+        aad.extend_from_slice(&self.aad);
+        aad.extend_from_slice(&self.index.to_le_bytes());
+        aad.push(u8::from(last));
+
+        aad
+    }
+
+    fn next_index(&mut self) -> anyhow::Result<()> {
+        self.index = self
+            .index
+            .checked_add(1)
+            .context("blob has too many segments")?;
+        Ok(())
+    }
+}
+
+pub struct BlobSealer {
+    segments: SegmentCipher,
+    buffer: Vec<u8>,
+    header_written: bool,
+}
+
+impl BlobSealer {
+    pub fn new(key: &Key<Aes256Gcm>, binding: &(impl EnvelopeBinding + ?Sized)) -> Self {
+        Self {
+            segments: SegmentCipher::new(key, binding),
+            buffer: Vec::with_capacity(SEGMENT_LEN),
+            header_written: false,
+        }
+    }
+
+    // returns whatever is ready to be written, which is nothing until a full segment is buffered.
+    // a full segment is only sealed once more data follows it, since the last one has to be flagged as such
+    pub fn push(&mut self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.buffer.extend_from_slice(plaintext);
+
+        let mut sealed = self.header();
+        while self.buffer.len() > SEGMENT_LEN {
+            let segment = self.buffer.drain(..SEGMENT_LEN).collect::<Vec<_>>();
+            self.seal_segment(&segment, false, &mut sealed)?;
+        }
+
+        Ok(sealed)
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<Vec<u8>> {
+        let mut sealed = self.header();
+        let segment = std::mem::take(&mut self.buffer);
+        self.seal_segment(&segment, true, &mut sealed)?;
+
+        Ok(sealed)
+    }
+
+    fn header(&mut self) -> Vec<u8> {
+        if self.header_written {
+            return Vec::new();
+        }
+
+        self.header_written = true;
+        vec![BLOB_V1]
+    }
+
+    fn seal_segment(
+        &mut self,
+        plaintext: &[u8],
+        last: bool,
+        out: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = self.segments.associated_data(last);
+
+        let ciphertext = self
+            .segments
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| anyhow!("failed to seal blob segment {}", self.segments.index))?;
+
+        out.push(u8::from(last));
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+
+        self.segments.next_index()
+    }
+}
+
+pub struct BlobOpener {
+    segments: SegmentCipher,
+    buffer: Vec<u8>,
+    header_read: bool,
+}
+
+impl BlobOpener {
+    pub fn new(key: &Key<Aes256Gcm>, binding: &(impl EnvelopeBinding + ?Sized)) -> Self {
+        Self {
+            segments: SegmentCipher::new(key, binding),
+            buffer: Vec::with_capacity(SEALED_SEGMENT_LEN),
+            header_read: false,
+        }
+    }
+
+    // returns the plaintext of every full segment read so far, the last segment runs to the end of the blob
+    // so it's only opened by `finish`
+    pub fn push(&mut self, sealed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.buffer.extend_from_slice(sealed);
+
+        if !self.header_read {
+            let Some(&version) = self.buffer.first() else {
+                return Ok(Vec::new());
+            };
+
+            if version != BLOB_V1 {
+                bail!("unsupported blob version {version}");
+            }
+
+            self.buffer.remove(0);
+            self.header_read = true;
+        }
+
+        let mut plaintext = Vec::new();
+        while self.buffer.len() >= SEALED_SEGMENT_LEN && self.buffer.first() == Some(&0) {
+            let segment = self.buffer.drain(..SEALED_SEGMENT_LEN).collect::<Vec<_>>();
+            plaintext.extend(self.open_segment(&segment)?);
+        }
+
+        Ok(plaintext)
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<Vec<u8>> {
+        if !self.header_read {
+            bail!("blob is empty");
+        }
+
+        match self.buffer.first() {
+            Some(1) if self.buffer.len() <= SEALED_SEGMENT_LEN => {
+                let segment = std::mem::take(&mut self.buffer);
+                self.open_segment(&segment)
+            }
+            Some(1) => bail!("last blob segment is too long"),
+            _ => bail!("blob is truncated"),
+        }
+    }
+
+    fn open_segment(&mut self, segment: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let (&last, rest) = segment.split_first().context("empty blob segment")?;
+        if rest.len() < NONCE_LEN + TAG_LEN {
+            bail!("blob segment is too short");
+        }
+
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let aad = self.segments.associated_data(last == 1);
+
+        let plaintext = self
+            .segments
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| anyhow!("failed to open blob segment {}", self.segments.index))?;
+
+        self.segments.next_index()?;
+        Ok(plaintext)
+    }
+}
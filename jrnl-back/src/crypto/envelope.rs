@@ -0,0 +1,340 @@
+use aes_gcm::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, Aes256Gcm, Key, KeyInit, Nonce,
+};
+use anyhow::{anyhow, bail, Context};
+use chrono::NaiveDate;
+use uuid::Uuid;
+
+// v1 envelopes never had a version byte, they were raw ciphertext with the nonce stored in its own column.
+// v2 envelopes are laid out as `version || nonce || ciphertext`, every AEAD operation gets its own nonce
+pub const ENVELOPE_V2: u8 = 2;
+
+const NONCE_LEN: usize = 12;
+
+// padded plaintext is `len (u32 le) || plaintext || zeroes`, rounded up to a power of two
+const PADDING_MIN_BUCKET: usize = 256;
+const PADDING_LEN_PREFIX: usize = 4;
+
+// what a sealed value is, so a wrapped key can't be swapped in as content or vice versa
+#[derive(Debug, Clone, Copy)]
+pub enum EnvelopePurpose {
+    UserKey,
+    ContentKey,
+    Content,
+    // content padded to a size bucket, only used when the author hides entry metadata
+    PaddedContent,
+    EmotionScale,
+    // always padded, tag names are short enough that their length alone would give them away
+    TagName,
+    AttachmentKey,
+    // one segment of an attachment blob, see `crypto::blob`
+    AttachmentSegment,
+    // padded like tag names
+    PromptText,
+    // a check-in's scale and note sealed together, padded so the note's length doesn't show
+    Checkin,
+    // a tracker's name, kind and unit, padded like tag names
+    TrackerDefinition,
+    TrackerValue,
+}
+
+impl EnvelopePurpose {
+    const fn label(self) -> &'static [u8] {
+        match self {
+            Self::UserKey => b"jrnl:user_key",
+            Self::ContentKey => b"jrnl:content_key",
+            Self::Content => b"jrnl:content",
+            Self::PaddedContent => b"jrnl:padded_content",
+            Self::EmotionScale => b"jrnl:emotion_scale",
+            Self::TagName => b"jrnl:tag_name",
+            Self::AttachmentKey => b"jrnl:attachment_key",
+            Self::AttachmentSegment => b"jrnl:attachment_segment",
+            Self::PromptText => b"jrnl:prompt_text",
+            Self::Checkin => b"jrnl:checkin",
+            Self::TrackerDefinition => b"jrnl:tracker_definition",
+            Self::TrackerValue => b"jrnl:tracker_value",
+        }
+    }
+}
+
+// anything that ties a sealed value to the row it was written for
+pub trait EnvelopeBinding {
+    fn purpose(&self) -> EnvelopePurpose;
+    fn write_associated_data(&self, aad: &mut Vec<u8>);
+
+    fn associated_data(&self, version: u8) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(64);
+        aad.push(version);
+        aad.extend_from_slice(self.purpose().label());
+        self.write_associated_data(&mut aad);
+
+        aad
+    }
+}
+
+// swapping columns between entry rows will fail to decrypt
+pub struct EntryBinding<'a> {
+    pub purpose: EnvelopePurpose,
+    pub id: &'a Uuid,
+    pub author: &'a Uuid,
+    pub date: &'a NaiveDate,
+}
+
+impl EnvelopeBinding for EntryBinding<'_> {
+    fn purpose(&self) -> EnvelopePurpose {
+        self.purpose
+    }
+
+    fn write_associated_data(&self, aad: &mut Vec<u8>) {
+        aad.extend_from_slice(self.id.as_bytes());
+        aad.extend_from_slice(self.author.as_bytes());
+        aad.extend_from_slice(self.date.to_string().as_bytes());
+    }
+}
+
+// for rows that only belong to their author, like tags, prompts, check-ins and trackers
+pub struct OwnedBinding<'a> {
+    pub purpose: EnvelopePurpose,
+    pub id: &'a Uuid,
+    pub author: &'a Uuid,
+}
+
+impl EnvelopeBinding for OwnedBinding<'_> {
+    fn purpose(&self) -> EnvelopePurpose {
+        self.purpose
+    }
+
+    fn write_associated_data(&self, aad: &mut Vec<u8>) {
+        aad.extend_from_slice(self.id.as_bytes());
+        aad.extend_from_slice(self.author.as_bytes());
+    }
+}
+
+// a value can't be moved to another tracker or day
+pub struct TrackerValueBinding<'a> {
+    pub tracker_id: &'a Uuid,
+    pub author: &'a Uuid,
+    pub date: &'a NaiveDate,
+}
+
+impl EnvelopeBinding for TrackerValueBinding<'_> {
+    fn purpose(&self) -> EnvelopePurpose {
+        EnvelopePurpose::TrackerValue
+    }
+
+    fn write_associated_data(&self, aad: &mut Vec<u8>) {
+        aad.extend_from_slice(self.tracker_id.as_bytes());
+        aad.extend_from_slice(self.author.as_bytes());
+        aad.extend_from_slice(self.date.to_string().as_bytes());
+    }
+}
+
+pub struct AttachmentBinding<'a> {
+    pub purpose: EnvelopePurpose,
+    pub id: &'a Uuid,
+    pub entry_id: &'a Uuid,
+    pub author: &'a Uuid,
+}
+
+impl EnvelopeBinding for AttachmentBinding<'_> {
+    fn purpose(&self) -> EnvelopePurpose {
+        self.purpose
+    }
+
+    fn write_associated_data(&self, aad: &mut Vec<u8>) {
+        aad.extend_from_slice(self.id.as_bytes());
+        aad.extend_from_slice(self.entry_id.as_bytes());
+        aad.extend_from_slice(self.author.as_bytes());
+    }
+}
+
+pub struct UserKeyBinding<'a> {
+    pub user_id: &'a Uuid,
+}
+
+impl EnvelopeBinding for UserKeyBinding<'_> {
+    fn purpose(&self) -> EnvelopePurpose {
+        EnvelopePurpose::UserKey
+    }
+
+    fn write_associated_data(&self, aad: &mut Vec<u8>) {
+        aad.extend_from_slice(self.user_id.as_bytes());
+    }
+}
+
+pub fn seal(
+    key: &Key<Aes256Gcm>,
+    plaintext: &[u8],
+    binding: &(impl EnvelopeBinding + ?Sized),
+) -> anyhow::Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = binding.associated_data(ENVELOPE_V2);
+
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| anyhow!("failed to seal {:?}", binding.purpose()))?;
+
+    let mut envelope = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    envelope.push(ENVELOPE_V2);
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+
+    Ok(envelope)
+}
+
+pub fn open(
+    key: &Key<Aes256Gcm>,
+    envelope: &[u8],
+    binding: &(impl EnvelopeBinding + ?Sized),
+) -> anyhow::Result<Vec<u8>> {
+    let (&version, rest) = envelope.split_first().context("empty envelope")?;
+    if version != ENVELOPE_V2 {
+        bail!("unsupported envelope version {version}");
+    }
+
+    if rest.len() < NONCE_LEN {
+        bail!("envelope is too short");
+    }
+
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let aad = binding.associated_data(version);
+
+    Aes256Gcm::new(key)
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| anyhow!("failed to open {:?}", binding.purpose()))
+}
+
+pub fn pad(plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let len = u32::try_from(plaintext.len()).context("plaintext is too long to pad")?;
+    let bucket = (PADDING_LEN_PREFIX + plaintext.len())
+        .next_power_of_two()
+        .max(PADDING_MIN_BUCKET);
+
+    let mut padded = Vec::with_capacity(bucket);
+    padded.extend_from_slice(&len.to_le_bytes());
+    padded.extend_from_slice(plaintext);
+    padded.resize(bucket, 0);
+
+    Ok(padded)
+}
+
+pub fn unpad(padded: &[u8]) -> anyhow::Result<&[u8]> {
+    let (len, rest) = padded
+        .split_first_chunk::<PADDING_LEN_PREFIX>()
+        .context("padded plaintext is too short")?;
+    let len = usize::try_from(u32::from_le_bytes(*len))?;
+
+    rest.get(..len)
+        .context("padded plaintext length is out of bounds")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding<'a>(id: &'a Uuid, author: &'a Uuid, date: &'a NaiveDate) -> EntryBinding<'a> {
+        EntryBinding {
+            purpose: EnvelopePurpose::Content,
+            id,
+            author,
+            date,
+        }
+    }
+
+    #[test]
+    fn pad_rounds_up_to_a_bucket() {
+        assert_eq!(pad(b"").unwrap().len(), PADDING_MIN_BUCKET);
+        assert_eq!(
+            pad(&[1; PADDING_MIN_BUCKET - PADDING_LEN_PREFIX])
+                .unwrap()
+                .len(),
+            PADDING_MIN_BUCKET
+        );
+        assert_eq!(
+            pad(&[1; PADDING_MIN_BUCKET - PADDING_LEN_PREFIX + 1])
+                .unwrap()
+                .len(),
+            PADDING_MIN_BUCKET * 2
+        );
+        assert_eq!(pad(&[1; 3000]).unwrap().len(), 4096);
+    }
+
+    #[test]
+    fn unpad_returns_what_was_padded() {
+        for plaintext in [&b""[..], b"a", b"some tag name", &[0; 1000]] {
+            assert_eq!(unpad(&pad(plaintext).unwrap()).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn unpad_rejects_bad_lengths() {
+        assert!(unpad(&[1, 0]).is_err());
+
+        let mut padded = pad(b"short").unwrap();
+        padded[..PADDING_LEN_PREFIX].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(unpad(&padded).is_err());
+    }
+
+    #[test]
+    fn open_returns_what_was_sealed() {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let (id, author) = (Uuid::new_v4(), Uuid::new_v4());
+        let date = NaiveDate::from_ymd_opt(2025, 1, 28).unwrap();
+
+        let envelope = seal(&key, b"dear diary", &binding(&id, &author, &date)).unwrap();
+        assert_eq!(envelope[0], ENVELOPE_V2);
+        assert_eq!(
+            open(&key, &envelope, &binding(&id, &author, &date)).unwrap(),
+            b"dear diary"
+        );
+    }
+
+    #[test]
+    fn open_rejects_a_different_binding() {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let (id, author) = (Uuid::new_v4(), Uuid::new_v4());
+        let date = NaiveDate::from_ymd_opt(2025, 1, 28).unwrap();
+        let envelope = seal(&key, b"dear diary", &binding(&id, &author, &date)).unwrap();
+
+        let other_id = Uuid::new_v4();
+        assert!(open(&key, &envelope, &binding(&other_id, &author, &date)).is_err());
+        assert!(open(&key, &envelope, &binding(&id, &other_id, &date)).is_err());
+
+        let other_date = date.succ_opt().unwrap();
+        assert!(open(&key, &envelope, &binding(&id, &author, &other_date)).is_err());
+
+        let other_purpose = EntryBinding {
+            purpose: EnvelopePurpose::ContentKey,
+            ..binding(&id, &author, &date)
+        };
+        assert!(open(&key, &envelope, &other_purpose).is_err());
+    }
+
+    #[test]
+    fn open_rejects_a_different_key_or_version() {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let (id, author) = (Uuid::new_v4(), Uuid::new_v4());
+        let date = NaiveDate::from_ymd_opt(2025, 1, 28).unwrap();
+        let mut envelope = seal(&key, b"dear diary", &binding(&id, &author, &date)).unwrap();
+
+        let other_key = Aes256Gcm::generate_key(OsRng);
+        assert!(open(&other_key, &envelope, &binding(&id, &author, &date)).is_err());
+
+        envelope[0] = 1;
+        assert!(open(&key, &envelope, &binding(&id, &author, &date)).is_err());
+        assert!(open(&key, &[ENVELOPE_V2, 0, 0], &binding(&id, &author, &date)).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::crypto::{
+    envelope::{self, EnvelopeBinding},
+    key_provider::{KeyProvider, WrappedKey, LEGACY_KEY_VERSION},
+};
+use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
+use anyhow::{anyhow, bail, Context};
+use axum::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs};
+use zeroize::Zeroizing;
+
+const LEGACY_KEY_VAR: &str = "MASTER_ENCRYPTION_KEY";
+const VERSIONED_KEY_VAR_PREFIX: &str = "MASTER_ENCRYPTION_KEY_V";
+const ACTIVE_VERSION_VAR: &str = "MASTER_ENCRYPTION_KEY_VERSION";
+
+// master keys held in process memory, loaded either from the environment or a keyring file.
+// new keys are always wrapped with the active version, older versions are only kept around
+// for reading until the maintenance task has moved every row off of them
+pub struct LocalKeyProvider {
+    name: &'static str,
+    active_version: i32,
+    keys: HashMap<i32, Zeroizing<[u8; 32]>>,
+}
+
+#[derive(Deserialize)]
+struct KeyringFile {
+    active_version: Option<i32>,
+    keys: HashMap<i32, String>,
+}
+
+impl LocalKeyProvider {
+    // MASTER_ENCRYPTION_KEY is version 1, MASTER_ENCRYPTION_KEY_V{n} is version n.
+    // MASTER_ENCRYPTION_KEY_VERSION picks the active key, defaulting to the highest version
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut keys = HashMap::new();
+
+        for (name, value) in env::vars() {
+            let version = if name == ACTIVE_VERSION_VAR {
+                continue;
+            } else if name == LEGACY_KEY_VAR {
+                LEGACY_KEY_VERSION
+            } else if let Some(version) = name.strip_prefix(VERSIONED_KEY_VAR_PREFIX) {
+                version
+                    .parse::<i32>()
+                    .with_context(|| format!("invalid master key version in {name}"))?
+            } else {
+                continue;
+            };
+
+            if keys.insert(version, parse_key(&name, &value)?).is_some() {
+                bail!("master key version {version} is defined more than once");
+            }
+        }
+
+        let active_version = env::var(ACTIVE_VERSION_VAR)
+            .ok()
+            .map(|version| version.parse::<i32>())
+            .transpose()
+            .with_context(|| format!("invalid {ACTIVE_VERSION_VAR}"))?;
+
+        Self::new("env", keys, active_version)
+    }
+
+    // {"active_version": 2, "keys": {"1": "base64:...", "2": "hex:..."}}
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = Zeroizing::new(
+            fs::read_to_string(path).with_context(|| format!("failed to read keyring {path}"))?,
+        );
+        let file = serde_json::from_str::<KeyringFile>(&contents)
+            .with_context(|| format!("invalid keyring {path}"))?;
+
+        let keys = file
+            .keys
+            .iter()
+            .map(|(version, value)| {
+                parse_key(&format!("keyring version {version}"), value).map(|key| (*version, key))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        Self::new("file", keys, file.active_version)
+    }
+
+    fn new(
+        name: &'static str,
+        keys: HashMap<i32, Zeroizing<[u8; 32]>>,
+        active_version: Option<i32>,
+    ) -> anyhow::Result<Self> {
+        let active_version = match active_version {
+            Some(version) => version,
+            None => *keys
+                .keys()
+                .max()
+                .context("no master encryption keys are set")?,
+        };
+
+        if !keys.contains_key(&active_version) {
+            bail!("active master key version {active_version} has no key set");
+        }
+
+        Ok(Self {
+            name,
+            active_version,
+            keys,
+        })
+    }
+
+    fn key(&self, version: i32) -> anyhow::Result<&Key<Aes256Gcm>> {
+        self.keys
+            .get(&version)
+            .map(|key| Key::<Aes256Gcm>::from_slice(&key[..]))
+            .with_context(|| format!("no master key loaded for version {version}"))
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalKeyProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn active_version(&self) -> anyhow::Result<i32> {
+        Ok(self.active_version)
+    }
+
+    async fn versions(&self) -> anyhow::Result<Vec<i32>> {
+        Ok(self.keys.keys().copied().collect())
+    }
+
+    async fn wrap(
+        &self,
+        plaintext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<WrappedKey> {
+        Ok(WrappedKey {
+            version: self.active_version,
+            ciphertext: envelope::seal(self.key(self.active_version)?, plaintext, binding)?,
+        })
+    }
+
+    async fn unwrap(
+        &self,
+        version: i32,
+        ciphertext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        envelope::open(self.key(version)?, ciphertext, binding).map(Zeroizing::new)
+    }
+
+    async fn unwrap_legacy(
+        &self,
+        version: i32,
+        nonce: &[u8],
+        ciphertext: &[u8],
+    ) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        Aes256Gcm::new(self.key(version)?)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("failed to decrypt legacy content key"))
+    }
+}
+
+// `hex:` and `base64:` prefixed keys are decoded, anything else is used as raw bytes like the original env var was
+fn parse_key(name: &str, value: &str) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+    let bytes = Zeroizing::new(if let Some(hex) = value.strip_prefix("hex:") {
+        hex::decode(hex.trim()).with_context(|| format!("{name} is not valid hex"))?
+    } else if let Some(base64) = value.strip_prefix("base64:") {
+        STANDARD
+            .decode(base64.trim())
+            .with_context(|| format!("{name} is not valid base64"))?
+    } else {
+        value.as_bytes().to_vec()
+    });
+
+    let mut key = Zeroizing::new([0; 32]);
+    if bytes.len() != key.len() {
+        bail!("{name} must be exactly 32 bytes, got {}", bytes.len());
+    }
+
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
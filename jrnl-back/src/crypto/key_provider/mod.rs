@@ -0,0 +1,98 @@
+use crate::crypto::envelope::EnvelopeBinding;
+use anyhow::{bail, Context};
+use axum::async_trait;
+use local::LocalKeyProvider;
+use std::{env, sync::Arc};
+use vault::VaultTransitKeyProvider;
+use zeroize::Zeroizing;
+
+pub mod local;
+pub mod vault;
+
+// all rows written before key rotation existed were wrapped with this version
+pub const LEGACY_KEY_VERSION: i32 = 1;
+
+pub struct WrappedKey {
+    pub version: i32,
+    pub ciphertext: Vec<u8>,
+}
+
+// wraps and unwraps key material with the master key, wherever that key happens to live.
+// nothing outside of a provider ever sees the master key itself
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // version that new keys get wrapped with
+    async fn active_version(&self) -> anyhow::Result<i32>;
+
+    // every version this provider can still unwrap
+    async fn versions(&self) -> anyhow::Result<Vec<i32>>;
+
+    async fn wrap(
+        &self,
+        plaintext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<WrappedKey>;
+
+    async fn unwrap(
+        &self,
+        version: i32,
+        ciphertext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<Zeroizing<Vec<u8>>>;
+
+    // v1 envelopes predate associated data and shared their nonce with the content,
+    // only providers that hold the old raw keys can ever read them
+    async fn unwrap_legacy(
+        &self,
+        version: i32,
+        _nonce: &[u8],
+        _ciphertext: &[u8],
+    ) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        bail!(
+            "{} cannot unwrap legacy v1 envelopes (key version {version})",
+            self.name()
+        );
+    }
+
+    // moves wrapped key material over to the active version
+    async fn rewrap(
+        &self,
+        version: i32,
+        ciphertext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<WrappedKey> {
+        let plaintext = self.unwrap(version, ciphertext, binding).await?;
+        self.wrap(&plaintext, binding).await
+    }
+
+    // versions that still have rows which need to be moved to the active key
+    async fn retired_versions(&self) -> anyhow::Result<Vec<i32>> {
+        let active_version = self.active_version().await?;
+
+        Ok(self
+            .versions()
+            .await?
+            .into_iter()
+            .filter(|version| *version != active_version)
+            .collect())
+    }
+}
+
+// KEY_PROVIDER picks the backend, defaulting to keys straight from the environment
+pub async fn key_provider_from_env() -> anyhow::Result<Arc<dyn KeyProvider>> {
+    let provider: Arc<dyn KeyProvider> = match env::var("KEY_PROVIDER").as_deref() {
+        Ok("env") | Err(_) => Arc::new(LocalKeyProvider::from_env()?),
+        Ok("file") => Arc::new(LocalKeyProvider::from_file(
+            &env::var("KEYRING_FILE").context("KEYRING_FILE must be set")?,
+        )?),
+        Ok("vault") => Arc::new(VaultTransitKeyProvider::from_env()?),
+        Ok(other) => bail!("unknown KEY_PROVIDER {other}"),
+    };
+
+    // fail on startup instead of on the first request if the backend is misconfigured
+    provider.active_version().await?;
+
+    Ok(provider)
+}
@@ -0,0 +1,371 @@
+use crate::crypto::{
+    envelope::{EnvelopeBinding, ENVELOPE_V2},
+    key_provider::{KeyProvider, WrappedKey},
+};
+use anyhow::{bail, Context};
+use axum::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, env};
+use zeroize::Zeroizing;
+
+// wraps keys through a vault transit engine, the master key never leaves vault.
+// anything speaking the same http api works, including a local stub
+pub struct VaultTransitKeyProvider {
+    client: Client,
+    address: String,
+    token: Zeroizing<String>,
+    mount: String,
+    key_name: String,
+}
+
+#[derive(Deserialize)]
+struct VaultResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct TransitKeyInfo {
+    latest_version: i32,
+    min_decryption_version: i32,
+    keys: HashMap<i32, Value>,
+}
+
+#[derive(Deserialize)]
+struct TransitCiphertext {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct TransitPlaintext {
+    plaintext: String,
+}
+
+impl VaultTransitKeyProvider {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            address: env::var("VAULT_ADDR")
+                .context("VAULT_ADDR must be set")?
+                .trim_end_matches('/')
+                .to_string(),
+            token: Zeroizing::new(env::var("VAULT_TOKEN").context("VAULT_TOKEN must be set")?),
+            mount: env::var("VAULT_TRANSIT_MOUNT").unwrap_or_else(|_| String::from("transit")),
+            key_name: env::var("VAULT_TRANSIT_KEY").context("VAULT_TRANSIT_KEY must be set")?,
+        })
+    }
+
+    fn url(&self, action: &str) -> String {
+        format!(
+            "{}/v1/{}/{action}/{}",
+            self.address, self.mount, self.key_name
+        )
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> anyhow::Result<T> {
+        request
+            .header("X-Vault-Token", self.token.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<VaultResponse<T>>()
+            .await
+            .map(|response| response.data)
+            .map_err(Into::into)
+    }
+
+    async fn key_info(&self) -> anyhow::Result<TransitKeyInfo> {
+        self.send(self.client.get(self.url("keys"))).await
+    }
+}
+
+#[async_trait]
+impl KeyProvider for VaultTransitKeyProvider {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn active_version(&self) -> anyhow::Result<i32> {
+        self.key_info().await.map(|info| info.latest_version)
+    }
+
+    async fn versions(&self) -> anyhow::Result<Vec<i32>> {
+        let info = self.key_info().await?;
+
+        Ok(info
+            .keys
+            .into_keys()
+            .filter(|version| *version >= info.min_decryption_version)
+            .collect())
+    }
+
+    async fn wrap(
+        &self,
+        plaintext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<WrappedKey> {
+        let body = json!({
+            "plaintext": STANDARD.encode(plaintext),
+            "associated_data": STANDARD.encode(binding.associated_data(ENVELOPE_V2)),
+        });
+
+        let TransitCiphertext { ciphertext } = self
+            .send(self.client.post(self.url("encrypt")).json(&body))
+            .await?;
+
+        Ok(WrappedKey {
+            version: parse_ciphertext_version(&ciphertext)?,
+            ciphertext: ciphertext.into_bytes(),
+        })
+    }
+
+    async fn unwrap(
+        &self,
+        version: i32,
+        ciphertext: &[u8],
+        binding: &(dyn EnvelopeBinding + Sync),
+    ) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        let ciphertext = std::str::from_utf8(ciphertext).context("invalid vault ciphertext")?;
+        if parse_ciphertext_version(ciphertext)? != version {
+            bail!("vault ciphertext does not match key version {version}");
+        }
+
+        let body = json!({
+            "ciphertext": ciphertext,
+            "associated_data": STANDARD.encode(binding.associated_data(ENVELOPE_V2)),
+        });
+
+        let TransitPlaintext { plaintext } = self
+            .send(self.client.post(self.url("decrypt")).json(&body))
+            .await?;
+        let plaintext = Zeroizing::new(plaintext);
+
+        STANDARD
+            .decode(plaintext.as_bytes())
+            .map(Zeroizing::new)
+            .map_err(Into::into)
+    }
+}
+
+// vault ciphertexts look like `vault:v3:...`
+fn parse_ciphertext_version(ciphertext: &str) -> anyhow::Result<i32> {
+    ciphertext
+        .strip_prefix("vault:v")
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(version, _)| version.parse().ok())
+        .context("malformed vault ciphertext")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::envelope::UserKeyBinding;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    const TOKEN: &str = "stub-token";
+
+    // a stand in for the transit engine, ciphertexts carry their plaintext and associated data in the clear
+    struct TransitStub {
+        latest_version: i32,
+        min_decryption_version: i32,
+    }
+
+    type StubResponse = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+    fn stub_error(status: StatusCode, error: &str) -> (StatusCode, Json<Value>) {
+        (status, Json(json!({ "errors": [error] })))
+    }
+
+    fn authorize(headers: &HeaderMap) -> Result<(), (StatusCode, Json<Value>)> {
+        if headers
+            .get("X-Vault-Token")
+            .is_some_and(|token| token == TOKEN)
+        {
+            Ok(())
+        } else {
+            Err(stub_error(StatusCode::FORBIDDEN, "permission denied"))
+        }
+    }
+
+    async fn stub_keys(State(stub): State<Arc<TransitStub>>, headers: HeaderMap) -> StubResponse {
+        authorize(&headers)?;
+
+        let keys = (1..=stub.latest_version)
+            .map(|version| (version.to_string(), json!(1_700_000_000)))
+            .collect::<serde_json::Map<_, _>>();
+
+        Ok(Json(json!({
+            "data": {
+                "latest_version": stub.latest_version,
+                "min_decryption_version": stub.min_decryption_version,
+                "keys": keys,
+            }
+        })))
+    }
+
+    async fn stub_encrypt(
+        State(stub): State<Arc<TransitStub>>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> StubResponse {
+        authorize(&headers)?;
+
+        Ok(Json(json!({
+            "data": {
+                "ciphertext": format!(
+                    "vault:v{}:{}:{}",
+                    stub.latest_version,
+                    body["associated_data"].as_str().unwrap_or_default(),
+                    body["plaintext"].as_str().unwrap_or_default(),
+                )
+            }
+        })))
+    }
+
+    async fn stub_decrypt(headers: HeaderMap, Json(body): Json<Value>) -> StubResponse {
+        authorize(&headers)?;
+
+        let ciphertext = body["ciphertext"].as_str().unwrap_or_default();
+        let mut parts = ciphertext.splitn(4, ':').skip(2);
+        let (Some(associated_data), Some(plaintext)) = (parts.next(), parts.next()) else {
+            return Err(stub_error(StatusCode::BAD_REQUEST, "invalid ciphertext"));
+        };
+
+        if body["associated_data"].as_str() != Some(associated_data) {
+            return Err(stub_error(
+                StatusCode::BAD_REQUEST,
+                "cipher: message authentication failed",
+            ));
+        }
+
+        Ok(Json(json!({ "data": { "plaintext": plaintext } })))
+    }
+
+    async fn provider(stub: TransitStub, token: &str) -> VaultTransitKeyProvider {
+        let router = Router::new()
+            .route("/v1/transit/keys/:name", get(stub_keys))
+            .route("/v1/transit/encrypt/:name", post(stub_encrypt))
+            .route("/v1/transit/decrypt/:name", post(stub_decrypt))
+            .with_state(Arc::new(stub));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        VaultTransitKeyProvider {
+            client: Client::new(),
+            address: format!("http://{address}"),
+            token: Zeroizing::new(token.to_string()),
+            mount: String::from("transit"),
+            key_name: String::from("jrnl"),
+        }
+    }
+
+    const STUB: TransitStub = TransitStub {
+        latest_version: 3,
+        min_decryption_version: 2,
+    };
+
+    #[test]
+    fn parses_ciphertext_versions() {
+        assert_eq!(parse_ciphertext_version("vault:v1:abc").unwrap(), 1);
+        assert_eq!(parse_ciphertext_version("vault:v12:abc:def").unwrap(), 12);
+
+        for malformed in [
+            "",
+            "vault:v1",
+            "vault:1:abc",
+            "vault:vx:abc",
+            "v1:abc",
+            "local:v1:abc",
+        ] {
+            assert!(parse_ciphertext_version(malformed).is_err(), "{malformed}");
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_versions_from_the_key() {
+        let provider = provider(STUB, TOKEN).await;
+
+        assert_eq!(provider.active_version().await.unwrap(), 3);
+
+        let mut versions = provider.versions().await.unwrap();
+        versions.sort_unstable();
+        assert_eq!(versions, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn unwraps_what_was_wrapped() {
+        let provider = provider(STUB, TOKEN).await;
+        let user_id = Uuid::new_v4();
+        let binding = UserKeyBinding { user_id: &user_id };
+
+        let wrapped = provider.wrap(b"user key", &binding).await.unwrap();
+        assert_eq!(wrapped.version, 3);
+        assert!(wrapped.ciphertext.starts_with(b"vault:v3:"));
+
+        let unwrapped = provider
+            .unwrap(wrapped.version, &wrapped.ciphertext, &binding)
+            .await
+            .unwrap();
+        assert_eq!(unwrapped.as_slice(), b"user key");
+    }
+
+    #[tokio::test]
+    async fn unwrap_rejects_a_different_binding_or_version() {
+        let provider = provider(STUB, TOKEN).await;
+        let user_id = Uuid::new_v4();
+        let wrapped = provider
+            .wrap(b"user key", &UserKeyBinding { user_id: &user_id })
+            .await
+            .unwrap();
+
+        let other_user_id = Uuid::new_v4();
+        assert!(provider
+            .unwrap(
+                wrapped.version,
+                &wrapped.ciphertext,
+                &UserKeyBinding {
+                    user_id: &other_user_id
+                },
+            )
+            .await
+            .is_err());
+
+        assert!(provider
+            .unwrap(
+                2,
+                &wrapped.ciphertext,
+                &UserKeyBinding { user_id: &user_id }
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn surfaces_error_responses() {
+        let provider = provider(STUB, "wrong-token").await;
+        let user_id = Uuid::new_v4();
+        let binding = UserKeyBinding { user_id: &user_id };
+
+        assert!(provider.active_version().await.is_err());
+        assert!(provider.wrap(b"user key", &binding).await.is_err());
+        assert!(provider
+            .unwrap(3, b"vault:v3:abc:def", &binding)
+            .await
+            .is_err());
+    }
+}
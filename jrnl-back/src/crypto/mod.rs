@@ -0,0 +1,4 @@
+pub mod blind_index;
+pub mod blob;
+pub mod envelope;
+pub mod key_provider;
@@ -78,6 +78,162 @@ pub enum JrnlError {
     #[error("failed to decrypt journal entry {0}")]
     #[status(StatusCode::INTERNAL_SERVER_ERROR)]
     EntryDecryptionFailed(anyhow::Error),
+
+    #[error("end to end encrypted journals only accept ciphertext")]
+    #[status(StatusCode::BAD_REQUEST)]
+    PlaintextNotAllowed,
+
+    #[error("end to end encryption is not enabled")]
+    #[status(StatusCode::BAD_REQUEST)]
+    E2eNotEnabled,
+
+    #[error("invalid end to end key material {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidE2eKeyMaterial(&'static str),
+
+    #[error("emotion scale must be between 0 and 10")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidEmotionScale,
+
+    #[error("revision limit must be between 0 and 100")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidRevisionLimit,
+
+    #[error("streak freezes must be between 0 and 4")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidStreakFreezes,
+
+    #[error("cannot create more than 100 tags")]
+    #[status(StatusCode::FORBIDDEN)]
+    CannotCreateMoreTags,
+
+    #[error("tag names must be between 1 and 64 characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTagName,
+
+    #[error("cannot write more than 20 entries a day")]
+    #[status(StatusCode::FORBIDDEN)]
+    CannotCreateMoreDailyEntries,
+
+    #[error("cannot record more than 50 check-ins a day")]
+    #[status(StatusCode::FORBIDDEN)]
+    CannotCreateMoreCheckins,
+
+    #[error("check-in notes must be at most 280 characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCheckinNote,
+
+    #[error("check-ins cannot be recorded in the future")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCheckinTime,
+
+    #[error("check-in ranges can cover at most 366 days")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCheckinRange,
+
+    #[error("send either an emotion scale or scale_from_checkins")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidEmotionScaleSource,
+
+    #[error("there are no check-ins today to take the scale from")]
+    #[status(StatusCode::BAD_REQUEST)]
+    NoCheckinsToday,
+
+    #[error("cannot create more than 100 prompts")]
+    #[status(StatusCode::FORBIDDEN)]
+    CannotCreateMorePrompts,
+
+    #[error("prompts must be between 1 and 280 characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidPromptText,
+
+    #[error("prompt does not exist")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidPrompt,
+
+    #[error("cannot create more than 50 trackers")]
+    #[status(StatusCode::FORBIDDEN)]
+    CannotCreateMoreTrackers,
+
+    #[error("tracker names must be between 1 and 64 characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTrackerName,
+
+    #[error("only numeric trackers have a unit, of at most 16 characters")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTrackerUnit,
+
+    #[error("value does not fit the tracker's kind")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTrackerValue,
+
+    #[error("tracker values cannot be set for future days")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTrackerDate,
+
+    #[error("tracker history can cover at most 366 days")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidTrackerRange,
+
+    #[error("entries cannot have more than 20 tags")]
+    #[status(StatusCode::BAD_REQUEST)]
+    TooManyEntryTags,
+
+    #[error("invalid import file {0}")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidImportFile(anyhow::Error),
+
+    #[error("attachments must be images or audio")]
+    #[status(StatusCode::UNSUPPORTED_MEDIA_TYPE)]
+    UnsupportedAttachmentType,
+
+    #[error("attachments cannot be larger than 25 MiB")]
+    #[status(StatusCode::PAYLOAD_TOO_LARGE)]
+    AttachmentTooLarge,
+
+    #[error("attachments cannot be empty")]
+    #[status(StatusCode::BAD_REQUEST)]
+    EmptyAttachment,
+
+    #[error("entries cannot have more than 10 attachments")]
+    #[status(StatusCode::BAD_REQUEST)]
+    TooManyEntryAttachments,
+
+    #[error("range start must not be after its end")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidDateRange,
+
+    #[error("minimum emotion scale must not be above the maximum")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidScaleRange,
+
+    #[error("entries with hidden metadata cannot be sorted by emotion scale")]
+    #[status(StatusCode::BAD_REQUEST)]
+    HiddenScaleSort,
+
+    #[error("cursor is invalid or does not match the sort order")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCursor,
+
+    #[error("can only jump to a date on the first page of entries sorted by date")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidJump,
+
+    #[error("invalid calendar year or month")]
+    #[status(StatusCode::BAD_REQUEST)]
+    InvalidCalendarPeriod,
+
+    #[error("revision is still being re-encrypted, try restoring it again later")]
+    #[status(StatusCode::CONFLICT)]
+    LegacyRevisionRestore,
+
+    #[error("entries are still being moved under the account key, try deleting again later")]
+    #[status(StatusCode::CONFLICT)]
+    AccountDeletionPending,
+
+    #[error("failed to store attachment {0}")]
+    #[status(StatusCode::INTERNAL_SERVER_ERROR)]
+    AttachmentStorageFailed(anyhow::Error),
 }
 
 #[derive(Debug, Error)]
@@ -0,0 +1,39 @@
+use crate::import::{plain_text_html, record, ImportRecord, RecordError};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct DayOneExport {
+    entries: Vec<DayOneEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    creation_date: DateTime<Utc>,
+    time_zone: Option<String>,
+    #[serde(default)]
+    text: String,
+}
+
+// Day One has no mood, every entry falls back to the default scale.
+// the date is taken in the zone the entry was written in, falling back to the user's
+pub fn parse(input: &str, timezone: Tz) -> anyhow::Result<Vec<Result<ImportRecord, RecordError>>> {
+    let export = serde_json::from_str::<DayOneExport>(input)?;
+
+    Ok(export
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let timezone = entry
+                .time_zone
+                .and_then(|zone| zone.parse::<Tz>().ok())
+                .unwrap_or(timezone);
+            let date = entry.creation_date.with_timezone(&timezone).date_naive();
+
+            record(index + 1, date, None, plain_text_html(&entry.text))
+        })
+        .collect())
+}
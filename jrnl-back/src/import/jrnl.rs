@@ -0,0 +1,65 @@
+use crate::import::{plain_text_html, record, ImportRecord, RecordError};
+use chrono::NaiveDate;
+
+// every entry starts on a line like `[2024-01-05 09:12:00 AM] Title` or, in older journals,
+// `2024-01-05 09:12 Title`. everything up to the next header belongs to the entry.
+// jrnl.sh has no mood either, so the default scale is used
+pub fn parse(input: &str) -> Vec<Result<ImportRecord, RecordError>> {
+    let mut records = Vec::new();
+    let mut current: Option<(NaiveDate, Vec<&str>)> = None;
+
+    for line in input.lines() {
+        let Some((date, title)) = parse_header(line) else {
+            if let Some((_, lines)) = &mut current {
+                lines.push(line);
+            }
+            continue;
+        };
+
+        if let Some((date, lines)) = current.replace((date, vec![title])) {
+            records.push(record(
+                records.len() + 1,
+                date,
+                None,
+                plain_text_html(&lines.join("\n")),
+            ));
+        }
+    }
+
+    if let Some((date, lines)) = current {
+        records.push(record(
+            records.len() + 1,
+            date,
+            None,
+            plain_text_html(&lines.join("\n")),
+        ));
+    }
+
+    records
+}
+
+fn parse_header(line: &str) -> Option<(NaiveDate, &str)> {
+    let (timestamp, title) = if let Some(bracketed) = line.strip_prefix('[') {
+        bracketed.split_once(']')?
+    } else {
+        // date, a space and an hh:mm time, the title follows whatever else the time format had
+        let timestamp = line.get(..16)?;
+        let title = line[16..]
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == ':')
+            .trim_start();
+        let title = title
+            .strip_prefix("AM ")
+            .or_else(|| title.strip_prefix("PM "))
+            .unwrap_or(title);
+        (timestamp, title)
+    };
+
+    let (date, time) = timestamp.split_at_checked(10)?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let time = time.strip_prefix(' ')?;
+    if !time.starts_with(|c: char| c.is_ascii_digit()) || !time.contains(':') {
+        return None;
+    }
+
+    Some((date, title.trim()))
+}
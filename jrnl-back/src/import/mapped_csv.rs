@@ -0,0 +1,98 @@
+use crate::import::{plain_text_html, record, sanitized_html, ImportRecord, RecordError};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+
+// columns default to the ones our own csv export writes, which imports back as is along with `text_is_html`
+#[derive(Debug, Default)]
+pub struct CsvMapping {
+    pub date_column: Option<String>,
+    pub text_column: Option<String>,
+    pub emotion_scale_column: Option<String>,
+    // chrono format string, iso dates and rfc 3339 timestamps are read without one
+    pub date_format: Option<String>,
+    // text is plain unless the column already holds html
+    pub text_is_html: bool,
+}
+
+// the emotion scale column is optional, date and text have to be there
+pub fn parse(
+    input: &str,
+    timezone: Tz,
+    mapping: &CsvMapping,
+) -> anyhow::Result<Vec<Result<ImportRecord, RecordError>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(input.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+
+    let date_column = mapping.date_column.as_deref().unwrap_or("date");
+    let text_column = mapping.text_column.as_deref().unwrap_or("text");
+    let emotion_scale_column = mapping
+        .emotion_scale_column
+        .as_deref()
+        .unwrap_or("emotion_scale");
+
+    let date_column =
+        column(date_column).with_context(|| format!("missing date column {date_column}"))?;
+    let text_column =
+        column(text_column).with_context(|| format!("missing text column {text_column}"))?;
+    let emotion_scale_column = column(emotion_scale_column);
+
+    Ok(reader
+        .records()
+        .enumerate()
+        .map(|(index, row)| {
+            let position = index + 1;
+            let failed = |reason: String| RecordError { position, reason };
+
+            let row = row.map_err(|why| failed(why.to_string()))?;
+
+            let date = row.get(date_column).unwrap_or_default().trim();
+            let date = parse_date(date, timezone, mapping.date_format.as_deref())
+                .ok_or_else(|| failed(format!("unreadable date {date:?}")))?;
+
+            let emotion_scale = match emotion_scale_column
+                .and_then(|column| row.get(column))
+                .map(str::trim)
+                .filter(|scale| !scale.is_empty())
+            {
+                Some(scale) => Some(
+                    scale
+                        .parse::<f32>()
+                        .map_err(|_| failed(format!("unreadable emotion scale {scale:?}")))?,
+                ),
+                None => None,
+            };
+
+            let text = row.get(text_column).unwrap_or_default();
+            let text = if mapping.text_is_html {
+                sanitized_html(text)
+            } else {
+                plain_text_html(text)
+            };
+
+            record(position, date, emotion_scale, text)
+        })
+        .collect())
+}
+
+fn parse_date(date: &str, timezone: Tz, format: Option<&str>) -> Option<NaiveDate> {
+    if let Some(format) = format {
+        return NaiveDate::parse_from_str(date, format).ok().or_else(|| {
+            NaiveDateTime::parse_from_str(date, format)
+                .ok()
+                .map(|date_time| date_time.date())
+        });
+    }
+
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(date)
+                .ok()
+                .map(|date_time| date_time.with_timezone(&timezone).date_naive())
+        })
+}
@@ -0,0 +1,240 @@
+pub mod day_one;
+pub mod jrnl;
+pub mod mapped_csv;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use mapped_csv::CsvMapping;
+
+const EXCERPT_LEN: usize = 140;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    DayOne,
+    // plain text journals written by jrnl.sh
+    Jrnl,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflict {
+    #[default]
+    Skip,
+    // the existing entry is kept as a revision
+    Overwrite,
+}
+
+// one entry as read from the file, `position` is where it appeared starting at 1
+pub struct ImportRecord {
+    pub position: usize,
+    pub date: NaiveDate,
+    pub emotion_scale: Option<f32>,
+    // sanitized html, like everything else entries hold
+    pub text: Option<String>,
+}
+
+// records that couldn't be read never stop the rest of the file from importing
+pub struct RecordError {
+    pub position: usize,
+    pub reason: String,
+}
+
+// every record is written as an entry of its own, several records for the same date become several entries that day
+pub struct ImportEntry {
+    pub position: usize,
+    pub date: NaiveDate,
+    pub emotion_scale: f32,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Imported,
+    Overwritten,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub positions: Vec<usize>,
+    pub date: Option<NaiveDate>,
+    pub status: ImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    // only sent back for dry runs, so a mapping can be checked before anything is written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub imported: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub results: Vec<ImportResult>,
+}
+
+impl ImportResult {
+    pub fn failed(error: RecordError) -> Self {
+        Self {
+            positions: vec![error.position],
+            date: None,
+            status: ImportStatus::Failed,
+            entry_id: None,
+            reason: Some(error.reason),
+            excerpt: None,
+        }
+    }
+
+    pub fn for_entry(entry: &ImportEntry, status: ImportStatus, reason: Option<&str>) -> Self {
+        Self {
+            positions: vec![entry.position],
+            date: Some(entry.date),
+            status,
+            entry_id: None,
+            reason: reason.map(ToString::to_string),
+            excerpt: None,
+        }
+    }
+}
+
+impl ImportReport {
+    pub fn new(dry_run: bool, mut results: Vec<ImportResult>) -> Self {
+        results.sort_by_key(|result| result.positions.first().copied());
+        let count = |status| {
+            results
+                .iter()
+                .filter(|result| result.status == status)
+                .count()
+        };
+
+        Self {
+            dry_run,
+            imported: count(ImportStatus::Imported),
+            overwritten: count(ImportStatus::Overwritten),
+            skipped: count(ImportStatus::Skipped),
+            failed: count(ImportStatus::Failed),
+            results,
+        }
+    }
+}
+
+// the whole file is rejected only if it can't be read at all, e.g. invalid json or a missing csv column
+pub fn parse(
+    format: ImportFormat,
+    input: &str,
+    timezone: Tz,
+    mapping: &CsvMapping,
+) -> anyhow::Result<Vec<Result<ImportRecord, RecordError>>> {
+    let input = input.trim_start_matches('\u{feff}');
+
+    match format {
+        ImportFormat::DayOne => day_one::parse(input, timezone),
+        ImportFormat::Jrnl => Ok(jrnl::parse(input)),
+        ImportFormat::Csv => mapped_csv::parse(input, timezone, mapping),
+    }
+}
+
+pub fn record(
+    position: usize,
+    date: NaiveDate,
+    emotion_scale: Option<f32>,
+    text: Option<String>,
+) -> Result<ImportRecord, RecordError> {
+    if emotion_scale.is_some_and(|scale| !(0.0..=10.0).contains(&scale)) {
+        return Err(RecordError {
+            position,
+            reason: "emotion scale must be between 0 and 10".to_string(),
+        });
+    }
+
+    Ok(ImportRecord {
+        position,
+        date,
+        emotion_scale,
+        text: text.filter(|text| !text.trim().is_empty()),
+    })
+}
+
+// ordered by date, records keep their file order within a day
+pub fn import_entries(records: Vec<ImportRecord>, default_emotion_scale: f32) -> Vec<ImportEntry> {
+    let mut entries = records
+        .into_iter()
+        .map(|record| ImportEntry {
+            position: record.position,
+            date: record.date,
+            emotion_scale: record.emotion_scale.unwrap_or(default_emotion_scale),
+            text: record.text,
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by_key(|entry| (entry.date, entry.position));
+    entries
+}
+
+pub fn excerpt(entry: &ImportEntry) -> String {
+    // tags are dropped without leaving a gap, so adjacent paragraphs would run together
+    let text = ammonia::Builder::empty()
+        .clean(&entry.text.as_deref().unwrap_or_default().replace('>', "> "))
+        .to_string();
+
+    let mut excerpt = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((end, _)) = excerpt.char_indices().nth(EXCERPT_LEN) {
+        excerpt.truncate(end);
+        excerpt.push('…');
+    }
+
+    excerpt
+}
+
+// blank lines separate paragraphs, single line breaks are kept within one
+pub fn plain_text_html(text: &str) -> Option<String> {
+    let paragraphs = text
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| {
+            let lines = paragraph
+                .lines()
+                .map(escape_html)
+                .collect::<Vec<_>>()
+                .join("<br>");
+            format!("<p>{lines}</p>")
+        })
+        .collect::<Vec<_>>()
+        .concat();
+
+    (!paragraphs.is_empty()).then_some(paragraphs)
+}
+
+pub fn sanitized_html(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    (!trimmed.is_empty()).then(|| ammonia::clean(trimmed))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -1,12 +1,20 @@
 mod auth;
 mod controllers;
+mod crypto;
 mod error;
+mod import;
 mod schemas;
 mod services;
+mod storage;
 mod web;
 
-use crate::{auth::clean_expired_sessions, schemas::user::User};
-use aes_gcm::{Aes256Gcm, Key};
+use crate::{
+    auth::clean_expired_sessions,
+    crypto::key_provider::{key_provider_from_env, KeyProvider},
+    schemas::user::User,
+    storage::{blob_storage_from_env, BlobStorage},
+    web::cursor::CursorSigner,
+};
 use axum::{
     extract::DefaultBodyLimit,
     http::header::{AUTHORIZATION, CONTENT_TYPE},
@@ -14,15 +22,24 @@ use axum::{
     Router,
 };
 use controllers::{
-    auth_controller::auth_controller, entry_controller::entries_controller,
+    attachment_controller::attachments_controller,
+    auth_controller::auth_controller,
+    checkin_controller::checkins_controller,
+    entry_controller::{entries_controller, entry_imports_controller},
+    prompt_controller::prompts_controller,
+    tag_controller::tags_controller,
+    tracker_controller::trackers_controller,
     user_controller::users_controller,
 };
-use services::entry_service::encrypt_old_entries;
+use services::{
+    attachment_service::purge_deleted_attachment_blobs,
+    entry_service::{encrypt_old_entries, run_entry_key_maintenance},
+};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     PgPool,
 };
-use std::{env, time::Duration};
+use std::{env, sync::Arc, time::Duration};
 use tokio::{join, task};
 use tower::ServiceBuilder;
 use tower_http::{
@@ -35,9 +52,17 @@ use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
-    pub master_key: Key<Aes256Gcm>,
+    pub key_provider: Arc<dyn KeyProvider>,
+    pub blob_storage: Arc<dyn BlobStorage>,
+    pub cursor_signer: CursorSigner,
 }
 
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// attachments come from phones on whatever connection they happen to have
+const ATTACHMENT_REQUEST_TIMEOUT: Duration = Duration::from_mins(5);
+// a whole journal from another app is parsed, sealed and indexed in one request
+const IMPORT_REQUEST_TIMEOUT: Duration = Duration::from_mins(2);
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = dotenvy::dotenv();
@@ -62,23 +87,60 @@ async fn main() -> anyhow::Result<()> {
         info!("migrations ran successfully / db connection valid");
     }
 
-    let master_key_env = env::var("MASTER_ENCRYPTION_KEY")?;
-    let master_key = Key::<Aes256Gcm>::from_slice(master_key_env.as_bytes());
+    let key_provider = key_provider_from_env().await?;
+    info!(
+        "using {} key provider, master key version {}",
+        key_provider.name(),
+        key_provider.active_version().await?
+    );
+
+    let blob_storage = blob_storage_from_env().await?;
+    info!("using {} blob storage", blob_storage.name());
+
+    let cursor_signer = CursorSigner::from_env()?;
 
     let session_clean_task = task::spawn(clean_expired_sessions(pool.clone()));
-    let encrypt_old_entries_task = task::spawn(encrypt_old_entries(pool.clone(), *master_key));
+    let encrypt_old_entries_task =
+        task::spawn(encrypt_old_entries(pool.clone(), Arc::clone(&key_provider)));
+    let entry_key_maintenance_task = task::spawn(run_entry_key_maintenance(
+        pool.clone(),
+        Arc::clone(&key_provider),
+    ));
+
+    let attachment_blob_purge_task = task::spawn(purge_deleted_attachment_blobs(
+        pool.clone(),
+        Arc::clone(&blob_storage),
+    ));
 
     let state = AppState {
         pool,
-        master_key: *master_key,
+        key_provider,
+        blob_storage,
+        cursor_signer,
     };
 
     let app = Router::new()
         .nest("/user", users_controller())
         .nest("/entries", entries_controller())
+        .nest("/tags", tags_controller())
+        .nest("/prompts", prompts_controller())
+        .nest("/checkins", checkins_controller())
+        .nest("/trackers", trackers_controller())
         // .nest("/groups", groups_controller())
+        .layer(TimeoutLayer::new(REQUEST_TIMEOUT))
+        .nest(
+            "/attachments",
+            attachments_controller().layer(TimeoutLayer::new(ATTACHMENT_REQUEST_TIMEOUT)),
+        )
+        .nest(
+            "/entries/import",
+            entry_imports_controller().layer(TimeoutLayer::new(IMPORT_REQUEST_TIMEOUT)),
+        )
         .layer(from_extractor_with_state::<User, AppState>(state.clone()))
-        .nest("/auth", auth_controller())
+        .nest(
+            "/auth",
+            auth_controller().layer(TimeoutLayer::new(REQUEST_TIMEOUT)),
+        )
         .layer(
             ServiceBuilder::new()
                 .layer(
@@ -91,8 +153,7 @@ async fn main() -> anyhow::Result<()> {
                         .allow_headers(AllowHeaders::list([AUTHORIZATION, CONTENT_TYPE]))
                         .allow_credentials(AllowCredentials::yes()),
                 )
-                .layer(DefaultBodyLimit::max(1024 * 12))
-                .layer(TimeoutLayer::new(Duration::from_secs(10))),
+                .layer(DefaultBodyLimit::max(1024 * 12)),
         )
         .with_state(state);
 
@@ -101,7 +162,13 @@ async fn main() -> anyhow::Result<()> {
 
     let axum_server = axum::serve(listener, app);
 
-    let _ = join!(axum_server, session_clean_task, encrypt_old_entries_task);
+    let _ = join!(
+        axum_server,
+        session_clean_task,
+        encrypt_old_entries_task,
+        entry_key_maintenance_task,
+        attachment_blob_purge_task
+    );
 
     unreachable!();
 }
@@ -1,55 +1,202 @@
-use crate::schemas::entry::EncryptedEntry;
-use aes_gcm::{
-    aead::{Aead, OsRng},
-    AeadCore, Aes256Gcm, Key, KeyInit,
+use crate::{
+    schemas::{entry::EncryptedEntry, user_key::UserKey},
+    web::base64_bytes,
 };
-use anyhow::{anyhow, bail};
-use chrono::NaiveDate;
+use anyhow::{bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 use sqlx::FromRow;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ActiveEntry {
     pub id: Uuid,
     pub author: Uuid,
     pub date: NaiveDate,
     pub emotion_scale: f32,
     pub text: Option<String>,
-    pub expiry: chrono::DateTime<chrono::Utc>,
+    // set instead of text for end to end encrypted journals
+    #[serde(with = "base64_bytes::option")]
+    pub ciphertext: Option<Vec<u8>>,
+    pub expiry: DateTime<Utc>,
     #[serde(default)]
     pub ephemeral: bool,
+    pub prompt_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+// today's entry as it is stored, sealed exactly like an `EncryptedEntry` with the same id
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedActiveEntry {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub date: NaiveDate,
+    pub emotion_scale: Option<f32>,
+    pub encrypted_emotion_scale: Option<Vec<u8>>,
+    // only set for rows written before active entries were encrypted
+    pub text: Option<String>,
+    pub encrypted_content: Option<Vec<u8>>,
+    pub content_key: Option<Vec<u8>>,
+    pub ciphertext: Option<Vec<u8>>,
+    pub expiry: DateTime<Utc>,
+    pub ephemeral: bool,
+    pub prompt_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
 }
 
 impl ActiveEntry {
-    pub fn encrypt(&self, master_key: &Key<Aes256Gcm>) -> anyhow::Result<EncryptedEntry> {
+    pub fn encrypt(
+        &self,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<EncryptedEntry> {
         if self.ephemeral {
             bail!("cannot encrypt ephemeral entry");
         }
 
-        let master_cipher = Aes256Gcm::new(master_key);
+        self.seal(user_key, hide_metadata)
+    }
 
-        let key = Aes256Gcm::generate_key(OsRng);
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    fn seal(&self, user_key: &UserKey, hide_metadata: bool) -> anyhow::Result<EncryptedEntry> {
+        let sealed = if let Some(ciphertext) = &self.ciphertext {
+            EncryptedEntry::client_encrypted(
+                self.id,
+                self.author,
+                self.date,
+                self.emotion_scale,
+                ciphertext.clone(),
+                user_key,
+                hide_metadata,
+            )?
+        } else {
+            EncryptedEntry::seal(
+                self.id,
+                self.author,
+                self.date,
+                self.emotion_scale,
+                self.text.as_deref(),
+                user_key,
+                hide_metadata,
+            )?
+        };
 
-        let content_key_cipher = Aes256Gcm::new(&key);
+        Ok(EncryptedEntry {
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+            ..sealed
+        })
+    }
 
-        let encrypted_content_key = master_cipher
-            .encrypt(&nonce, &key[..])
-            .map_err(|_| anyhow!("failed to encrypt content key"))?;
+    pub fn encrypt_active(
+        &self,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<EncryptedActiveEntry> {
+        let sealed = self.seal(user_key, hide_metadata)?;
 
-        let encrypted_content = content_key_cipher
-            .encrypt(&nonce, self.text.as_deref().unwrap_or_default().as_bytes())
-            .map_err(|_| anyhow!("failed to encrypt entry content"))?;
+        Ok(EncryptedActiveEntry {
+            id: self.id,
+            author: self.author,
+            date: self.date,
+            emotion_scale: sealed.emotion_scale,
+            encrypted_emotion_scale: sealed.encrypted_emotion_scale,
+            text: None,
+            encrypted_content: (!sealed.client_encrypted).then_some(sealed.encrypted_content),
+            content_key: (!sealed.client_encrypted).then_some(sealed.content_key),
+            ciphertext: self.ciphertext.clone(),
+            expiry: self.expiry,
+            ephemeral: self.ephemeral,
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+        })
+    }
+}
 
-        Ok(EncryptedEntry {
+impl EncryptedActiveEntry {
+    pub fn decrypt(&self, user_key: &UserKey) -> anyhow::Result<ActiveEntry> {
+        let (emotion_scale, text) = match self.as_entry() {
+            Some(entry) if entry.client_encrypted => (entry.emotion_scale(user_key)?, None),
+            Some(entry) => {
+                let content_key = entry.unwrap_user_content_key(user_key)?;
+                let decrypted = entry.decrypt(&content_key, user_key)?;
+                (decrypted.emotion_scale, decrypted.text)
+            }
+            None => (
+                self.emotion_scale.context("entry has no emotion scale")?,
+                self.text.clone(),
+            ),
+        };
+
+        Ok(ActiveEntry {
+            id: self.id,
+            author: self.author,
+            date: self.date,
+            emotion_scale,
+            text,
+            ciphertext: self.ciphertext.clone(),
+            expiry: self.expiry,
+            ephemeral: self.ephemeral,
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+        })
+    }
+
+    // seals rows that are still plaintext in place
+    pub fn seal_plaintext(
+        &mut self,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<()> {
+        if self.text.is_none() {
+            return Ok(());
+        }
+
+        *self = self
+            .decrypt(user_key)?
+            .encrypt_active(user_key, hide_metadata)?;
+        Ok(())
+    }
+
+    // the envelope is bound to the same id, author and date the entry keeps after rollover,
+    // so sealed content moves over as is. only leftover plaintext rows get sealed here
+    pub fn into_entry(
+        self,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<EncryptedEntry> {
+        if self.ephemeral {
+            bail!("cannot encrypt ephemeral entry");
+        }
+
+        match self.as_entry() {
+            Some(entry) => Ok(entry),
+            None => self.decrypt(user_key)?.encrypt(user_key, hide_metadata),
+        }
+    }
+
+    fn as_entry(&self) -> Option<EncryptedEntry> {
+        let (encrypted_content, content_key, client_encrypted) =
+            match (&self.ciphertext, &self.encrypted_content, &self.content_key) {
+                (Some(ciphertext), _, _) => (ciphertext.clone(), Vec::new(), true),
+                (None, Some(encrypted_content), Some(content_key)) => {
+                    (encrypted_content.clone(), content_key.clone(), false)
+                }
+                _ => return None,
+            };
+
+        Some(EncryptedEntry {
             id: self.id,
             author: self.author,
             date: self.date,
             emotion_scale: self.emotion_scale,
+            encrypted_emotion_scale: self.encrypted_emotion_scale.clone(),
             encrypted_content,
-            content_key: encrypted_content_key,
-            nonce: nonce.to_vec(),
+            content_key,
+            nonce: None,
+            key_version: None,
+            client_encrypted,
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
         })
     }
 }
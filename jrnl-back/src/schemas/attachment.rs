@@ -0,0 +1,126 @@
+use crate::{
+    crypto::{
+        blob::{BlobOpener, BlobSealer},
+        envelope::{self, AttachmentBinding, EnvelopePurpose},
+    },
+    schemas::user_key::UserKey,
+};
+use aes_gcm::{aead::OsRng, Aes256Gcm, Key, KeyInit};
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedAttachment {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub author: Uuid,
+    pub content_type: String,
+    // plaintext size of the blob
+    pub size: i64,
+    // sealed with the author's user key, the same way an entry's content_key is
+    pub blob_key: Vec<u8>,
+    pub storage_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub content_type: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EncryptedAttachment {
+    pub fn storage_key(id: &Uuid, author: &Uuid) -> String {
+        format!("{author}/{id}")
+    }
+
+    // every attachment gets its own key, returned wrapped so it can be stored next to the blob it sealed
+    pub fn sealer(
+        id: &Uuid,
+        entry_id: &Uuid,
+        author: &Uuid,
+        user_key: &UserKey,
+    ) -> anyhow::Result<(BlobSealer, Vec<u8>)> {
+        if user_key.user_id != *author {
+            bail!("user key does not belong to attachment author");
+        }
+
+        let key = Aes256Gcm::generate_key(OsRng);
+        let blob_key = envelope::seal(
+            &user_key.key,
+            &key[..],
+            &binding(id, entry_id, author, EnvelopePurpose::AttachmentKey),
+        )?;
+
+        let sealer = BlobSealer::new(
+            &key,
+            &binding(id, entry_id, author, EnvelopePurpose::AttachmentSegment),
+        );
+
+        Ok((sealer, blob_key))
+    }
+
+    pub fn opener(&self, user_key: &UserKey) -> anyhow::Result<BlobOpener> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to attachment author");
+        }
+
+        let key = Zeroizing::new(envelope::open(
+            &user_key.key,
+            &self.blob_key,
+            &binding(
+                &self.id,
+                &self.entry_id,
+                &self.author,
+                EnvelopePurpose::AttachmentKey,
+            ),
+        )?);
+
+        if key.len() != 32 {
+            bail!("attachment key has the wrong length");
+        }
+
+        Ok(BlobOpener::new(
+            Key::<Aes256Gcm>::from_slice(&key),
+            &binding(
+                &self.id,
+                &self.entry_id,
+                &self.author,
+                EnvelopePurpose::AttachmentSegment,
+            ),
+        ))
+    }
+}
+
+impl From<EncryptedAttachment> for Attachment {
+    fn from(attachment: EncryptedAttachment) -> Self {
+        Self {
+            id: attachment.id,
+            entry_id: attachment.entry_id,
+            content_type: attachment.content_type,
+            size: attachment.size,
+            created_at: attachment.created_at,
+        }
+    }
+}
+
+const fn binding<'a>(
+    id: &'a Uuid,
+    entry_id: &'a Uuid,
+    author: &'a Uuid,
+    purpose: EnvelopePurpose,
+) -> AttachmentBinding<'a> {
+    AttachmentBinding {
+        purpose,
+        id,
+        entry_id,
+        author,
+    }
+}
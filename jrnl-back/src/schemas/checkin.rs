@@ -0,0 +1,86 @@
+use crate::{
+    crypto::envelope::{self, EnvelopePurpose, OwnedBinding},
+    schemas::user_key::UserKey,
+};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// the sealed data is `scale (f32 le) || note`, the note being empty when there's none
+const SCALE_LEN: usize = 4;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedCheckin {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub encrypted_data: Vec<u8>,
+    pub recorded_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Checkin {
+    pub id: Uuid,
+    pub emotion_scale: f32,
+    pub note: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EncryptedCheckin {
+    pub fn seal_data(
+        id: Uuid,
+        author: Uuid,
+        emotion_scale: f32,
+        note: Option<&str>,
+        user_key: &UserKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to check-in author");
+        }
+
+        let mut data = emotion_scale.to_le_bytes().to_vec();
+        data.extend_from_slice(note.unwrap_or_default().as_bytes());
+
+        envelope::seal(
+            &user_key.key,
+            &envelope::pad(&data)?,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::Checkin,
+                id: &id,
+                author: &author,
+            },
+        )
+    }
+
+    pub fn decrypt(&self, user_key: &UserKey) -> anyhow::Result<Checkin> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to check-in author");
+        }
+
+        let padded = envelope::open(
+            &user_key.key,
+            &self.encrypted_data,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::Checkin,
+                id: &self.id,
+                author: &self.author,
+            },
+        )?;
+
+        let (scale, note) = envelope::unpad(&padded)?
+            .split_first_chunk::<SCALE_LEN>()
+            .context("check-in is too short")?;
+        let note = String::from_utf8(note.to_vec())?;
+
+        Ok(Checkin {
+            id: self.id,
+            emotion_scale: f32::from_le_bytes(*scale),
+            note: (!note.is_empty()).then_some(note),
+            recorded_at: self.recorded_at,
+            created_at: self.created_at,
+        })
+    }
+}
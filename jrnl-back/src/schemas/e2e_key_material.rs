@@ -0,0 +1,44 @@
+use crate::web::base64_bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{types::Json, FromRow};
+
+pub const SUPPORTED_KDF_ALGORITHMS: [&str; 2] = ["argon2id", "pbkdf2-sha256"];
+
+// the client derives a key from the user's passphrase with these parameters and unwraps its data key.
+// the recovery key is a second, independent wrap of that same data key so a lost passphrase isn't fatal
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct E2eKeyMaterial {
+    pub kdf_algorithm: String,
+    pub kdf_params: Json<Value>,
+    #[serde(with = "base64_bytes")]
+    pub salt: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub wrapped_data_key: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub wrapped_recovery_key: Vec<u8>,
+}
+
+impl E2eKeyMaterial {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !SUPPORTED_KDF_ALGORITHMS.contains(&self.kdf_algorithm.as_str()) {
+            return Err("unsupported kdf algorithm");
+        }
+
+        if !self.kdf_params.is_object() {
+            return Err("kdf params must be an object");
+        }
+
+        if self.salt.len() < 16 || self.salt.len() > 64 {
+            return Err("salt must be between 16 and 64 bytes");
+        }
+
+        for wrapped_key in [&self.wrapped_data_key, &self.wrapped_recovery_key] {
+            if wrapped_key.is_empty() || wrapped_key.len() > 512 {
+                return Err("wrapped keys must be between 1 and 512 bytes");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,19 +1,54 @@
-use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
-use anyhow::anyhow;
-use chrono::NaiveDate;
+use crate::{
+    crypto::{
+        envelope::{self, EntryBinding, EnvelopePurpose},
+        key_provider::KeyProvider,
+    },
+    schemas::user_key::UserKey,
+    web::base64_bytes,
+};
+use aes_gcm::{
+    aead::{Aead, OsRng},
+    Aes256Gcm, Key, KeyInit, Nonce,
+};
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 use sqlx::FromRow;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
 #[derive(Debug, Clone, FromRow)]
 pub struct EncryptedEntry {
     pub id: Uuid,
     pub author: Uuid,
     pub date: NaiveDate,
-    pub emotion_scale: f32,
+    // none when the author hides entry metadata, the scale is sealed in encrypted_emotion_scale instead
+    pub emotion_scale: Option<f32>,
+    pub encrypted_emotion_scale: Option<Vec<u8>>,
     pub encrypted_content: Vec<u8>,
     pub content_key: Vec<u8>,
-    pub nonce: Vec<u8>,
+    // only set for legacy v1 envelopes, where the content key and content shared this nonce
+    pub nonce: Option<Vec<u8>>,
+    // master key version for rows written before user keys existed, none once wrapped by the author's user key
+    pub key_version: Option<i32>,
+    // end to end encrypted by the client, encrypted_content is an opaque blob the server can't read
+    pub client_encrypted: bool,
+    // the daily prompt it answered, see `schemas::prompt`
+    pub prompt_id: Option<Uuid>,
+    // a day can hold several entries, they're ordered by when they were written
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct ContentKey(Zeroizing<Vec<u8>>);
+
+impl ContentKey {
+    fn key(&self) -> anyhow::Result<&Key<Aes256Gcm>> {
+        if self.0.len() != 32 {
+            bail!("content key has the wrong length");
+        }
+
+        Ok(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -23,23 +58,190 @@ pub struct DecryptedEntry {
     pub date: NaiveDate,
     pub emotion_scale: f32,
     pub text: Option<String>,
+    #[serde(with = "base64_bytes::option", skip_serializing_if = "Option::is_none")]
+    pub ciphertext: Option<Vec<u8>>,
+    pub prompt_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+// the scale is sealed directly with the user key, so listing entries never has to touch content keys
+pub fn open_emotion_scale(
+    user_key: &UserKey,
+    binding: &EntryBinding,
+    sealed: &[u8],
+) -> anyhow::Result<f32> {
+    if user_key.user_id != *binding.author {
+        bail!("user key does not belong to entry author");
+    }
+
+    let bytes = envelope::open(&user_key.key, sealed, binding)?;
+    let bytes =
+        <[u8; 4]>::try_from(bytes.as_slice()).context("emotion scale has the wrong length")?;
+
+    Ok(f32::from_le_bytes(bytes))
 }
 
 impl EncryptedEntry {
-    pub fn decrypt(&self, master_key: &Key<Aes256Gcm>) -> anyhow::Result<DecryptedEntry> {
-        let master_cipher = Aes256Gcm::new(master_key);
+    pub fn seal(
+        id: Uuid,
+        author: Uuid,
+        date: NaiveDate,
+        emotion_scale: f32,
+        text: Option<&str>,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<Self> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to entry author");
+        }
 
-        let nonce = Nonce::from_slice(&self.nonce[..]);
-        let decrypted_content_key = master_cipher
-            .decrypt(nonce, &self.content_key[..])
-            .map_err(|_| anyhow!("failed to decrypt content key"))?;
+        let key = Aes256Gcm::generate_key(OsRng);
 
-        let content_key_cipher =
-            Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&decrypted_content_key));
+        let mut entry = Self {
+            id,
+            author,
+            date,
+            emotion_scale: None,
+            encrypted_emotion_scale: None,
+            encrypted_content: Vec::new(),
+            content_key: Vec::new(),
+            nonce: None,
+            key_version: None,
+            client_encrypted: false,
+            prompt_id: None,
+            created_at: Utc::now(),
+        };
 
-        let decrypted_content_bytes = content_key_cipher
-            .decrypt(nonce, &self.encrypted_content[..])
-            .map_err(|_| anyhow!("failed to decrypt entry content"))?;
+        entry.set_emotion_scale(emotion_scale, user_key, hide_metadata)?;
+        entry.content_key = envelope::seal(
+            &user_key.key,
+            &key[..],
+            &entry.binding(EnvelopePurpose::ContentKey),
+        )?;
+
+        let text = text.unwrap_or_default().as_bytes();
+        entry.encrypted_content = if hide_metadata {
+            envelope::seal(
+                &key,
+                &envelope::pad(text)?,
+                &entry.binding(EnvelopePurpose::PaddedContent),
+            )?
+        } else {
+            envelope::seal(&key, text, &entry.binding(EnvelopePurpose::Content))?
+        };
+
+        Ok(entry)
+    }
+
+    // padding the ciphertext is left to the client, only the scale can be hidden here
+    pub fn client_encrypted(
+        id: Uuid,
+        author: Uuid,
+        date: NaiveDate,
+        emotion_scale: f32,
+        ciphertext: Vec<u8>,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<Self> {
+        let mut entry = Self {
+            id,
+            author,
+            date,
+            emotion_scale: None,
+            encrypted_emotion_scale: None,
+            encrypted_content: ciphertext,
+            content_key: Vec::new(),
+            nonce: None,
+            key_version: None,
+            client_encrypted: true,
+            prompt_id: None,
+            created_at: Utc::now(),
+        };
+
+        entry.set_emotion_scale(emotion_scale, user_key, hide_metadata)?;
+        Ok(entry)
+    }
+
+    pub const fn metadata_hidden(&self) -> bool {
+        self.encrypted_emotion_scale.is_some()
+    }
+
+    pub fn emotion_scale(&self, user_key: &UserKey) -> anyhow::Result<f32> {
+        match (self.emotion_scale, &self.encrypted_emotion_scale) {
+            (Some(emotion_scale), _) => Ok(emotion_scale),
+            (None, Some(sealed)) => open_emotion_scale(
+                user_key,
+                &self.binding(EnvelopePurpose::EmotionScale),
+                sealed,
+            ),
+            (None, None) => bail!("entry has no emotion scale"),
+        }
+    }
+
+    fn set_emotion_scale(
+        &mut self,
+        emotion_scale: f32,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<()> {
+        if hide_metadata {
+            self.emotion_scale = None;
+            self.encrypted_emotion_scale = Some(envelope::seal(
+                &user_key.key,
+                &emotion_scale.to_le_bytes(),
+                &self.binding(EnvelopePurpose::EmotionScale),
+            )?);
+        } else {
+            self.emotion_scale = Some(emotion_scale);
+            self.encrypted_emotion_scale = None;
+        }
+
+        Ok(())
+    }
+
+    // client encrypted entries are handed back exactly as they were uploaded
+    pub fn into_client_encrypted(self, user_key: &UserKey) -> anyhow::Result<DecryptedEntry> {
+        if !self.client_encrypted {
+            bail!("entry is not client encrypted");
+        }
+
+        Ok(DecryptedEntry {
+            id: self.id,
+            author: self.author,
+            date: self.date,
+            emotion_scale: self.emotion_scale(user_key)?,
+            text: None,
+            ciphertext: Some(self.encrypted_content),
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+        })
+    }
+
+    pub fn decrypt(
+        &self,
+        content_key: &ContentKey,
+        user_key: &UserKey,
+    ) -> anyhow::Result<DecryptedEntry> {
+        let content_key = content_key.key()?;
+
+        let decrypted_content_bytes = if let Some(nonce) = &self.nonce {
+            Aes256Gcm::new(content_key)
+                .decrypt(Nonce::from_slice(nonce), &self.encrypted_content[..])
+                .map_err(|_| anyhow!("failed to decrypt entry content"))?
+        } else if self.metadata_hidden() {
+            let padded = envelope::open(
+                content_key,
+                &self.encrypted_content,
+                &self.binding(EnvelopePurpose::PaddedContent),
+            )?;
+            envelope::unpad(&padded)?.to_vec()
+        } else {
+            envelope::open(
+                content_key,
+                &self.encrypted_content,
+                &self.binding(EnvelopePurpose::Content),
+            )?
+        };
 
         let decrypted_content = String::from_utf8(decrypted_content_bytes)?;
         let text = if decrypted_content.is_empty() {
@@ -52,8 +254,156 @@ impl EncryptedEntry {
             id: self.id,
             author: self.author,
             date: self.date,
-            emotion_scale: self.emotion_scale,
+            emotion_scale: self.emotion_scale(user_key)?,
             text,
+            ciphertext: None,
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
         })
     }
+
+    // re-encrypts a v1 entry into a v2 envelope with a fresh content key, this has to touch the entry text
+    pub fn upgrade_envelope(
+        &mut self,
+        content_key: &ContentKey,
+        user_key: &UserKey,
+    ) -> anyhow::Result<()> {
+        if self.nonce.is_none() {
+            return Ok(());
+        }
+
+        let decrypted = self.decrypt(content_key, user_key)?;
+        *self = Self {
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+            ..Self::seal(
+                self.id,
+                self.author,
+                self.date,
+                decrypted.emotion_scale,
+                decrypted.text.as_deref(),
+                user_key,
+                self.metadata_hidden(),
+            )?
+        };
+
+        Ok(())
+    }
+
+    // moves a content key wrapped directly by the master key under the author's user key,
+    // without touching the entry text
+    pub fn wrap_with_user_key(
+        &mut self,
+        content_key: &ContentKey,
+        user_key: &UserKey,
+    ) -> anyhow::Result<()> {
+        if self.key_version.is_none() {
+            return Ok(());
+        }
+
+        if self.nonce.is_some() {
+            bail!("legacy envelopes have to be upgraded instead of re-wrapped");
+        }
+
+        self.content_key = envelope::seal(
+            &user_key.key,
+            content_key.key()?,
+            &self.binding(EnvelopePurpose::ContentKey),
+        )?;
+        self.key_version = None;
+
+        Ok(())
+    }
+
+    // hiding or revealing metadata changes the padding, so server encrypted text is sealed again.
+    // client encrypted entries only have their scale moved
+    pub fn set_metadata_hidden(
+        &mut self,
+        content_key: Option<&ContentKey>,
+        user_key: &UserKey,
+        hide_metadata: bool,
+    ) -> anyhow::Result<()> {
+        if self.metadata_hidden() == hide_metadata {
+            return Ok(());
+        }
+
+        let emotion_scale = self.emotion_scale(user_key)?;
+        if self.client_encrypted {
+            return self.set_emotion_scale(emotion_scale, user_key, hide_metadata);
+        }
+
+        let content_key = content_key.context("missing content key")?;
+        let decrypted = self.decrypt(content_key, user_key)?;
+        *self = Self {
+            prompt_id: self.prompt_id,
+            created_at: self.created_at,
+            ..Self::seal(
+                self.id,
+                self.author,
+                self.date,
+                emotion_scale,
+                decrypted.text.as_deref(),
+                user_key,
+                hide_metadata,
+            )?
+        };
+
+        Ok(())
+    }
+
+    // user key wrapped content keys are unwrapped locally, anything older has to go through the key provider
+    pub async fn unwrap_content_key(
+        &self,
+        key_provider: &dyn KeyProvider,
+        user_key: &UserKey,
+    ) -> anyhow::Result<ContentKey> {
+        if self.client_encrypted {
+            bail!("client encrypted entries have no content key");
+        }
+
+        let binding = self.binding(EnvelopePurpose::ContentKey);
+
+        let key = match (self.key_version, &self.nonce) {
+            (None, _) => return self.unwrap_user_content_key(user_key),
+            (Some(version), Some(nonce)) => {
+                key_provider
+                    .unwrap_legacy(version, nonce, &self.content_key)
+                    .await
+            }
+            (Some(version), None) => {
+                key_provider
+                    .unwrap(version, &self.content_key, &binding)
+                    .await
+            }
+        };
+
+        key.map(ContentKey).context("failed to decrypt content key")
+    }
+
+    pub fn unwrap_user_content_key(&self, user_key: &UserKey) -> anyhow::Result<ContentKey> {
+        if self.key_version.is_some() {
+            bail!("content key is wrapped by a master key");
+        }
+
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to entry author");
+        }
+
+        envelope::open(
+            &user_key.key,
+            &self.content_key,
+            &self.binding(EnvelopePurpose::ContentKey),
+        )
+        .map(|key| ContentKey(Zeroizing::new(key)))
+        .context("failed to decrypt content key")
+    }
+
+    const fn binding(&self, purpose: EnvelopePurpose) -> EntryBinding<'_> {
+        EntryBinding {
+            purpose,
+            id: &self.id,
+            author: &self.author,
+            date: &self.date,
+        }
+    }
 }
@@ -0,0 +1,60 @@
+use crate::{schemas::entry::EncryptedEntry, web::base64_bytes};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EntryRevision {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub author: Uuid,
+    pub date: NaiveDate,
+    pub emotion_scale: Option<f32>,
+    pub encrypted_emotion_scale: Option<Vec<u8>>,
+    pub encrypted_content: Vec<u8>,
+    pub content_key: Vec<u8>,
+    pub nonce: Option<Vec<u8>>,
+    pub key_version: Option<i32>,
+    pub client_encrypted: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct EntryRevisionSummary {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecryptedEntryRevision {
+    pub id: Uuid,
+    pub entry_id: Uuid,
+    pub date: NaiveDate,
+    pub emotion_scale: f32,
+    pub text: Option<String>,
+    #[serde(with = "base64_bytes::option", skip_serializing_if = "Option::is_none")]
+    pub ciphertext: Option<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EntryRevision {
+    // revisions keep the envelope of the entry they were copied from, so they open as that entry
+    pub fn as_entry(&self) -> EncryptedEntry {
+        EncryptedEntry {
+            id: self.entry_id,
+            author: self.author,
+            date: self.date,
+            emotion_scale: self.emotion_scale,
+            encrypted_emotion_scale: self.encrypted_emotion_scale.clone(),
+            encrypted_content: self.encrypted_content.clone(),
+            content_key: self.content_key.clone(),
+            nonce: self.nonce.clone(),
+            key_version: self.key_version,
+            client_encrypted: self.client_encrypted,
+            prompt_id: None,
+            created_at: self.created_at,
+        }
+    }
+}
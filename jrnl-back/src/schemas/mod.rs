@@ -1,4 +1,12 @@
 pub mod active_entry;
+pub mod attachment;
+pub mod checkin;
+pub mod e2e_key_material;
 pub mod entry;
+pub mod entry_revision;
 pub mod group;
+pub mod prompt;
+pub mod tag;
+pub mod tracker;
 pub mod user;
+pub mod user_key;
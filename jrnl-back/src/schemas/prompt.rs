@@ -0,0 +1,231 @@
+use crate::{
+    crypto::envelope::{self, EnvelopePurpose, OwnedBinding},
+    schemas::user_key::UserKey,
+};
+use anyhow::bail;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use uuid::{uuid, Uuid};
+
+// ids are fixed since entries point at the prompt they answered. prompts can be added, never removed or reworded
+pub const BUILT_IN_PROMPTS: &[(Uuid, &str)] = &[
+    (
+        uuid!("67063c8e-b6d6-45f8-8bfe-45cc9906bd8a"),
+        "What made you smile today?",
+    ),
+    (
+        uuid!("c989223f-2280-4530-9280-b5b8d3bc8c8f"),
+        "What's taking up most of your thoughts right now?",
+    ),
+    (
+        uuid!("d9007ce2-ff1d-40f7-a347-8c5f6da88704"),
+        "What are you grateful for today?",
+    ),
+    (
+        uuid!("c03bc47d-2a7d-41fe-916e-9e4c963d46e7"),
+        "What drained your energy today, and what gave it back?",
+    ),
+    (
+        uuid!("90b5fb39-3438-424f-854f-71f25b058f95"),
+        "Who did you talk to today, and how did it leave you feeling?",
+    ),
+    (
+        uuid!("8b4c2947-52cb-49e2-adcd-bc1fb87ae323"),
+        "What's something you learned recently?",
+    ),
+    (
+        uuid!("de111ed1-0f01-451f-bb8f-f6396752a4b1"),
+        "What would make tomorrow a good day?",
+    ),
+    (
+        uuid!("c4efe9c8-f2e1-495e-a37d-07599182b405"),
+        "What's something you're looking forward to?",
+    ),
+    (
+        uuid!("830693a8-69be-48a2-a846-29a510a44f79"),
+        "What did you do today just for yourself?",
+    ),
+    (
+        uuid!("5fbb7871-f080-4f27-9b31-4b2f252db9c3"),
+        "What's been harder than it should be lately?",
+    ),
+    (
+        uuid!("53435971-1921-4add-be85-5249d5d4e82d"),
+        "Describe a small moment from today you'd like to remember.",
+    ),
+    (
+        uuid!("2f79a5e9-9137-407e-904f-195d351912b8"),
+        "What's a worry you can let go of tonight?",
+    ),
+    (
+        uuid!("0bd457dd-df44-4cb9-8d45-2b5ec5a02e9e"),
+        "How did you take care of your body today?",
+    ),
+    (
+        uuid!("cfe999df-d587-475e-8635-96d06c2bfc4a"),
+        "What's something you're proud of this week?",
+    ),
+    (
+        uuid!("c5100391-af00-479e-ab53-b449f0563913"),
+        "If today had a title, what would it be?",
+    ),
+    (
+        uuid!("2d604701-ca4c-4582-9f8d-654047015225"),
+        "What did you notice today that you usually overlook?",
+    ),
+    (
+        uuid!("8205de4d-2a7a-4134-bf64-76178b3862a9"),
+        "Who would you like to thank, and for what?",
+    ),
+    (
+        uuid!("495e50f5-cb62-429f-aafd-8e41fbb62260"),
+        "What's one thing you'd do differently if you could redo today?",
+    ),
+    (
+        uuid!("ea8bd63d-657a-41bc-bdf2-6e80eab06e57"),
+        "What's on your mind that you haven't said out loud?",
+    ),
+    (
+        uuid!("831d3295-0dd5-4649-91f5-fa4e0847be17"),
+        "What made you feel most like yourself today?",
+    ),
+    (
+        uuid!("db69aeaf-4cb4-4bc9-85ac-c7b0e822838b"),
+        "What are you avoiding, and why?",
+    ),
+    (
+        uuid!("049a231a-15d8-4129-88b4-15e08a70704c"),
+        "What's a decision you're weighing right now?",
+    ),
+    (
+        uuid!("234e993c-7f26-4398-ae61-ad276062e95c"),
+        "Where did you spend most of your time today, and did it feel right?",
+    ),
+    (
+        uuid!("47efcddd-21fe-4426-87bd-2aa96052e5a6"),
+        "What's something kind someone did for you recently?",
+    ),
+    (
+        uuid!("3088c1c1-0314-497d-b538-b08109eaf2c2"),
+        "What song, book or show has stuck with you lately?",
+    ),
+    (
+        uuid!("207273fa-b270-4107-a3ac-9499ccc3be8a"),
+        "How are you feeling compared to a week ago?",
+    ),
+    (
+        uuid!("534046f6-d2a9-49cc-95c7-b0fa78298d27"),
+        "What's a goal you've been quietly working towards?",
+    ),
+    (
+        uuid!("aead27f2-8949-4b3d-becb-7796673afce5"),
+        "What would you tell yourself from a year ago?",
+    ),
+    (
+        uuid!("a9779334-4029-4bcc-bfbb-2fb8904a5495"),
+        "What surprised you today?",
+    ),
+    (
+        uuid!("49ea594b-7dda-4de6-8aaf-a43764d504bf"),
+        "What do you need more of right now?",
+    ),
+];
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedPrompt {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub encrypted_text: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+// built in prompts have no author and were never created
+#[derive(Debug, Clone, Serialize)]
+pub struct Prompt {
+    pub id: Uuid,
+    pub text: String,
+    pub built_in: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl EncryptedPrompt {
+    pub fn seal_text(
+        id: Uuid,
+        author: Uuid,
+        text: &str,
+        user_key: &UserKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to prompt author");
+        }
+
+        envelope::seal(
+            &user_key.key,
+            &envelope::pad(text.as_bytes())?,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::PromptText,
+                id: &id,
+                author: &author,
+            },
+        )
+    }
+
+    pub fn decrypt(&self, user_key: &UserKey) -> anyhow::Result<Prompt> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to prompt author");
+        }
+
+        let padded = envelope::open(
+            &user_key.key,
+            &self.encrypted_text,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::PromptText,
+                id: &self.id,
+                author: &self.author,
+            },
+        )?;
+
+        Ok(Prompt {
+            id: self.id,
+            text: String::from_utf8(envelope::unpad(&padded)?.to_vec())?,
+            built_in: false,
+            created_at: Some(self.created_at),
+        })
+    }
+}
+
+impl Prompt {
+    pub fn built_in() -> impl Iterator<Item = Self> {
+        BUILT_IN_PROMPTS.iter().map(|(id, text)| Self {
+            id: *id,
+            text: (*text).to_string(),
+            built_in: true,
+            created_at: None,
+        })
+    }
+
+    pub fn is_built_in(id: &Uuid) -> bool {
+        BUILT_IN_PROMPTS
+            .iter()
+            .any(|(built_in_id, _)| built_in_id == id)
+    }
+
+    // the same user gets the same prompt all day, and a different user most likely gets another one.
+    // `prompts` has to come in a stable order, a new user prompt reshuffles every day after it
+    pub fn daily(user_id: Uuid, date: NaiveDate, prompts: &[Self]) -> Option<&Self> {
+        let digest = Sha256::new()
+            .chain_update(b"jrnl:daily_prompt:")
+            .chain_update(user_id.as_bytes())
+            .chain_update(date.to_string().as_bytes())
+            .finalize();
+
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&digest[..8]);
+
+        let len = u64::try_from(prompts.len()).ok()?;
+        let index = u64::from_le_bytes(seed).checked_rem(len)?;
+        prompts.get(usize::try_from(index).ok()?)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{
+    crypto::envelope::{self, EnvelopePurpose, OwnedBinding},
+    schemas::user_key::UserKey,
+};
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedTag {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub encrypted_name: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tag {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EncryptedTag {
+    pub fn seal_name(
+        id: Uuid,
+        author: Uuid,
+        name: &str,
+        user_key: &UserKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to tag author");
+        }
+
+        envelope::seal(
+            &user_key.key,
+            &envelope::pad(name.as_bytes())?,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::TagName,
+                id: &id,
+                author: &author,
+            },
+        )
+    }
+
+    pub fn decrypt(&self, user_key: &UserKey) -> anyhow::Result<Tag> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to tag author");
+        }
+
+        let padded = envelope::open(
+            &user_key.key,
+            &self.encrypted_name,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::TagName,
+                id: &self.id,
+                author: &self.author,
+            },
+        )?;
+
+        Ok(Tag {
+            id: self.id,
+            name: String::from_utf8(envelope::unpad(&padded)?.to_vec())?,
+            created_at: self.created_at,
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    crypto::envelope::{self, EnvelopePurpose, OwnedBinding, TrackerValueBinding},
+    schemas::user_key::UserKey,
+};
+use anyhow::{bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// the sealed definition is `kind (u8) || unit length (u8) || unit || name`, an empty unit being none
+const DEFINITION_HEADER_LEN: usize = 2;
+
+// counts are whole numbers, this keeps them exact as f64
+const MAX_COUNT: f64 = 1_000_000.0;
+const MAX_NUMERIC: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackerKind {
+    Boolean,
+    Numeric,
+    Count,
+}
+
+impl TrackerKind {
+    const fn as_byte(self) -> u8 {
+        match self {
+            Self::Boolean => 0,
+            Self::Numeric => 1,
+            Self::Count => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> anyhow::Result<Self> {
+        Ok(match byte {
+            0 => Self::Boolean,
+            1 => Self::Numeric,
+            2 => Self::Count,
+            _ => bail!("unknown tracker kind {byte}"),
+        })
+    }
+
+    // none when the value doesn't fit, counts are non negative whole numbers
+    pub fn validate(self, value: TrackerValue) -> Option<TrackerValue> {
+        match (self, value) {
+            (Self::Boolean, TrackerValue::Boolean(_)) => Some(value),
+            (Self::Count, TrackerValue::Number(number))
+                if (0.0..=MAX_COUNT).contains(&number) && number.fract() == 0.0 =>
+            {
+                Some(value)
+            }
+            (Self::Numeric, TrackerValue::Number(number))
+                if number.is_finite() && number.abs() <= MAX_NUMERIC =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TrackerValue {
+    Boolean(bool),
+    Number(f64),
+}
+
+impl TrackerValue {
+    // booleans count as 1 and 0, so their average is the share of days they were done
+    pub const fn as_f64(self) -> f64 {
+        match self {
+            Self::Boolean(true) => 1.0,
+            Self::Boolean(false) => 0.0,
+            Self::Number(number) => number,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedTracker {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub encrypted_definition: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tracker {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: TrackerKind,
+    pub unit: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EncryptedTracker {
+    pub fn seal_definition(
+        id: Uuid,
+        author: Uuid,
+        name: &str,
+        kind: TrackerKind,
+        unit: Option<&str>,
+        user_key: &UserKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to tracker author");
+        }
+
+        let unit = unit.unwrap_or_default();
+        let mut data = vec![
+            kind.as_byte(),
+            u8::try_from(unit.len()).context("tracker unit is too long")?,
+        ];
+        data.extend_from_slice(unit.as_bytes());
+        data.extend_from_slice(name.as_bytes());
+
+        envelope::seal(
+            &user_key.key,
+            &envelope::pad(&data)?,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::TrackerDefinition,
+                id: &id,
+                author: &author,
+            },
+        )
+    }
+
+    pub fn decrypt(&self, user_key: &UserKey) -> anyhow::Result<Tracker> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to tracker author");
+        }
+
+        let padded = envelope::open(
+            &user_key.key,
+            &self.encrypted_definition,
+            &OwnedBinding {
+                purpose: EnvelopePurpose::TrackerDefinition,
+                id: &self.id,
+                author: &self.author,
+            },
+        )?;
+
+        let ([kind, unit_len], rest) = envelope::unpad(&padded)?
+            .split_first_chunk::<DEFINITION_HEADER_LEN>()
+            .context("tracker definition is too short")?;
+        let (unit, name) = rest
+            .split_at_checked(usize::from(*unit_len))
+            .context("tracker unit is cut short")?;
+        let unit = String::from_utf8(unit.to_vec())?;
+
+        Ok(Tracker {
+            id: self.id,
+            name: String::from_utf8(name.to_vec())?,
+            kind: TrackerKind::from_byte(*kind)?,
+            unit: (!unit.is_empty()).then_some(unit),
+            created_at: self.created_at,
+        })
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct EncryptedTrackerValue {
+    pub tracker_id: Uuid,
+    pub author: Uuid,
+    pub date: NaiveDate,
+    pub encrypted_value: Vec<u8>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackerDayValue {
+    pub tracker_id: Uuid,
+    pub date: NaiveDate,
+    pub value: TrackerValue,
+    pub updated_at: DateTime<Utc>,
+}
+
+// values are sealed as an f64 and read back by the tracker's kind, which never changes
+impl EncryptedTrackerValue {
+    pub fn seal_value(
+        tracker_id: Uuid,
+        author: Uuid,
+        date: NaiveDate,
+        value: TrackerValue,
+        user_key: &UserKey,
+    ) -> anyhow::Result<Vec<u8>> {
+        if user_key.user_id != author {
+            bail!("user key does not belong to tracker author");
+        }
+
+        envelope::seal(
+            &user_key.key,
+            &value.as_f64().to_le_bytes(),
+            &TrackerValueBinding {
+                tracker_id: &tracker_id,
+                author: &author,
+                date: &date,
+            },
+        )
+    }
+
+    pub fn decrypt(
+        &self,
+        kind: TrackerKind,
+        user_key: &UserKey,
+    ) -> anyhow::Result<TrackerDayValue> {
+        if user_key.user_id != self.author {
+            bail!("user key does not belong to tracker author");
+        }
+
+        let value = envelope::open(
+            &user_key.key,
+            &self.encrypted_value,
+            &TrackerValueBinding {
+                tracker_id: &self.tracker_id,
+                author: &self.author,
+                date: &self.date,
+            },
+        )?;
+        let value = f64::from_le_bytes(
+            value
+                .as_slice()
+                .try_into()
+                .context("tracker value has the wrong length")?,
+        );
+
+        Ok(TrackerDayValue {
+            tracker_id: self.tracker_id,
+            date: self.date,
+            value: match kind {
+                TrackerKind::Boolean => TrackerValue::Boolean(value != 0.0),
+                TrackerKind::Numeric | TrackerKind::Count => TrackerValue::Number(value),
+            },
+            updated_at: self.updated_at,
+        })
+    }
+}
@@ -1,10 +1,11 @@
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 use chrono_tz::Tz;
 use serde::Serialize;
 use sqlx::FromRow;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, FromRow, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct User {
     pub id: Uuid,
     pub name: String,
@@ -13,7 +14,13 @@ pub struct User {
     pub theme: Option<String>,
     pub timezone: String,
     pub has_had_tour: bool,
-    pub has_seen_app_push: bool
+    pub has_seen_app_push: bool,
+    pub e2e_enabled: bool,
+    pub hide_metadata: bool,
+    // how many previous versions are kept per entry
+    pub revision_limit: i32,
+    // missed days per calendar month that don't break a streak
+    pub streak_freezes: i32,
 }
 
 impl User {
@@ -28,4 +35,14 @@ impl User {
     pub fn current_date_by_timezone(&self) -> NaiveDate {
         self.current_date_time_by_timezone().date_naive()
     }
+
+    // where `date` starts in the user's timezone. a dst change can skip midnight, the day then starts an hour in
+    pub fn start_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = date.and_time(NaiveTime::MIN);
+
+        [midnight, midnight + TimeDelta::hours(1)]
+            .into_iter()
+            .find_map(|time| time.and_local_timezone(self.timezone()).earliest())
+            .map_or_else(|| midnight.and_utc(), |time| time.to_utc())
+    }
 }
@@ -0,0 +1,77 @@
+use crate::crypto::{envelope::UserKeyBinding, key_provider::KeyProvider};
+use aes_gcm::{aead::OsRng, Aes256Gcm, Key, KeyInit};
+use anyhow::bail;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// a user's data key as stored, wrapped by the master key.
+// it wraps every content key that user owns, so deleting this row makes all of their entries unreadable
+#[derive(Debug, Clone, FromRow)]
+pub struct WrappedUserKey {
+    pub user_id: Uuid,
+    pub wrapped_key: Vec<u8>,
+    pub key_version: i32,
+}
+
+#[derive(Clone)]
+pub struct UserKey {
+    pub user_id: Uuid,
+    pub key: Key<Aes256Gcm>,
+    // master key version this was unwrapped with, keys derived from the user key are rotated along with it
+    pub key_version: i32,
+}
+
+impl WrappedUserKey {
+    pub async fn generate(user_id: Uuid, key_provider: &dyn KeyProvider) -> anyhow::Result<Self> {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let wrapped = key_provider
+            .wrap(&key[..], &UserKeyBinding { user_id: &user_id })
+            .await?;
+
+        Ok(Self {
+            user_id,
+            wrapped_key: wrapped.ciphertext,
+            key_version: wrapped.version,
+        })
+    }
+
+    pub async fn unwrap(&self, key_provider: &dyn KeyProvider) -> anyhow::Result<UserKey> {
+        let key = key_provider
+            .unwrap(
+                self.key_version,
+                &self.wrapped_key,
+                &UserKeyBinding {
+                    user_id: &self.user_id,
+                },
+            )
+            .await?;
+
+        if key.len() != 32 {
+            bail!("unwrapped user key has the wrong length");
+        }
+
+        Ok(UserKey {
+            user_id: self.user_id,
+            key: *Key::<Aes256Gcm>::from_slice(&key),
+            key_version: self.key_version,
+        })
+    }
+
+    // moves the user key over to the active master key, none of the user's entries need to be touched
+    pub async fn rewrap(&mut self, key_provider: &dyn KeyProvider) -> anyhow::Result<()> {
+        let rewrapped = key_provider
+            .rewrap(
+                self.key_version,
+                &self.wrapped_key,
+                &UserKeyBinding {
+                    user_id: &self.user_id,
+                },
+            )
+            .await?;
+
+        self.wrapped_key = rewrapped.ciphertext;
+        self.key_version = rewrapped.version;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    impl_service,
+    schemas::{attachment::EncryptedAttachment, user::User},
+    storage::BlobStorage,
+};
+use sqlx::{Error, PgConnection, PgPool};
+use std::{sync::Arc, time::Duration};
+use tokio::time::interval;
+use tracing::error;
+use uuid::Uuid;
+
+const BLOB_DELETION_BATCH_SIZE: i64 = 100;
+
+pub struct AttachmentService(PgPool);
+impl_service!(AttachmentService);
+
+impl AttachmentService {
+    pub async fn get_entry_attachments(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+    ) -> Result<Vec<EncryptedAttachment>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM attachments WHERE author = $1 AND entry_id = $2 ORDER BY created_at, id",
+        )
+        .bind(user.id)
+        .bind(entry_id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_attachment(
+        &self,
+        user: &User,
+        id: &Uuid,
+    ) -> Result<Option<EncryptedAttachment>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM attachments WHERE author = $1 AND id = $2",
+        )
+        .bind(user.id)
+        .bind(id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // how many attachments the entry already has, none if the entry can't take attachments at all.
+    // like tags, ephemeral entries can't have any since they're thrown away at the end of the day
+    pub async fn get_entry_attachment_count(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+    ) -> Result<Option<i64>, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "
+                SELECT (SELECT COUNT(*) FROM attachments WHERE author = $2 AND entry_id = $1)
+                WHERE EXISTS (SELECT 1 FROM entries WHERE id = $1 AND author = $2)
+                   OR EXISTS (SELECT 1 FROM active_entries WHERE id = $1 AND author = $2 AND NOT ephemeral)
+            ",
+        )
+        .bind(entry_id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // the blob has to be stored before the row is written, a row never points at a blob that isn't there
+    pub async fn create_attachment(
+        &self,
+        attachment: &EncryptedAttachment,
+    ) -> Result<EncryptedAttachment, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                INSERT INTO attachments (id, entry_id, author, content_type, size, blob_key, storage_key)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING *
+            ",
+        )
+        .bind(attachment.id)
+        .bind(attachment.entry_id)
+        .bind(attachment.author)
+        .bind(&attachment.content_type)
+        .bind(attachment.size)
+        .bind(&attachment.blob_key)
+        .bind(&attachment.storage_key)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // the blob is queued for deletion along with the row
+    pub async fn delete_attachment(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM attachments WHERE author = $1 AND id = $2",
+        )
+        .bind(user.id)
+        .bind(id)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    pub async fn delete_entry_attachments(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_ids: &[Uuid],
+    ) -> Result<(), Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM attachments WHERE author = $1 AND entry_id = ANY($2)",
+        )
+        .bind(author)
+        .bind(entry_ids)
+        .execute(connection)
+        .await
+        .map(|_| ())
+    }
+}
+
+// removes blobs whose rows are gone, deletions that fail stay queued and are tried again on the next tick
+pub async fn purge_deleted_attachment_blobs(pool: PgPool, blob_storage: Arc<dyn BlobStorage>) {
+    let mut ticker = interval(Duration::from_mins(5));
+    loop {
+        ticker.tick().await;
+
+        let storage_keys = sqlx::query_scalar::<_, String>(
+            // language=postgresql
+            "SELECT storage_key FROM attachment_blob_deletions ORDER BY queued_at LIMIT $1",
+        )
+        .bind(BLOB_DELETION_BATCH_SIZE)
+        .fetch_all(&pool)
+        .await;
+
+        let storage_keys = match storage_keys {
+            Ok(storage_keys) => storage_keys,
+            Err(why) => {
+                error!("Failed to fetch queued attachment blob deletions: {}", why);
+                continue;
+            }
+        };
+
+        let mut deleted = Vec::with_capacity(storage_keys.len());
+        for storage_key in storage_keys {
+            match blob_storage.delete(&storage_key).await {
+                Ok(()) => deleted.push(storage_key),
+                Err(why) => error!("Failed to delete attachment blob {}: {}", storage_key, why),
+            }
+        }
+
+        if deleted.is_empty() {
+            continue;
+        }
+
+        let dequeue_future = sqlx::query(
+            // language=postgresql
+            "DELETE FROM attachment_blob_deletions WHERE storage_key = ANY($1)",
+        )
+        .bind(&deleted)
+        .execute(&pool);
+
+        if let Err(why) = dequeue_future.await {
+            error!("Failed to dequeue deleted attachment blobs: {}", why);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    impl_service,
+    schemas::{
+        checkin::{Checkin, EncryptedCheckin},
+        user::User,
+        user_key::UserKey,
+    },
+    services::stats_service::{day_summary, mean},
+};
+use chrono::{DateTime, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use sqlx::{Error, PgPool};
+use std::collections::BTreeMap;
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+pub struct CheckinService(PgPool);
+impl_service!(CheckinService);
+
+impl CheckinService {
+    // `from` inclusive and `to` exclusive, oldest first
+    pub async fn get_checkins(
+        &self,
+        user: &User,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<EncryptedCheckin>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                SELECT * FROM checkins
+                WHERE author = $1 AND recorded_at >= $2 AND recorded_at < $3
+                ORDER BY recorded_at, id
+            ",
+        )
+        .bind(user.id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_checkins_count(
+        &self,
+        user: &User,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<i64, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT COUNT(*) FROM checkins WHERE author = $1 AND recorded_at >= $2 AND recorded_at < $3",
+        )
+        .bind(user.id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // the data is sealed against the id, so the id is picked before the row is written
+    pub async fn create_checkin(
+        &self,
+        user: &User,
+        id: Uuid,
+        encrypted_data: &[u8],
+        recorded_at: DateTime<Utc>,
+    ) -> Result<EncryptedCheckin, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                INSERT INTO checkins (id, author, encrypted_data, recorded_at)
+                VALUES ($1, $2, $3, $4)
+                RETURNING *
+            ",
+        )
+        .bind(id)
+        .bind(user.id)
+        .bind(encrypted_data)
+        .bind(recorded_at)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    pub async fn delete_checkin(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM checkins WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+pub async fn decrypt_checkins(
+    checkins: Vec<EncryptedCheckin>,
+    user_key: UserKey,
+) -> anyhow::Result<Vec<Checkin>> {
+    spawn_blocking(move || {
+        checkins
+            .iter()
+            .map(|checkin| checkin.decrypt(&user_key))
+            .collect()
+    })
+    .await?
+}
+
+// check-ins are always sealed, so every number here is worked out after they're opened
+#[derive(Debug, Serialize)]
+pub struct CheckinPatterns {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub count: i64,
+    pub average: Option<f64>,
+    // every hour of the day in the user's timezone, midnight first, even without check-ins
+    pub hours: Vec<HourStats>,
+    // one per day with a check-in
+    pub days: Vec<CheckinDay>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HourStats {
+    pub hour: u32,
+    pub count: i64,
+    pub average: Option<f64>,
+}
+
+// `emotion_scale` is the summary the day's entry gets when its scale is taken from check-ins
+#[derive(Debug, Serialize)]
+pub struct CheckinDay {
+    pub date: NaiveDate,
+    pub count: i64,
+    pub emotion_scale: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl CheckinPatterns {
+    pub fn new(from: NaiveDate, to: NaiveDate, checkins: &[Checkin], timezone: Tz) -> Self {
+        let local = checkins
+            .iter()
+            .map(|checkin| {
+                (
+                    checkin.recorded_at.with_timezone(&timezone),
+                    checkin.emotion_scale,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let hours = (0..24)
+            .map(|hour| {
+                let values = local
+                    .iter()
+                    .filter(|(recorded_at, _)| recorded_at.hour() == hour)
+                    .map(|(_, scale)| f64::from(*scale))
+                    .collect::<Vec<_>>();
+
+                HourStats {
+                    hour,
+                    count: i64::try_from(values.len()).unwrap_or(i64::MAX),
+                    average: mean(&values),
+                }
+            })
+            .collect();
+
+        let mut days = BTreeMap::<NaiveDate, Vec<f32>>::new();
+        for (recorded_at, scale) in &local {
+            days.entry(recorded_at.date_naive())
+                .or_default()
+                .push(*scale);
+        }
+
+        let days = days
+            .into_iter()
+            .filter_map(|(date, scales)| {
+                Some(CheckinDay {
+                    date,
+                    count: i64::try_from(scales.len()).unwrap_or(i64::MAX),
+                    emotion_scale: day_summary(&scales)?,
+                    min: scales.iter().copied().reduce(f32::min)?,
+                    max: scales.iter().copied().reduce(f32::max)?,
+                })
+            })
+            .collect();
+
+        let values = local
+            .iter()
+            .map(|(_, scale)| f64::from(*scale))
+            .collect::<Vec<_>>();
+
+        Self {
+            from,
+            to,
+            count: i64::try_from(values.len()).unwrap_or(i64::MAX),
+            average: mean(&values),
+            hours,
+            days,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    error::{JrnlError, JrnlResult},
+    impl_service,
+    schemas::{
+        entry_revision::{EntryRevision, EntryRevisionSummary},
+        user::User,
+    },
+    services::entry_service::EntryService,
+};
+use sqlx::{Error, PgConnection, PgPool};
+use uuid::Uuid;
+
+pub struct EntryRevisionService(PgPool);
+impl_service!(EntryRevisionService);
+
+impl EntryRevisionService {
+    pub async fn get_revisions(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+    ) -> Result<Vec<EntryRevisionSummary>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                SELECT id, entry_id, created_at FROM entry_revisions
+                WHERE author = $1 AND entry_id = $2
+                ORDER BY created_at DESC, id DESC
+            ",
+        )
+        .bind(user.id)
+        .bind(entry_id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_revision_maybe(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+        revision_id: &Uuid,
+    ) -> Result<Option<EntryRevision>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM entry_revisions WHERE author = $1 AND entry_id = $2 AND id = $3 LIMIT 1",
+        )
+        .bind(user.id)
+        .bind(entry_id)
+        .bind(revision_id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // the current version becomes a revision itself, so a restore can always be undone.
+    // returns false if either the revision or the entry is gone
+    pub async fn restore_revision(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+        revision_id: &Uuid,
+    ) -> JrnlResult<bool> {
+        let mut transaction = self.0.begin().await?;
+
+        let Some(revision) = sqlx::query_as::<_, EntryRevision>(
+            // language=postgresql
+            "SELECT * FROM entry_revisions WHERE author = $1 AND entry_id = $2 AND id = $3 FOR UPDATE",
+        )
+        .bind(user.id)
+        .bind(entry_id)
+        .bind(revision_id)
+        .fetch_optional(&mut *transaction)
+        .await?
+        else {
+            return Ok(false);
+        };
+
+        let restored = if Self::snapshot_entry(&mut transaction, user.id, *entry_id).await? != 0 {
+            sqlx::query(
+                // language=postgresql
+                "
+                    UPDATE entries
+                    SET emotion_scale = $1, encrypted_emotion_scale = $2, encrypted_content = $3, content_key = $4,
+                        nonce = $5, key_version = $6, client_encrypted = $7
+                    WHERE id = $8 AND author = $9
+                ",
+            )
+            .bind(revision.emotion_scale)
+            .bind(&revision.encrypted_emotion_scale)
+            .bind(&revision.encrypted_content)
+            .bind(&revision.content_key)
+            .bind(&revision.nonce)
+            .bind(revision.key_version)
+            .bind(revision.client_encrypted)
+            .bind(entry_id)
+            .bind(user.id)
+            .execute(&mut *transaction)
+            .await?;
+
+            // the maintenance task indexes it again
+            EntryService::replace_search_tokens(&mut transaction, &revision.as_entry(), None)
+                .await?;
+
+            true
+        } else {
+            Self::restore_active_entry(&mut transaction, user, &revision).await?
+        };
+
+        if !restored {
+            return Ok(false);
+        }
+
+        Self::prune(&mut transaction, user.id, Some(*entry_id)).await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn restore_active_entry(
+        connection: &mut PgConnection,
+        user: &User,
+        revision: &EntryRevision,
+    ) -> JrnlResult<bool> {
+        // only past entries ever had these, today's entry can't hold them. the maintenance task moves the
+        // revision onto the current envelope under the user key, after which it can be restored
+        if revision.nonce.is_some() || revision.key_version.is_some() {
+            return Err(JrnlError::LegacyRevisionRestore);
+        }
+
+        Self::snapshot_active_entry(&mut *connection, user.id, revision.entry_id).await?;
+
+        let (encrypted_content, content_key, ciphertext) = if revision.client_encrypted {
+            (None, None, Some(&revision.encrypted_content))
+        } else {
+            (
+                Some(&revision.encrypted_content),
+                Some(&revision.content_key),
+                None,
+            )
+        };
+
+        let updated = sqlx::query(
+            // language=postgresql
+            "
+                UPDATE active_entries
+                SET emotion_scale = $1, encrypted_emotion_scale = $2, text = NULL, encrypted_content = $3,
+                    content_key = $4, ciphertext = $5
+                WHERE id = $6 AND author = $7
+            ",
+        )
+        .bind(revision.emotion_scale)
+        .bind(&revision.encrypted_emotion_scale)
+        .bind(encrypted_content)
+        .bind(content_key)
+        .bind(ciphertext)
+        .bind(revision.entry_id)
+        .bind(user.id)
+        .execute(&mut *connection)
+        .await?
+        .rows_affected();
+
+        Ok(updated != 0)
+    }
+
+    pub async fn snapshot_entry(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_id: Uuid,
+    ) -> Result<u64, Error> {
+        sqlx::query(
+            // language=postgresql
+            "
+                INSERT INTO entry_revisions (entry_id, author, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, nonce, key_version, client_encrypted)
+                SELECT id, author, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, nonce, key_version, client_encrypted
+                FROM entries
+                WHERE id = $1 AND author = $2
+            ",
+        )
+            .bind(entry_id)
+            .bind(author)
+            .execute(connection)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    // ephemeral entries don't get a history, and rows still waiting to be sealed are never copied
+    pub async fn snapshot_active_entry(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_id: Uuid,
+    ) -> Result<u64, Error> {
+        sqlx::query(
+            // language=postgresql
+            "
+                INSERT INTO entry_revisions (entry_id, author, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, client_encrypted)
+                SELECT id, author, date, emotion_scale, encrypted_emotion_scale, COALESCE(ciphertext, encrypted_content),
+                       COALESCE(content_key, ''::bytea), ciphertext IS NOT NULL
+                FROM active_entries
+                WHERE id = $1 AND author = $2 AND NOT ephemeral
+                AND (ciphertext IS NOT NULL OR (encrypted_content IS NOT NULL AND content_key IS NOT NULL))
+            ",
+        )
+            .bind(entry_id)
+            .bind(author)
+            .execute(connection)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    // keeps the newest `users.revision_limit` revisions of every entry, or only of one
+    pub async fn prune(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_id: Option<Uuid>,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            // language=postgresql
+            "
+                DELETE FROM entry_revisions WHERE id IN (
+                    SELECT id FROM (
+                        SELECT entry_revisions.id, users.revision_limit,
+                               row_number() OVER (PARTITION BY entry_id ORDER BY created_at DESC, entry_revisions.id DESC) AS position
+                        FROM entry_revisions
+                        JOIN users ON users.id = entry_revisions.author
+                        WHERE entry_revisions.author = $1
+                        AND ($2::uuid IS NULL OR entry_revisions.entry_id = $2)
+                    ) ranked
+                    WHERE position > revision_limit
+                )
+            ",
+        )
+            .bind(author)
+            .bind(entry_id)
+            .execute(connection)
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn delete_revisions(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_ids: &[Uuid],
+    ) -> Result<(), Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM entry_revisions WHERE author = $1 AND entry_id = ANY($2)",
+        )
+        .bind(author)
+        .bind(entry_ids)
+        .execute(connection)
+        .await
+        .map(|_| ())
+    }
+}
@@ -1,27 +1,362 @@
 use crate::{
+    crypto::{
+        blind_index::{EntrySearchTokens, SearchIndexKey},
+        envelope::{EntryBinding, EnvelopePurpose},
+        key_provider::KeyProvider,
+    },
     error::JrnlResult,
     impl_service,
-    schemas::{active_entry::ActiveEntry, entry::EncryptedEntry, user::User},
-    web::cursor::Cursor,
+    schemas::{
+        active_entry::{ActiveEntry, EncryptedActiveEntry},
+        entry::{open_emotion_scale, DecryptedEntry, EncryptedEntry},
+        entry_revision::EntryRevision,
+        user::User,
+        user_key::{UserKey, WrappedUserKey},
+    },
+    services::{
+        attachment_service::AttachmentService, entry_revision_service::EntryRevisionService,
+        tag_service::TagService, user_key_service::UserKeyService, user_service::UserService,
+    },
+    web::cursor::{Cursor, CursorKey},
 };
-use aes_gcm::{Aes256Gcm, Key};
-use chrono::{NaiveDate, Timelike};
-use serde::Serialize;
-use sqlx::{postgres::PgArguments, query::Query, Error, FromRow, PgPool, Postgres, Transaction};
-use std::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    postgres::PgArguments, query::Query, Error, FromRow, PgConnection, PgPool, Postgres,
+    Transaction,
+};
+use std::{future::Future, sync::Arc, time::Duration};
 use tokio::{task::spawn_blocking, time::interval};
-use tracing::warn;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 pub struct EntryService(PgPool);
 
 impl_service!(EntryService);
 
-#[derive(Serialize, FromRow)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryUploadStatus {
+    Inserted,
+    // an entry with the same id was already uploaded
+    Duplicate,
+    Invalid,
+    EncryptionFailed,
+    // would have been inserted, but another entry in an atomic batch wasn't
+    Aborted,
+}
+
+#[derive(Serialize)]
 pub struct StrippedEntry {
     pub emotion_scale: f32,
     pub date: NaiveDate,
     pub id: Uuid,
+    pub tag_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntrySort {
+    #[default]
+    DateDesc,
+    DateAsc,
+    ScaleDesc,
+    ScaleAsc,
+}
+
+// what the entries list is paged by, it depends on the sort so it's only checked against it once both are read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntrySortKey {
+    Date(EntryDateKey),
+    Scale(f32),
+}
+
+// a day's entries are in the order they were written, the same as everywhere else they're listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EntryDateKey {
+    pub date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+}
+
+// every bound is inclusive
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryFilter {
+    pub tag: Option<Uuid>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_scale: Option<f32>,
+    pub max_scale: Option<f32>,
+    // going by the search index, so entries that aren't indexed (yet) match neither true nor false
+    pub has_text: Option<bool>,
+}
+
+impl EntrySort {
+    pub const fn by_scale(self) -> bool {
+        matches!(self, Self::ScaleDesc | Self::ScaleAsc)
+    }
+
+    pub const fn key(self, entry: &StrippedEntry) -> EntrySortKey {
+        if self.by_scale() {
+            EntrySortKey::Scale(entry.emotion_scale)
+        } else {
+            EntrySortKey::Date(EntryDateKey::of(entry))
+        }
+    }
+
+    pub const fn accepts(self, key: EntrySortKey) -> bool {
+        matches!(key, EntrySortKey::Scale(_)) == self.by_scale()
+    }
+
+    pub const fn reversed(self) -> Self {
+        match self {
+            Self::DateDesc => Self::DateAsc,
+            Self::DateAsc => Self::DateDesc,
+            Self::ScaleDesc => Self::ScaleAsc,
+            Self::ScaleAsc => Self::ScaleDesc,
+        }
+    }
+
+    // the order a page is fetched in, a backward page is fetched walking away from its cursor
+    pub fn paging(self, cursor: Option<&Cursor<EntrySortKey>>) -> Self {
+        if cursor.is_some_and(Cursor::is_backward) {
+            self.reversed()
+        } else {
+            self
+        }
+    }
+
+    // a cursor just before the first entry on `date`, so the page it starts holds that day's entries first
+    pub const fn cursor_at(self, date: NaiveDate) -> Option<Cursor<EntrySortKey>> {
+        match self {
+            Self::DateDesc => Some(Cursor::forward(
+                EntrySortKey::Date(EntryDateKey {
+                    date,
+                    created_at: DateTime::<Utc>::MAX_UTC,
+                }),
+                Uuid::max(),
+            )),
+            Self::DateAsc => Some(Cursor::forward(
+                EntrySortKey::Date(EntryDateKey {
+                    date,
+                    created_at: DateTime::<Utc>::MIN_UTC,
+                }),
+                Uuid::nil(),
+            )),
+            Self::ScaleDesc | Self::ScaleAsc => None,
+        }
+    }
+}
+
+impl CursorKey for EntrySortKey {
+    fn encode_key(&self) -> String {
+        match self {
+            Self::Date(key) => key.encode_key(),
+            Self::Scale(scale) => scale.to_string(),
+        }
+    }
+
+    // dates and scales never look alike
+    fn decode_key(s: &str) -> Option<Self> {
+        EntryDateKey::decode_key(s).map(Self::Date).or_else(|| {
+            s.parse::<f32>()
+                .ok()
+                .filter(|scale| scale.is_finite())
+                .map(Self::Scale)
+        })
+    }
+}
+
+impl EntryDateKey {
+    // past every entry, where a list that's newest first starts
+    pub const LATEST: Self = Self {
+        date: NaiveDate::MAX,
+        created_at: DateTime::<Utc>::MAX_UTC,
+    };
+
+    pub const fn of(entry: &StrippedEntry) -> Self {
+        Self {
+            date: entry.date,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+// the creation time is kept to the microsecond, as precise as postgres stores it
+impl CursorKey for EntryDateKey {
+    fn encode_key(&self) -> String {
+        format!(
+            "{}/{}",
+            self.date.encode_key(),
+            self.created_at.timestamp_micros()
+        )
+    }
+
+    fn decode_key(s: &str) -> Option<Self> {
+        let (date, created_at) = s.split_once('/')?;
+
+        Some(Self {
+            date: NaiveDate::decode_key(date)?,
+            created_at: DateTime::from_timestamp_micros(created_at.parse().ok()?)?,
+        })
+    }
+}
+
+impl EntryFilter {
+    pub const fn by_scale(&self) -> bool {
+        self.min_scale.is_some() || self.max_scale.is_some()
+    }
+
+    pub fn matches_scale(&self, emotion_scale: f32) -> bool {
+        self.min_scale.is_none_or(|min| emotion_scale >= min)
+            && self.max_scale.is_none_or(|max| emotion_scale <= max)
+    }
+}
+
+// every ordering shares its filters, the cursor's key and id are null on the first page. the creation time is
+// only part of the key when ordering by date
+macro_rules! trimmed_entries_query {
+    ($keyset:literal, $order:literal) => {
+        concat!(
+            "
+                SELECT emotion_scale, encrypted_emotion_scale, date, id, author, created_at,
+                       ARRAY(SELECT tag_id FROM entry_tags WHERE entry_id = entries.id ORDER BY tag_id) AS tag_ids
+                FROM entries
+                WHERE entries.author = $1
+                AND ($4::uuid IS NULL OR ",
+            $keyset,
+            ")
+                AND ($6::uuid IS NULL OR EXISTS (SELECT 1 FROM entry_tags WHERE entry_id = entries.id AND tag_id = $6))
+                AND date >= COALESCE($7, '-infinity'::date) AND date <= COALESCE($8, 'infinity'::date)
+                AND ($9::float4 IS NULL OR emotion_scale >= $9)
+                AND ($10::float4 IS NULL OR emotion_scale <= $10)
+                AND ($11::bool IS NULL OR (
+                    search_key_version IS NOT NULL
+                    AND EXISTS (SELECT 1 FROM entry_search_tokens WHERE entry_id = entries.id) = $11
+                ))
+                ORDER BY ",
+            $order,
+            "
+                LIMIT $5
+            "
+        )
+    };
+}
+
+macro_rules! search_entries_query {
+    ($keyset:literal, $order:literal) => {
+        concat!(
+            "
+                SELECT emotion_scale, encrypted_emotion_scale, date, id, author, created_at,
+                       ARRAY(SELECT tag_id FROM entry_tags WHERE entry_id = entries.id ORDER BY tag_id) AS tag_ids
+                FROM entries
+                WHERE author = $1
+                AND search_key_version = $2
+                AND id IN (
+                    SELECT entry_id FROM entry_search_tokens
+                    WHERE author = $1 AND token = ANY($3)
+                    GROUP BY entry_id
+                    HAVING COUNT(*) = cardinality($3)
+                )
+                AND ",
+            $keyset,
+            "
+                ORDER BY ",
+            $order,
+            "
+                LIMIT $7
+            "
+        )
+    };
+}
+
+#[derive(FromRow)]
+pub struct StrippedEntryRow {
+    pub emotion_scale: Option<f32>,
+    pub encrypted_emotion_scale: Option<Vec<u8>>,
+    pub date: NaiveDate,
+    pub id: Uuid,
+    pub author: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub tag_ids: Vec<Uuid>,
+}
+
+impl StrippedEntryRow {
+    pub const fn metadata_hidden(&self) -> bool {
+        self.encrypted_emotion_scale.is_some()
+    }
+
+    pub fn reveal(self, user_key: Option<&UserKey>) -> anyhow::Result<StrippedEntry> {
+        let emotion_scale = reveal_emotion_scale(
+            self.emotion_scale,
+            self.encrypted_emotion_scale.as_deref(),
+            &EntryBinding {
+                purpose: EnvelopePurpose::EmotionScale,
+                id: &self.id,
+                author: &self.author,
+                date: &self.date,
+            },
+            user_key,
+        )?;
+
+        Ok(StrippedEntry {
+            emotion_scale,
+            date: self.date,
+            id: self.id,
+            tag_ids: self.tag_ids,
+            created_at: self.created_at,
+        })
+    }
+}
+
+// plaintext scales are returned as they are, hidden ones are opened with the user key
+pub fn reveal_emotion_scale(
+    emotion_scale: Option<f32>,
+    encrypted_emotion_scale: Option<&[u8]>,
+    binding: &EntryBinding,
+    user_key: Option<&UserKey>,
+) -> anyhow::Result<f32> {
+    match (emotion_scale, encrypted_emotion_scale) {
+        (Some(emotion_scale), _) => Ok(emotion_scale),
+        (None, Some(sealed)) => open_emotion_scale(
+            user_key.context("missing user key for hidden entry")?,
+            binding,
+            sealed,
+        ),
+        (None, None) => anyhow::bail!("entry has no emotion scale"),
+    }
+}
+
+// content keys are unwrapped one at a time since the key provider might be remote, the decryption itself
+// happens in one go off the async runtime
+pub async fn decrypt_entries(
+    entries: Vec<EncryptedEntry>,
+    key_provider: &dyn KeyProvider,
+    user_key: &UserKey,
+) -> anyhow::Result<Vec<DecryptedEntry>> {
+    let mut keyed_entries = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let content_key = if entry.client_encrypted {
+            None
+        } else {
+            Some(entry.unwrap_content_key(key_provider, user_key).await?)
+        };
+
+        keyed_entries.push((entry, content_key));
+    }
+
+    let user_key = user_key.clone();
+    spawn_blocking(move || {
+        keyed_entries
+            .into_iter()
+            .map(|(entry, content_key)| match content_key {
+                Some(content_key) => entry.decrypt(&content_key, &user_key),
+                None => entry.into_client_encrypted(&user_key),
+            })
+            .collect()
+    })
+    .await?
 }
 
 #[derive(FromRow)]
@@ -34,9 +369,9 @@ impl EntryService {
     pub async fn create_entry_migration_transaction_without_today(
         &self,
         user: &User,
-    ) -> Result<(Transaction<'_, Postgres>, Vec<ActiveEntry>), Error> {
+    ) -> Result<(Transaction<'_, Postgres>, Vec<EncryptedActiveEntry>), Error> {
         let mut transaction = self.0.begin().await?;
-        let entries = sqlx::query_as::<_, ActiveEntry>(
+        let entries = sqlx::query_as::<_, EncryptedActiveEntry>(
             // language=postgresql
             "DELETE FROM active_entries WHERE author = $1 AND expiry < timezone('utc', now()) RETURNING *",
         )
@@ -47,12 +382,14 @@ impl EntryService {
         Ok((transaction, entries))
     }
 
-    pub fn create_encrypted_entry_query(entry: &EncryptedEntry) -> Query<Postgres, PgArguments> {
+    pub fn create_encrypted_entry_query(
+        entry: &EncryptedEntry,
+    ) -> Query<'_, Postgres, PgArguments> {
         sqlx::query(
             // language=postgresql
             "
-                INSERT INTO entries (id, author, date, emotion_scale, encrypted_content, content_key, nonce)
-                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                INSERT INTO entries (id, author, date, emotion_scale, encrypted_content, content_key, nonce, key_version, client_encrypted, encrypted_emotion_scale, prompt_id, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             ",
         )
             .bind(entry.id)
@@ -62,54 +399,307 @@ impl EntryService {
             .bind(&entry.encrypted_content)
             .bind(&entry.content_key)
             .bind(&entry.nonce)
+            .bind(entry.key_version)
+            .bind(entry.client_encrypted)
+            .bind(&entry.encrypted_emotion_scale)
+            .bind(entry.prompt_id)
+            .bind(entry.created_at)
     }
 
+    // the id, author and date are part of the envelope binding, so only the sealed columns are replaced
+    pub fn update_encrypted_entry_query(
+        entry: &EncryptedEntry,
+    ) -> Query<'_, Postgres, PgArguments> {
+        sqlx::query(
+            // language=postgresql
+            "
+                UPDATE entries
+                SET emotion_scale = $1, encrypted_emotion_scale = $2, encrypted_content = $3, content_key = $4,
+                    nonce = $5, key_version = $6, client_encrypted = $7
+                WHERE id = $8 AND author = $9
+            ",
+        )
+            .bind(entry.emotion_scale)
+            .bind(&entry.encrypted_emotion_scale)
+            .bind(&entry.encrypted_content)
+            .bind(&entry.content_key)
+            .bind(&entry.nonce)
+            .bind(entry.key_version)
+            .bind(entry.client_encrypted)
+            .bind(entry.id)
+            .bind(entry.author)
+    }
+
+    // `limit` is how many entries make a page, one more is fetched to know if there's another. none fetches every match.
+    // a backward cursor's page comes back in reverse
     pub async fn get_paginated_trimmed_entries(
         &self,
         user: &User,
-        cursor: &Cursor,
+        filter: &EntryFilter,
+        sort: EntrySort,
+        cursor: Option<&Cursor<EntrySortKey>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<StrippedEntryRow>, Error> {
+        let query = match sort.paging(cursor) {
+            EntrySort::DateDesc => trimmed_entries_query!(
+                "(date, created_at, id) < ($2, $3, $4)",
+                "date DESC, created_at DESC, id DESC"
+            ),
+            EntrySort::DateAsc => trimmed_entries_query!(
+                "(date, created_at, id) > ($2, $3, $4)",
+                "date, created_at, id"
+            ),
+            EntrySort::ScaleDesc => trimmed_entries_query!(
+                "(emotion_scale, id) < ($2, $4)",
+                "emotion_scale DESC, id DESC"
+            ),
+            EntrySort::ScaleAsc => {
+                trimmed_entries_query!("(emotion_scale, id) > ($2, $4)", "emotion_scale, id")
+            }
+        };
+
+        let query = sqlx::query_as(query).bind(user.id);
+        let query = match cursor.map(|cursor| cursor.key) {
+            Some(EntrySortKey::Date(key)) => query.bind(key.date).bind(key.created_at),
+            Some(EntrySortKey::Scale(scale)) => query.bind(scale).bind(None::<DateTime<Utc>>),
+            None if sort.by_scale() => query.bind(None::<f32>).bind(None::<DateTime<Utc>>),
+            None => query.bind(None::<NaiveDate>).bind(None::<DateTime<Utc>>),
+        };
+
+        query
+            .bind(cursor.map(|cursor| cursor.id))
+            .bind(limit.map(|limit| limit + 1))
+            .bind(filter.tag)
+            .bind(filter.from)
+            .bind(filter.to)
+            .bind(filter.min_scale)
+            .bind(filter.max_scale)
+            .bind(filter.has_text)
+            .fetch_all(&self.0)
+            .await
+    }
+
+    // sealed scales can't be filtered or sorted on in sql
+    pub async fn has_hidden_scales(&self, user: &User) -> Result<bool, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT EXISTS (SELECT 1 FROM entries WHERE author = $1 AND emotion_scale IS NULL)",
+        )
+        .bind(user.id)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // an entry matches when it has a token for every term, entries indexed under an older key version are skipped.
+    // a backward cursor's page comes back in reverse
+    pub async fn search_entries(
+        &self,
+        user: &User,
+        search_key_version: i32,
+        tokens: &[Vec<u8>],
+        cursor: &Cursor<EntryDateKey>,
         limit: i64,
-    ) -> Result<Vec<StrippedEntry>, Error> {
+    ) -> Result<Vec<StrippedEntryRow>, Error> {
+        let query = if cursor.is_backward() {
+            search_entries_query!(
+                "(date, created_at, id) > ($4, $5, $6)",
+                "date, created_at, id"
+            )
+        } else {
+            search_entries_query!(
+                "(date, created_at, id) < ($4, $5, $6)",
+                "date DESC, created_at DESC, id DESC"
+            )
+        };
+
+        sqlx::query_as(query)
+            .bind(user.id)
+            .bind(search_key_version)
+            .bind(tokens)
+            .bind(cursor.key.date)
+            .bind(cursor.key.created_at)
+            .bind(cursor.id)
+            .bind(limit + 1)
+            .fetch_all(&self.0)
+            .await
+    }
+
+    // no tokens leaves the entry unindexed, client encrypted entries stay that way
+    pub async fn replace_search_tokens(
+        connection: &mut PgConnection,
+        entry: &EncryptedEntry,
+        search_tokens: Option<&EntrySearchTokens>,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM entry_search_tokens WHERE entry_id = $1",
+        )
+        .bind(entry.id)
+        .execute(&mut *connection)
+        .await?;
+
+        if let Some(search_tokens) = search_tokens {
+            sqlx::query(
+                // language=postgresql
+                "
+                    INSERT INTO entry_search_tokens (entry_id, author, token)
+                    SELECT $1, $2, UNNEST($3::bytea[])
+                ",
+            )
+            .bind(entry.id)
+            .bind(entry.author)
+            .bind(&search_tokens.tokens)
+            .execute(&mut *connection)
+            .await?;
+        }
+
+        sqlx::query(
+            // language=postgresql
+            "UPDATE entries SET search_key_version = $1 WHERE id = $2",
+        )
+        .bind(search_tokens.map(|search_tokens| search_tokens.version))
+        .bind(entry.id)
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+
+    // the whole envelope is replaced, so a patched entry never shares a content key with its old text.
+    // returns false if the entry doesn't exist or belongs to someone else
+    pub async fn update_entry(
+        &self,
+        user: &User,
+        entry: &EncryptedEntry,
+        search_tokens: Option<&EntrySearchTokens>,
+    ) -> Result<bool, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        EntryRevisionService::snapshot_entry(&mut transaction, user.id, entry.id).await?;
+
+        let updated = Self::update_encrypted_entry_query(entry)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        Self::replace_search_tokens(&mut transaction, entry, search_tokens).await?;
+        EntryRevisionService::prune(&mut transaction, user.id, Some(entry.id)).await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn delete_entry(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        self.delete_entries(user, &[*id])
+            .await
+            .map(|deleted| deleted != 0)
+    }
+
+    // ids that don't exist or aren't owned by the user are skipped, revisions and attachments go along with their entry
+    pub async fn delete_entries(&self, user: &User, ids: &[Uuid]) -> Result<u64, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        let deleted_ids = sqlx::query_scalar::<_, Uuid>(
+            // language=postgresql
+            "DELETE FROM entries WHERE author = $1 AND id = ANY($2) RETURNING id",
+        )
+        .bind(user.id)
+        .bind(ids)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        EntryRevisionService::delete_revisions(&mut transaction, user.id, &deleted_ids).await?;
+        TagService::delete_entry_tags(&mut transaction, user.id, &deleted_ids).await?;
+        AttachmentService::delete_entry_attachments(&mut transaction, user.id, &deleted_ids)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(deleted_ids.len() as u64)
+    }
+
+    pub async fn get_entry_maybe(
+        &self,
+        user: &User,
+        id: &Uuid,
+    ) -> Result<Option<EncryptedEntry>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM entries WHERE author = $1 AND id = $2 LIMIT 1",
+        )
+        .bind(user.id)
+        .bind(id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // every past year's entry for `date`'s month and day, newest first. on a non leap year february 29th
+    // entries show up on the 28th instead of never
+    pub async fn get_entries_on_this_day(
+        &self,
+        user: &User,
+        date: NaiveDate,
+    ) -> Result<Vec<EncryptedEntry>, Error> {
+        let mut days = vec![date.format("%m-%d").to_string()];
+        if date.month() == 2 && date.day() == 28 && !date.leap_year() {
+            days.push(String::from("02-29"));
+        }
+
         sqlx::query_as(
             // language=postgresql
             "
-                SELECT emotion_scale, date, id FROM entries
-                WHERE entries.author = $1
-                AND (date, id) < ($2, $3)
-                ORDER BY date DESC, id DESC
-                LIMIT $4
+                SELECT * FROM entries
+                WHERE author = $1
+                AND date < $2
+                AND to_char(date, 'MM-DD') = ANY($3)
+                ORDER BY date DESC, created_at, id
             ",
         )
         .bind(user.id)
-        .bind(cursor.date)
-        .bind(cursor.id)
-        .bind(limit + 1)
+        .bind(date)
+        .bind(days)
         .fetch_all(&self.0)
         .await
     }
 
-    pub async fn get_entry_maybe(
+    // hidden scales never match `min_scale` here, they're filtered on after they're opened instead
+    pub async fn get_random_entry(
         &self,
         user: &User,
-        id: &Uuid,
+        min_scale: Option<f32>,
     ) -> Result<Option<EncryptedEntry>, Error> {
         sqlx::query_as(
             // language=postgresql
-            "SELECT * FROM entries WHERE author = $1 AND id = $2 LIMIT 1",
+            "
+                SELECT * FROM entries
+                WHERE author = $1
+                AND ($2::float4 IS NULL OR emotion_scale >= $2)
+                ORDER BY random()
+                LIMIT 1
+            ",
         )
         .bind(user.id)
-        .bind(id)
+        .bind(min_scale)
         .fetch_optional(&self.0)
         .await
     }
 
+    // the most recently written of today's entries
     pub async fn get_user_daily_entry_maybe(
         &self,
         user: &User,
-    ) -> Result<Option<ActiveEntry>, Error> {
+    ) -> Result<Option<EncryptedActiveEntry>, Error> {
         sqlx::query_as(
             // language=postgresql
-            "SELECT * FROM active_entries WHERE author = $1 AND date = $2 LIMIT 1",
+            "
+                SELECT * FROM active_entries
+                WHERE author = $1 AND date = $2
+                ORDER BY created_at DESC, id DESC
+                LIMIT 1
+            ",
         )
         .bind(user.id)
         .bind(user.current_date_by_timezone())
@@ -117,102 +707,296 @@ impl EntryService {
         .await
     }
 
-    pub async fn update_or_create_daily_entry(
+    // oldest first
+    pub async fn get_user_daily_entries(
         &self,
         user: &User,
-        emotion_scale: f32,
-        text: Option<String>,
-        ephemeral: bool,
-    ) -> Result<ActiveEntry, Error> {
+    ) -> Result<Vec<EncryptedActiveEntry>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM active_entries WHERE author = $1 AND date = $2 ORDER BY created_at, id",
+        )
+        .bind(user.id)
+        .bind(user.current_date_by_timezone())
+        .fetch_all(&self.0)
+        .await
+    }
+
+    // entries from a day that already rolled over are no longer today's, even before they've been moved
+    pub async fn get_user_daily_entry_by_id(
+        &self,
+        user: &User,
+        id: &Uuid,
+    ) -> Result<Option<EncryptedActiveEntry>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM active_entries WHERE author = $1 AND date = $2 AND id = $3",
+        )
+        .bind(user.id)
+        .bind(user.current_date_by_timezone())
+        .bind(id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    pub async fn get_user_daily_entries_count(&self, user: &User) -> Result<i64, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT COUNT(*) FROM active_entries WHERE author = $1 AND date = $2",
+        )
+        .bind(user.id)
+        .bind(user.current_date_by_timezone())
+        .fetch_one(&self.0)
+        .await
+    }
+
+    pub fn daily_entry_expiry(user: &User) -> DateTime<Utc> {
         let expiry = user.current_date_time_by_timezone() + chrono::Duration::days(1);
-        let expiry_midnight = expiry
+        expiry
             .with_hour(0)
             .and_then(|d| d.with_minute(0))
             .and_then(|d| d.with_second(0))
             .unwrap_or(expiry)
-            .to_utc();
+            .to_utc()
+    }
 
-        sqlx::query_as(
+    // the entry is sealed against its id, author and date, so an existing row is only replaced when all three
+    // still match, otherwise nothing is written or returned. `created_at` is kept from the first write
+    pub async fn update_or_create_daily_entry(
+        &self,
+        entry: &EncryptedActiveEntry,
+    ) -> Result<Option<EncryptedActiveEntry>, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        EntryRevisionService::snapshot_active_entry(&mut transaction, entry.author, entry.id)
+            .await?;
+
+        let saved = sqlx::query_as::<_, EncryptedActiveEntry>(
             // language=postgresql
             "
-                INSERT INTO active_entries (author, date, emotion_scale, text, expiry) VALUES ($1, $2, $3, $4, $5, $6)
-                ON CONFLICT (author, date)
-                DO UPDATE SET emotion_scale = $3, text = $4, ephemeral = $6
+                INSERT INTO active_entries (id, author, date, emotion_scale, encrypted_content, content_key, expiry, ephemeral, ciphertext, encrypted_emotion_scale, prompt_id, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (id)
+                DO UPDATE SET emotion_scale = $4, text = NULL, encrypted_content = $5, content_key = $6, ephemeral = $8, ciphertext = $9,
+                              encrypted_emotion_scale = $10, prompt_id = $11
+                WHERE active_entries.author = $2 AND active_entries.date = $3
                 RETURNING *
             ",
         )
-            .bind(user.id) // $1
-            .bind(user.current_date_by_timezone()) // $2
-            .bind(emotion_scale) // $3
-            .bind(text) // $4
-            .bind(expiry_midnight) // $5
-            .bind(ephemeral) // $6
-            .fetch_one(&self.0)
-            .await
+            .bind(entry.id) // $1
+            .bind(entry.author) // $2
+            .bind(entry.date) // $3
+            .bind(entry.emotion_scale) // $4
+            .bind(&entry.encrypted_content) // $5
+            .bind(&entry.content_key) // $6
+            .bind(entry.expiry) // $7
+            .bind(entry.ephemeral) // $8
+            .bind(&entry.ciphertext) // $9
+            .bind(&entry.encrypted_emotion_scale) // $10
+            .bind(entry.prompt_id) // $11
+            .bind(entry.created_at) // $12
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+        if saved.is_none() {
+            return Ok(None);
+        }
+
+        // marking an entry ephemeral throws its history, tags and attachments away along with it
+        if entry.ephemeral {
+            EntryRevisionService::delete_revisions(&mut transaction, entry.author, &[entry.id])
+                .await?;
+            TagService::delete_entry_tags(&mut transaction, entry.author, &[entry.id]).await?;
+            AttachmentService::delete_entry_attachments(
+                &mut transaction,
+                entry.author,
+                &[entry.id],
+            )
+            .await?;
+        } else {
+            EntryRevisionService::prune(&mut transaction, entry.author, Some(entry.id)).await?;
+        }
+
+        transaction.commit().await?;
+        Ok(saved)
     }
 
     pub async fn get_multiple_users_entries_between_dates(
         &self,
         group_member_ids: &[Uuid],
         start_date: &NaiveDate,
-        before_date: &NaiveDate,
+        end_date: &NaiveDate,
     ) -> Result<Vec<DayDataRow>, Error> {
+        // one summary scale per member and day. members hiding their metadata are left out, their scales
+        // are never decrypted for anyone else
         sqlx::query_as(
             // language=postgresql
             "
-                SELECT date, emotion_scale FROM entries
+                SELECT date, AVG(emotion_scale)::float4 AS emotion_scale FROM entries
                 WHERE author = ANY($1)
                 AND date >= $2
                 AND date <= $3
+                GROUP BY author, date
+                HAVING COUNT(*) = COUNT(emotion_scale)
                 ORDER BY date DESC
                 LIMIT 500
         ",
         )
         .bind(group_member_ids)
         .bind(start_date)
-        .bind(before_date)
+        .bind(end_date)
         .fetch_all(&self.0)
         .await
     }
 
-    // will ignore any individual errors
+    // whether any day before `date` would show up in the group's day data
+    pub async fn has_multiple_users_entries_before_date(
+        &self,
+        group_member_ids: &[Uuid],
+        date: &NaiveDate,
+    ) -> Result<bool, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "
+                SELECT EXISTS (
+                    SELECT 1 FROM entries
+                    WHERE author = ANY($1)
+                    AND date < $2
+                    GROUP BY author, date
+                    HAVING COUNT(*) = COUNT(emotion_scale)
+                )
+            ",
+        )
+        .bind(group_member_ids)
+        .bind(date)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // returns one status per entry, in order. an atomic batch is rolled back as soon as any entry
+    // can't be inserted, every entry that would have been is reported as aborted instead
     pub async fn insert_many_entries(
         &self,
+        user: &User,
         entries: Vec<ActiveEntry>,
-        master_key: Key<Aes256Gcm>,
-    ) -> JrnlResult<()> {
-        let encrypted_entries = spawn_blocking(move || -> Vec<EncryptedEntry> {
+        user_key: UserKey,
+        atomic: bool,
+    ) -> JrnlResult<Vec<EntryUploadStatus>> {
+        let hide_metadata = user.hide_metadata;
+        let sealed = spawn_blocking(move || {
             entries
-                .into_iter()
-                .filter(|entry| !entry.ephemeral)
-                .filter_map(|entry| ActiveEntry::encrypt(&entry, &master_key).ok())
+                .iter()
+                .map(|entry| entry.encrypt(&user_key, hide_metadata))
                 .collect::<Vec<_>>()
         })
         .await
         .map_err(Into::<anyhow::Error>::into)?;
 
+        for why in sealed.iter().filter_map(|entry| entry.as_ref().err()) {
+            warn!("failed to encrypt uploaded entry for {} {why:?}", user.id);
+        }
+
+        if atomic && sealed.iter().any(Result::is_err) {
+            return Ok(sealed
+                .iter()
+                .map(|entry| {
+                    if entry.is_ok() {
+                        EntryUploadStatus::Aborted
+                    } else {
+                        EntryUploadStatus::EncryptionFailed
+                    }
+                })
+                .collect());
+        }
+
         let mut transaction = self.0.begin().await?;
 
-        for entry in encrypted_entries {
-            let _ = Self::create_encrypted_entry_query(&entry)
-                .execute(&mut *transaction)
-                .await;
+        let encrypted_entries = sealed
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .collect::<Vec<_>>();
+        let inserted =
+            Self::insert_new_entries(&mut transaction, user.id, &encrypted_entries).await?;
+
+        let statuses = sealed
+            .iter()
+            .map(|entry| match entry {
+                Ok(entry) if inserted.contains(&entry.id) => EntryUploadStatus::Inserted,
+                Ok(_) => EntryUploadStatus::Duplicate,
+                Err(_) => EntryUploadStatus::EncryptionFailed,
+            })
+            .collect::<Vec<_>>();
+
+        if atomic
+            && statuses
+                .iter()
+                .any(|status| *status != EntryUploadStatus::Inserted)
+        {
+            transaction.rollback().await?;
+
+            return Ok(statuses
+                .into_iter()
+                .map(|status| match status {
+                    EntryUploadStatus::Inserted => EntryUploadStatus::Aborted,
+                    status => status,
+                })
+                .collect());
         }
 
-        transaction.commit().await.map_err(Into::into)
+        transaction.commit().await?;
+        Ok(statuses)
+    }
+
+    // one round trip for the whole batch, entries whose id is already taken are left out of the returned ids,
+    // so retrying an upload with the same ids never writes an entry twice. only meant for freshly sealed entries,
+    // which never carry legacy key columns
+    pub async fn insert_new_entries(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entries: &[&EncryptedEntry],
+    ) -> Result<Vec<Uuid>, Error> {
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        sqlx::query_scalar(
+            // language=postgresql
+            "
+                INSERT INTO entries (id, author, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, client_encrypted, created_at)
+                SELECT id, $1, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, client_encrypted, created_at
+                FROM UNNEST($2::uuid[], $3::date[], $4::real[], $5::bytea[], $6::bytea[], $7::bytea[], $8::boolean[], $9::timestamptz[])
+                    AS new_entries (id, date, emotion_scale, encrypted_emotion_scale, encrypted_content, content_key, client_encrypted, created_at)
+                ON CONFLICT (id) DO NOTHING
+                RETURNING id
+            ",
+        )
+            .bind(author)
+            .bind(entries.iter().map(|entry| entry.id).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.date).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.emotion_scale).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.encrypted_emotion_scale.clone()).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.encrypted_content.clone()).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.content_key.clone()).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.client_encrypted).collect::<Vec<_>>())
+            .bind(entries.iter().map(|entry| entry.created_at).collect::<Vec<_>>())
+            .fetch_all(connection)
+            .await
     }
 }
 
 // FIXME this needs to be done in a safer way
-pub async fn encrypt_old_entries(pool: PgPool, master_key: Key<Aes256Gcm>) -> anyhow::Result<()> {
-    let mut ticker = interval(Duration::from_secs(60 * 5));
+pub async fn encrypt_old_entries(
+    pool: PgPool,
+    key_provider: Arc<dyn KeyProvider>,
+) -> anyhow::Result<()> {
+    let mut ticker = interval(Duration::from_mins(5));
 
     loop {
         ticker.tick().await;
 
         let mut transaction = pool.begin().await?;
 
-        let entries = sqlx::query_as::<_, ActiveEntry>(
+        let entries = sqlx::query_as::<_, EncryptedActiveEntry>(
             // language=postgresql
             "DELETE FROM active_entries WHERE expiry < timezone('utc', now()) RETURNING *",
         )
@@ -223,11 +1007,24 @@ pub async fn encrypt_old_entries(pool: PgPool, master_key: Key<Aes256Gcm>) -> an
             continue;
         }
 
+        let authors = entries.iter().map(|entry| entry.author).collect::<Vec<_>>();
+        let user_keys =
+            UserKeyService::get_or_create_user_keys(&mut transaction, &authors, &*key_provider)
+                .await?;
+        let hiding_metadata =
+            UserService::get_users_hiding_metadata(&mut transaction, &authors).await?;
+
         let encrypted_entries = spawn_blocking(move || {
             entries
                 .into_iter()
                 .filter(|entry| !entry.ephemeral)
-                .map(|entry| ActiveEntry::encrypt(&entry, &master_key))
+                .map(|entry| {
+                    let user_key = user_keys
+                        .get(&entry.author)
+                        .context("missing user key for entry author")?;
+                    let hide_metadata = hiding_metadata.contains(&entry.author);
+                    entry.into_entry(user_key, hide_metadata)
+                })
                 .collect::<Vec<anyhow::Result<_>>>()
         })
         .await?;
@@ -252,3 +1049,546 @@ pub async fn encrypt_old_entries(pool: PgPool, master_key: Key<Aes256Gcm>) -> an
         transaction.commit().await?;
     }
 }
+
+const MAINTENANCE_BATCH_SIZE: u32 = 100;
+
+#[derive(Debug, Clone, Copy)]
+enum EntryMaintenanceJob {
+    // re-encrypts v1 envelopes (shared nonce, no associated data) as v2 under the author's user key
+    UpgradeLegacyEnvelopes,
+    // moves v2 content keys wrapped directly by a master key under the author's user key
+    WrapWithUserKeys,
+    // seals scales and pads content for authors who turned on metadata hiding
+    HideMetadata,
+    // and the reverse once they turn it back off
+    RevealMetadata,
+}
+
+impl EntryMaintenanceJob {
+    const ALL: [Self; 4] = [
+        Self::UpgradeLegacyEnvelopes,
+        Self::WrapWithUserKeys,
+        Self::HideMetadata,
+        Self::RevealMetadata,
+    ];
+
+    const fn pending_query(self) -> &'static str {
+        // only ever select rows with a master key we actually have loaded, anything else would fail forever
+        match self {
+            Self::UpgradeLegacyEnvelopes => {
+                // language=postgresql
+                "
+                    SELECT * FROM entries
+                    WHERE nonce IS NOT NULL AND key_version = ANY($1)
+                    ORDER BY id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                "
+            }
+            Self::WrapWithUserKeys => {
+                // language=postgresql
+                "
+                    SELECT * FROM entries
+                    WHERE nonce IS NULL AND key_version = ANY($1)
+                    ORDER BY id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                "
+            }
+            Self::HideMetadata => {
+                // language=postgresql
+                "
+                    SELECT entries.* FROM entries
+                    JOIN users ON users.id = entries.author
+                    WHERE users.hide_metadata
+                    AND entries.encrypted_emotion_scale IS NULL
+                    AND (entries.key_version IS NULL OR entries.key_version = ANY($1))
+                    ORDER BY entries.id
+                    LIMIT $2
+                    FOR UPDATE OF entries SKIP LOCKED
+                "
+            }
+            Self::RevealMetadata => {
+                // language=postgresql
+                "
+                    SELECT entries.* FROM entries
+                    JOIN users ON users.id = entries.author
+                    WHERE NOT users.hide_metadata
+                    AND entries.encrypted_emotion_scale IS NOT NULL
+                    AND (entries.key_version IS NULL OR entries.key_version = ANY($1))
+                    ORDER BY entries.id
+                    LIMIT $2
+                    FOR UPDATE OF entries SKIP LOCKED
+                "
+            }
+        }
+    }
+
+    // revisions keep the envelope they were copied with, so the jobs that move entries off of master keys
+    // have to move them too, otherwise they're lost with a retired key or outlive a deleted user key.
+    // metadata hiding leaves them in the form they were saved in
+    const fn revision_pending_query(self) -> Option<&'static str> {
+        match self {
+            Self::UpgradeLegacyEnvelopes => Some(
+                // language=postgresql
+                "
+                    SELECT * FROM entry_revisions
+                    WHERE nonce IS NOT NULL AND key_version = ANY($1)
+                    ORDER BY id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                ",
+            ),
+            Self::WrapWithUserKeys => Some(
+                // language=postgresql
+                "
+                    SELECT * FROM entry_revisions
+                    WHERE nonce IS NULL AND key_version = ANY($1)
+                    ORDER BY id
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                ",
+            ),
+            Self::HideMetadata | Self::RevealMetadata => None,
+        }
+    }
+
+    async fn apply(
+        self,
+        entry: &mut EncryptedEntry,
+        key_provider: &dyn KeyProvider,
+        user_key: &UserKey,
+    ) -> anyhow::Result<()> {
+        let content_key = if entry.client_encrypted {
+            None
+        } else {
+            Some(entry.unwrap_content_key(key_provider, user_key).await?)
+        };
+
+        match (self, &content_key) {
+            (Self::UpgradeLegacyEnvelopes, Some(content_key)) => {
+                entry.upgrade_envelope(content_key, user_key)
+            }
+            (Self::WrapWithUserKeys, Some(content_key)) => {
+                entry.wrap_with_user_key(content_key, user_key)
+            }
+            (Self::HideMetadata, _) => {
+                entry.set_metadata_hidden(content_key.as_ref(), user_key, true)
+            }
+            (Self::RevealMetadata, _) => {
+                entry.set_metadata_hidden(content_key.as_ref(), user_key, false)
+            }
+            (Self::UpgradeLegacyEnvelopes | Self::WrapWithUserKeys, None) => {
+                anyhow::bail!("client encrypted entries have no content key")
+            }
+        }
+    }
+}
+
+// upgrades old envelopes, moves entries under user keys, hides or reveals metadata, builds the search index,
+// seals leftover plaintext active entries, and moves user keys off of retired master keys, a batch at a time.
+// progress lives in the rows themselves, so a restart just picks up whatever is left
+pub async fn run_entry_key_maintenance(
+    pool: PgPool,
+    key_provider: Arc<dyn KeyProvider>,
+) -> anyhow::Result<()> {
+    let mut ticker = interval(Duration::from_mins(1));
+
+    loop {
+        ticker.tick().await;
+
+        if let Err(why) = run_entry_key_maintenance_pass(&pool, &*key_provider).await {
+            warn!("entry key maintenance failed {why:?}");
+        }
+    }
+}
+
+async fn run_entry_key_maintenance_pass(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+) -> anyhow::Result<()> {
+    let known_versions = key_provider.versions().await?;
+    for job in EntryMaintenanceJob::ALL {
+        let migrated = run_until_exhausted(|| {
+            run_entry_maintenance_batch(pool, key_provider, job, &known_versions)
+        })
+        .await?;
+
+        if migrated != 0 {
+            info!("{job:?} migrated {migrated} entries");
+        }
+
+        let Some(revision_query) = job.revision_pending_query() else {
+            continue;
+        };
+
+        let migrated = run_until_exhausted(|| {
+            run_revision_maintenance_batch(pool, key_provider, job, revision_query, &known_versions)
+        })
+        .await?;
+
+        if migrated != 0 {
+            info!("{job:?} migrated {migrated} entry revisions");
+        }
+    }
+
+    let indexed =
+        run_until_exhausted(|| index_entries_batch(pool, key_provider, &known_versions)).await?;
+    if indexed != 0 {
+        info!("indexed {indexed} entries for search");
+    }
+
+    let sealed =
+        run_until_exhausted(|| seal_plaintext_active_entries_batch(pool, key_provider)).await?;
+    if sealed != 0 {
+        info!("sealed {sealed} plaintext active entries");
+    }
+
+    let retired_versions = key_provider.retired_versions().await?;
+    if retired_versions.is_empty() {
+        return Ok(());
+    }
+
+    let rewrapped = run_until_exhausted(|| {
+        rewrap_retired_user_keys_batch(pool, key_provider, &retired_versions)
+    })
+    .await?;
+
+    if rewrapped != 0 {
+        info!(
+            "re-wrapped {rewrapped} user keys with the active {} master key",
+            key_provider.name()
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_until_exhausted<F, Fut>(mut run_batch: F) -> anyhow::Result<usize>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<(usize, usize)>>,
+{
+    let mut migrated = 0;
+    loop {
+        let (fetched, batch_migrated) = run_batch().await?;
+        migrated += batch_migrated;
+
+        // stop early if anything failed so the same broken rows aren't retried in a hot loop
+        if fetched < MAINTENANCE_BATCH_SIZE as usize || batch_migrated < fetched {
+            return Ok(migrated);
+        }
+    }
+}
+
+async fn run_entry_maintenance_batch(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+    job: EntryMaintenanceJob,
+    key_versions: &[i32],
+) -> anyhow::Result<(usize, usize)> {
+    let mut transaction = pool.begin().await?;
+
+    let entries = sqlx::query_as::<_, EncryptedEntry>(job.pending_query())
+        .bind(key_versions)
+        .bind(i64::from(MAINTENANCE_BATCH_SIZE))
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let fetched = entries.len();
+    if fetched == 0 {
+        return Ok((0, 0));
+    }
+
+    let authors = entries.iter().map(|entry| entry.author).collect::<Vec<_>>();
+    let user_keys =
+        UserKeyService::get_or_create_user_keys(&mut transaction, &authors, key_provider).await?;
+
+    let mut migrated_entries = Vec::with_capacity(fetched);
+    for mut entry in entries {
+        let result = match user_keys.get(&entry.author) {
+            Some(user_key) => job.apply(&mut entry, key_provider, user_key).await,
+            None => Err(anyhow::anyhow!("missing user key for entry author")),
+        };
+
+        match result {
+            Ok(()) => migrated_entries.push(entry),
+            Err(why) => warn!("{job:?} failed for entry {} {why:?}", entry.id),
+        }
+    }
+
+    let migrated = migrated_entries.len();
+    for entry in migrated_entries {
+        sqlx::query(
+            // language=postgresql
+            "
+                UPDATE entries
+                SET encrypted_content = $1, content_key = $2, nonce = $3, key_version = $4,
+                    emotion_scale = $5, encrypted_emotion_scale = $6
+                WHERE id = $7
+            ",
+        )
+        .bind(&entry.encrypted_content)
+        .bind(&entry.content_key)
+        .bind(&entry.nonce)
+        .bind(entry.key_version)
+        .bind(entry.emotion_scale)
+        .bind(&entry.encrypted_emotion_scale)
+        .bind(entry.id)
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+    Ok((fetched, migrated))
+}
+
+async fn run_revision_maintenance_batch(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+    job: EntryMaintenanceJob,
+    pending_query: &'static str,
+    key_versions: &[i32],
+) -> anyhow::Result<(usize, usize)> {
+    let mut transaction = pool.begin().await?;
+
+    let revisions = sqlx::query_as::<_, EntryRevision>(pending_query)
+        .bind(key_versions)
+        .bind(i64::from(MAINTENANCE_BATCH_SIZE))
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let fetched = revisions.len();
+    if fetched == 0 {
+        return Ok((0, 0));
+    }
+
+    let authors = revisions
+        .iter()
+        .map(|revision| revision.author)
+        .collect::<Vec<_>>();
+    let user_keys =
+        UserKeyService::get_or_create_user_keys(&mut transaction, &authors, key_provider).await?;
+
+    let mut migrated = 0;
+    for revision in revisions {
+        // opened as the entry it was copied from, the envelope is bound to that
+        let mut entry = revision.as_entry();
+        let result = match user_keys.get(&entry.author) {
+            Some(user_key) => job.apply(&mut entry, key_provider, user_key).await,
+            None => Err(anyhow::anyhow!("missing user key for revision author")),
+        };
+
+        if let Err(why) = result {
+            warn!("{job:?} failed for entry revision {} {why:?}", revision.id);
+            continue;
+        }
+
+        sqlx::query(
+            // language=postgresql
+            "
+                UPDATE entry_revisions
+                SET encrypted_content = $1, content_key = $2, nonce = $3, key_version = $4,
+                    emotion_scale = $5, encrypted_emotion_scale = $6
+                WHERE id = $7
+            ",
+        )
+        .bind(&entry.encrypted_content)
+        .bind(&entry.content_key)
+        .bind(&entry.nonce)
+        .bind(entry.key_version)
+        .bind(entry.emotion_scale)
+        .bind(&entry.encrypted_emotion_scale)
+        .bind(revision.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        migrated += 1;
+    }
+
+    transaction.commit().await?;
+    Ok((fetched, migrated))
+}
+
+// (re)builds search tokens for entries that were never indexed or were indexed under an older key version
+async fn index_entries_batch(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+    key_versions: &[i32],
+) -> anyhow::Result<(usize, usize)> {
+    let mut transaction = pool.begin().await?;
+
+    let entries = sqlx::query_as::<_, EncryptedEntry>(
+        // language=postgresql
+        "
+            SELECT entries.* FROM entries
+            JOIN user_keys ON user_keys.user_id = entries.author
+            WHERE NOT entries.client_encrypted
+            AND entries.search_key_version IS DISTINCT FROM user_keys.key_version
+            AND (entries.key_version IS NULL OR entries.key_version = ANY($1))
+            ORDER BY entries.id
+            LIMIT $2
+            FOR UPDATE OF entries SKIP LOCKED
+        ",
+    )
+    .bind(key_versions)
+    .bind(i64::from(MAINTENANCE_BATCH_SIZE))
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let fetched = entries.len();
+    if fetched == 0 {
+        return Ok((0, 0));
+    }
+
+    let authors = entries.iter().map(|entry| entry.author).collect::<Vec<_>>();
+    let user_keys =
+        UserKeyService::get_or_create_user_keys(&mut transaction, &authors, key_provider).await?;
+
+    let mut indexed = 0;
+    for entry in entries {
+        let result = async {
+            let user_key = user_keys
+                .get(&entry.author)
+                .context("missing user key for entry author")?;
+            let content_key = entry.unwrap_content_key(key_provider, user_key).await?;
+            let text = entry.decrypt(&content_key, user_key)?.text;
+
+            let index_key = SearchIndexKey::derive(user_key)?;
+            anyhow::Ok(index_key.entry_tokens(text.as_deref().unwrap_or_default()))
+        }
+        .await;
+
+        let search_tokens = match result {
+            Ok(search_tokens) => search_tokens,
+            Err(why) => {
+                warn!("failed to index entry {} {why:?}", entry.id);
+                continue;
+            }
+        };
+
+        EntryService::replace_search_tokens(&mut transaction, &entry, Some(&search_tokens)).await?;
+        indexed += 1;
+    }
+
+    transaction.commit().await?;
+    Ok((fetched, indexed))
+}
+
+async fn seal_plaintext_active_entries_batch(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+) -> anyhow::Result<(usize, usize)> {
+    let mut transaction = pool.begin().await?;
+
+    let entries = sqlx::query_as::<_, EncryptedActiveEntry>(
+        // language=postgresql
+        "
+            SELECT * FROM active_entries
+            WHERE text IS NOT NULL
+            ORDER BY id
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        ",
+    )
+    .bind(i64::from(MAINTENANCE_BATCH_SIZE))
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let fetched = entries.len();
+    if fetched == 0 {
+        return Ok((0, 0));
+    }
+
+    let authors = entries.iter().map(|entry| entry.author).collect::<Vec<_>>();
+    let user_keys =
+        UserKeyService::get_or_create_user_keys(&mut transaction, &authors, key_provider).await?;
+    let hiding_metadata =
+        UserService::get_users_hiding_metadata(&mut transaction, &authors).await?;
+
+    let mut sealed = 0;
+    for mut entry in entries {
+        let hide_metadata = hiding_metadata.contains(&entry.author);
+        let result = user_keys
+            .get(&entry.author)
+            .context("missing user key for entry author")
+            .and_then(|user_key| entry.seal_plaintext(user_key, hide_metadata));
+
+        if let Err(why) = result {
+            warn!("failed to seal active entry {} {why:?}", entry.id);
+            continue;
+        }
+
+        sqlx::query(
+            // language=postgresql
+            "
+                UPDATE active_entries
+                SET text = NULL, encrypted_content = $1, content_key = $2, emotion_scale = $3, encrypted_emotion_scale = $4
+                WHERE id = $5
+            ",
+        )
+        .bind(&entry.encrypted_content)
+        .bind(&entry.content_key)
+        .bind(entry.emotion_scale)
+        .bind(&entry.encrypted_emotion_scale)
+        .bind(entry.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        sealed += 1;
+    }
+
+    transaction.commit().await?;
+    Ok((fetched, sealed))
+}
+
+async fn rewrap_retired_user_keys_batch(
+    pool: &PgPool,
+    key_provider: &dyn KeyProvider,
+    retired_versions: &[i32],
+) -> anyhow::Result<(usize, usize)> {
+    let mut transaction = pool.begin().await?;
+
+    let user_keys = sqlx::query_as::<_, WrappedUserKey>(
+        // language=postgresql
+        "
+            SELECT * FROM user_keys
+            WHERE key_version = ANY($1)
+            ORDER BY user_id
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        ",
+    )
+    .bind(retired_versions)
+    .bind(i64::from(MAINTENANCE_BATCH_SIZE))
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let fetched = user_keys.len();
+    let mut rewrapped = 0;
+
+    for mut user_key in user_keys {
+        if let Err(why) = user_key.rewrap(key_provider).await {
+            warn!(
+                "failed to re-wrap user key for {} {why:?}",
+                user_key.user_id
+            );
+            continue;
+        }
+
+        sqlx::query(
+            // language=postgresql
+            "UPDATE user_keys SET wrapped_key = $1, key_version = $2 WHERE user_id = $3",
+        )
+        .bind(&user_key.wrapped_key)
+        .bind(user_key.key_version)
+        .bind(user_key.user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+        rewrapped += 1;
+    }
+
+    transaction.commit().await?;
+    Ok((fetched, rewrapped))
+}
@@ -0,0 +1,495 @@
+use crate::{
+    crypto::key_provider::KeyProvider,
+    impl_service,
+    schemas::{
+        active_entry::EncryptedActiveEntry,
+        entry::{DecryptedEntry, EncryptedEntry},
+        tracker::{
+            EncryptedTracker, EncryptedTrackerValue, Tracker, TrackerDayValue, TrackerValue,
+        },
+        user::User,
+        user_key::UserKey,
+    },
+    services::{
+        entry_service::decrypt_entries,
+        tracker_service::{decrypt_tracker_values, decrypt_trackers},
+    },
+};
+use axum::body::{Body, Bytes};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{collections::BTreeMap, iter, mem, sync::Arc};
+use tokio::sync::mpsc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::web::zip_stream::ZipStreamWriter;
+
+pub struct ExportService(PgPool);
+impl_service!(ExportService);
+
+const EXPORT_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+    Csv,
+    // one markdown file per entry, and the trackers as a csv
+    Zip,
+}
+
+impl ExportFormat {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Zip => "application/zip",
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Markdown => "md",
+            Self::Csv => "csv",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl ExportService {
+    // the body is produced by a background task a batch at a time, the timeout layer only covers
+    // the response head so long exports aren't cut off, and only one batch is ever held in memory
+    pub fn export_entries(
+        &self,
+        user: User,
+        user_key: UserKey,
+        key_provider: Arc<dyn KeyProvider>,
+        format: ExportFormat,
+    ) -> Body {
+        let (sender, mut receiver) = mpsc::channel::<anyhow::Result<Bytes>>(2);
+        let pool = self.0.clone();
+
+        tokio::spawn(async move {
+            let result =
+                write_export(&pool, &user, &user_key, &*key_provider, format, &sender).await;
+
+            if let Err(why) = result {
+                warn!("export failed for {} {why:?}", user.id);
+                let _ = sender.send(Err(why)).await;
+            }
+        });
+
+        Body::from_stream(stream::poll_fn(move |cx| receiver.poll_recv(cx)))
+    }
+}
+
+async fn write_export(
+    pool: &PgPool,
+    user: &User,
+    user_key: &UserKey,
+    key_provider: &dyn KeyProvider,
+    format: ExportFormat,
+    sender: &mpsc::Sender<anyhow::Result<Bytes>>,
+) -> anyhow::Result<()> {
+    let trackers = export_trackers(pool, user, user_key).await?;
+    let mut encoder = ExportEncoder::new(format, trackers);
+    let mut chunk = encoder.start()?;
+
+    let mut after = (NaiveDate::MIN, DateTime::<Utc>::MIN_UTC, Uuid::nil());
+    loop {
+        let entries = sqlx::query_as::<_, EncryptedEntry>(
+            // language=postgresql
+            "
+                SELECT * FROM entries
+                WHERE author = $1
+                AND (date, created_at, id) > ($2, $3, $4)
+                ORDER BY date, created_at, id
+                LIMIT $5
+            ",
+        )
+        .bind(user.id)
+        .bind(after.0)
+        .bind(after.1)
+        .bind(after.2)
+        .bind(EXPORT_BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+
+        let Some(last) = entries.last() else {
+            break;
+        };
+        after = (last.date, last.created_at, last.id);
+
+        for entry in decrypt_entries(entries, key_provider, user_key).await? {
+            chunk.extend(encoder.entry(&entry)?);
+        }
+
+        // the client went away, nothing left to do
+        if sender.send(Ok(Bytes::from(chunk))).await.is_err() {
+            return Ok(());
+        }
+        chunk = Vec::new();
+    }
+
+    // past entries were already rolled over, so this is just today's
+    let active_entries = sqlx::query_as::<_, EncryptedActiveEntry>(
+        // language=postgresql
+        "SELECT * FROM active_entries WHERE author = $1 ORDER BY date, created_at, id",
+    )
+    .bind(user.id)
+    .fetch_all(pool)
+    .await?;
+
+    for active_entry in active_entries {
+        let active_entry = active_entry.decrypt(user_key)?;
+        chunk.extend(encoder.entry(&DecryptedEntry {
+            id: active_entry.id,
+            author: active_entry.author,
+            date: active_entry.date,
+            emotion_scale: active_entry.emotion_scale,
+            text: active_entry.text,
+            ciphertext: active_entry.ciphertext,
+            prompt_id: active_entry.prompt_id,
+            created_at: active_entry.created_at,
+        })?);
+    }
+
+    chunk.extend(encoder.finish()?);
+    let _ = sender.send(Ok(Bytes::from(chunk))).await;
+
+    Ok(())
+}
+
+// unlike entries there's few enough trackers and values to hold them all while exporting
+struct ExportTrackers {
+    trackers: Vec<Tracker>,
+    days: BTreeMap<NaiveDate, Vec<TrackerDayValue>>,
+}
+
+#[derive(Serialize)]
+struct ExportTracker<'a> {
+    #[serde(flatten)]
+    tracker: &'a Tracker,
+    values: Vec<&'a TrackerDayValue>,
+}
+
+async fn export_trackers(
+    pool: &PgPool,
+    user: &User,
+    user_key: &UserKey,
+) -> anyhow::Result<ExportTrackers> {
+    let trackers = sqlx::query_as::<_, EncryptedTracker>(
+        // language=postgresql
+        "SELECT * FROM trackers WHERE author = $1 ORDER BY created_at, id",
+    )
+    .bind(user.id)
+    .fetch_all(pool)
+    .await?;
+
+    if trackers.is_empty() {
+        return Ok(ExportTrackers {
+            trackers: Vec::new(),
+            days: BTreeMap::new(),
+        });
+    }
+
+    let values = sqlx::query_as::<_, EncryptedTrackerValue>(
+        // language=postgresql
+        "SELECT * FROM tracker_values WHERE author = $1 ORDER BY date",
+    )
+    .bind(user.id)
+    .fetch_all(pool)
+    .await?;
+
+    let trackers = decrypt_trackers(trackers, user_key.clone()).await?;
+    let kinds = trackers
+        .iter()
+        .map(|tracker| (tracker.id, tracker.kind))
+        .collect();
+
+    let mut days = BTreeMap::<NaiveDate, Vec<TrackerDayValue>>::new();
+    for value in decrypt_tracker_values(values, kinds, user_key.clone()).await? {
+        days.entry(value.date).or_default().push(value);
+    }
+
+    Ok(ExportTrackers { trackers, days })
+}
+
+impl ExportTrackers {
+    // a column per tracker, in the order they were added
+    fn columns(&self, values: Option<&[TrackerDayValue]>) -> Vec<String> {
+        self.trackers
+            .iter()
+            .map(|tracker| {
+                values
+                    .and_then(|values| values.iter().find(|value| value.tracker_id == tracker.id))
+                    .map(|value| tracker_value_text(value.value))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn markdown(&self) -> String {
+        if self.trackers.is_empty() {
+            return String::new();
+        }
+
+        let trackers = self
+            .trackers
+            .iter()
+            .map(|tracker| {
+                let unit = tracker
+                    .unit
+                    .as_ref()
+                    .map(|unit| format!(" ({unit})"))
+                    .unwrap_or_default();
+                let values = self
+                    .values(tracker)
+                    .map(|value| format!("- {}: {}\n", value.date, tracker_value_text(value.value)))
+                    .collect::<Vec<_>>()
+                    .concat();
+
+                format!("\n## {}{unit}\n\n{values}", tracker.name)
+            })
+            .collect::<Vec<_>>()
+            .concat();
+
+        format!("# Trackers\n{trackers}")
+    }
+
+    // the same layout as the csv export, a row per day and a column per tracker
+    fn csv(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = csv_record(
+            iter::once("date").chain(self.trackers.iter().map(|tracker| tracker.name.as_str())),
+        )?;
+
+        for (date, values) in &self.days {
+            bytes.extend(csv_record(
+                iter::once(date.to_string()).chain(self.columns(Some(values))),
+            )?);
+        }
+
+        Ok(bytes)
+    }
+
+    fn json(&self) -> Vec<ExportTracker<'_>> {
+        self.trackers
+            .iter()
+            .map(|tracker| ExportTracker {
+                tracker,
+                values: self.values(tracker).collect(),
+            })
+            .collect()
+    }
+
+    fn values<'a>(&'a self, tracker: &'a Tracker) -> impl Iterator<Item = &'a TrackerDayValue> {
+        self.days
+            .values()
+            .flatten()
+            .filter(|value| value.tracker_id == tracker.id)
+    }
+}
+
+fn tracker_value_text(value: TrackerValue) -> String {
+    match value {
+        TrackerValue::Boolean(done) => done.to_string(),
+        TrackerValue::Number(number) => number.to_string(),
+    }
+}
+
+struct ExportEncoder {
+    format: ExportFormat,
+    entries: usize,
+    zip: ZipStreamWriter,
+    // entries come ordered by date, so this is enough to name every file of a day differently
+    day: Option<(NaiveDate, usize)>,
+    trackers: ExportTrackers,
+}
+
+impl ExportEncoder {
+    const fn new(format: ExportFormat, trackers: ExportTrackers) -> Self {
+        Self {
+            format,
+            entries: 0,
+            zip: ZipStreamWriter::new(),
+            day: None,
+            trackers,
+        }
+    }
+
+    fn start(&self) -> anyhow::Result<Vec<u8>> {
+        match self.format {
+            ExportFormat::Json => Ok(br#"{"entries":["#.to_vec()),
+            ExportFormat::Csv => csv_record(
+                [
+                    "id",
+                    "date",
+                    "created_at",
+                    "emotion_scale",
+                    "text",
+                    "ciphertext",
+                ]
+                .into_iter()
+                .chain(
+                    self.trackers
+                        .trackers
+                        .iter()
+                        .map(|tracker| tracker.name.as_str()),
+                ),
+            ),
+            ExportFormat::Markdown | ExportFormat::Zip => Ok(Vec::new()),
+        }
+    }
+
+    // tracker values are taken out as the entries pass their day. days before `date` without an entry
+    // get a row of their own, `date`'s values are handed back for its first entry. so every value is
+    // written once even with several entries a day
+    fn csv_tracker_rows(
+        &mut self,
+        date: Option<NaiveDate>,
+    ) -> anyhow::Result<(Vec<u8>, Option<Vec<TrackerDayValue>>)> {
+        let days = match date {
+            Some(date) => {
+                let later = self.trackers.days.split_off(&date);
+                mem::replace(&mut self.trackers.days, later)
+            }
+            None => mem::take(&mut self.trackers.days),
+        };
+        let values = date.and_then(|date| self.trackers.days.remove(&date));
+
+        let mut bytes = Vec::new();
+        for (date, values) in days {
+            bytes.extend(csv_record(
+                [
+                    String::new(),
+                    date.to_string(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                ]
+                .into_iter()
+                .chain(self.trackers.columns(Some(&values))),
+            )?);
+        }
+
+        Ok((bytes, values))
+    }
+
+    fn entry(&mut self, entry: &DecryptedEntry) -> anyhow::Result<Vec<u8>> {
+        self.entries += 1;
+
+        match self.format {
+            ExportFormat::Json => {
+                let mut bytes = if self.entries == 1 {
+                    Vec::new()
+                } else {
+                    b",".to_vec()
+                };
+                serde_json::to_writer(&mut bytes, entry)?;
+                Ok(bytes)
+            }
+            ExportFormat::Markdown => Ok(entry_markdown(entry, true).into_bytes()),
+            ExportFormat::Csv => {
+                let (mut bytes, values) = self.csv_tracker_rows(Some(entry.date))?;
+                bytes.extend(csv_record(
+                    [
+                        entry.id.to_string(),
+                        entry.date.to_string(),
+                        entry.created_at.to_rfc3339(),
+                        entry.emotion_scale.to_string(),
+                        entry.text.clone().unwrap_or_default(),
+                        entry
+                            .ciphertext
+                            .as_ref()
+                            .map(|ciphertext| STANDARD.encode(ciphertext))
+                            .unwrap_or_default(),
+                    ]
+                    .into_iter()
+                    .chain(self.trackers.columns(values.as_deref())),
+                )?);
+                Ok(bytes)
+            }
+            ExportFormat::Zip => {
+                let index = match self.day {
+                    Some((date, index)) if date == entry.date => index + 1,
+                    _ => 1,
+                };
+                self.day = Some((entry.date, index));
+
+                // a day's first entry keeps the plain date as its name
+                let name = if index == 1 {
+                    format!("{}.md", entry.date)
+                } else {
+                    format!("{}-{index}.md", entry.date)
+                };
+
+                self.zip.add_file(
+                    &name,
+                    entry_markdown(entry, false).as_bytes(),
+                    entry.created_at.naive_utc(),
+                )
+            }
+        }
+    }
+
+    fn finish(mut self) -> anyhow::Result<Vec<u8>> {
+        match self.format {
+            ExportFormat::Json => {
+                let mut bytes = br#"],"trackers":"#.to_vec();
+                serde_json::to_writer(&mut bytes, &self.trackers.json())?;
+                bytes.push(b'}');
+                Ok(bytes)
+            }
+            ExportFormat::Markdown => Ok(self.trackers.markdown().into_bytes()),
+            ExportFormat::Csv => self.csv_tracker_rows(None).map(|(bytes, _)| bytes),
+            ExportFormat::Zip => {
+                let mut bytes = Vec::new();
+                if !self.trackers.trackers.is_empty() {
+                    bytes.extend(self.zip.add_file(
+                        "trackers.csv",
+                        &self.trackers.csv()?,
+                        Utc::now().naive_utc(),
+                    )?);
+                }
+                bytes.extend(self.zip.finish()?);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+fn csv_record<I, T>(record: I) -> anyhow::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record)?;
+    writer.into_inner().map_err(|why| anyhow::anyhow!("{why}"))
+}
+
+// text is kept as the sanitized html it was written as, markdown renders it as is
+fn entry_markdown(entry: &DecryptedEntry, separator: bool) -> String {
+    let body = match (&entry.text, &entry.ciphertext) {
+        (_, Some(ciphertext)) => format!(
+            "_end to end encrypted, decrypt in the app_\n\n```\n{}\n```",
+            STANDARD.encode(ciphertext)
+        ),
+        (Some(text), None) => text.clone(),
+        (None, None) => String::new(),
+    };
+
+    let separator = if separator { "\n---\n\n" } else { "" };
+    format!(
+        "# {}\n\n_emotion scale: {}_\n\n{body}\n{separator}",
+        entry.date, entry.emotion_scale
+    )
+}
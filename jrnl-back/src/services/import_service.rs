@@ -0,0 +1,257 @@
+use crate::{
+    error::{JrnlError, JrnlResult},
+    impl_service,
+    import::{excerpt, ImportConflict, ImportEntry, ImportResult, ImportStatus},
+    schemas::{entry::EncryptedEntry, user::User, user_key::UserKey},
+    services::{entry_revision_service::EntryRevisionService, entry_service::EntryService},
+};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use sqlx::{Error, PgConnection, PgPool};
+use std::{collections::HashMap, mem};
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+pub struct ImportService(PgPool);
+impl_service!(ImportService);
+
+// every chunk is encrypted and written in its own transaction. chunks written before a failure stay imported,
+// running the same import again with `skip` picks up where it stopped
+const IMPORT_CHUNK_SIZE: usize = 200;
+
+struct PlannedEntry {
+    entry: ImportEntry,
+    id: Uuid,
+    overwrite: bool,
+}
+
+impl ImportService {
+    // entries have to be in the past and ordered by date, today's entries are only ever written by the app
+    pub async fn import_entries(
+        &self,
+        user: &User,
+        user_key: &UserKey,
+        mut entries: Vec<ImportEntry>,
+        conflict: ImportConflict,
+        dry_run: bool,
+    ) -> JrnlResult<Vec<ImportResult>> {
+        let mut results = Vec::with_capacity(entries.len());
+        // file order within a day is kept as the order the entries were written in
+        let imported_at = Utc::now();
+
+        while !entries.is_empty() {
+            // a day is never split over two chunks, otherwise its first chunk would be a conflict for the next
+            let mut end = IMPORT_CHUNK_SIZE.min(entries.len());
+            while entries
+                .get(end)
+                .is_some_and(|entry| entry.date == entries[end - 1].date)
+            {
+                end += 1;
+            }
+
+            let rest = entries.split_off(end);
+            let chunk = mem::replace(&mut entries, rest);
+
+            results.extend(
+                self.import_chunk(user, user_key, chunk, imported_at, conflict, dry_run)
+                    .await?,
+            );
+        }
+
+        Ok(results)
+    }
+
+    async fn import_chunk(
+        &self,
+        user: &User,
+        user_key: &UserKey,
+        entries: Vec<ImportEntry>,
+        imported_at: DateTime<Utc>,
+        conflict: ImportConflict,
+        dry_run: bool,
+    ) -> JrnlResult<Vec<ImportResult>> {
+        let dates = entries.iter().map(|entry| entry.date).collect::<Vec<_>>();
+        let mut existing = HashMap::<NaiveDate, Vec<Uuid>>::new();
+        for (date, id) in sqlx::query_as::<_, (NaiveDate, Uuid)>(
+            // language=postgresql
+            "
+                SELECT date, id FROM entries
+                WHERE author = $1 AND date = ANY($2)
+                ORDER BY date, created_at, id
+            ",
+        )
+        .bind(user.id)
+        .bind(&dates)
+        .fetch_all(&self.0)
+        .await?
+        {
+            existing.entry(date).or_default().push(id);
+        }
+
+        let mut results = Vec::with_capacity(entries.len());
+        let mut planned = Vec::with_capacity(entries.len());
+
+        // when overwriting, a day's records replace its existing entries in order and any left over are added.
+        // existing entries past the last record are left as they are
+        let mut overwritten = HashMap::<NaiveDate, usize>::new();
+        for entry in entries {
+            let existing_ids = existing.get(&entry.date);
+            match (existing_ids, conflict) {
+                (Some(_), ImportConflict::Skip) => results.push(ImportResult::for_entry(
+                    &entry,
+                    ImportStatus::Skipped,
+                    Some("entries already exist for this day"),
+                )),
+                (Some(ids), ImportConflict::Overwrite) => {
+                    let index = overwritten.entry(entry.date).or_default();
+                    let id = ids.get(*index).copied();
+                    *index += 1;
+
+                    planned.push(PlannedEntry {
+                        entry,
+                        id: id.unwrap_or_else(Uuid::new_v4),
+                        overwrite: id.is_some(),
+                    });
+                }
+                (None, _) => planned.push(PlannedEntry {
+                    entry,
+                    id: Uuid::new_v4(),
+                    overwrite: false,
+                }),
+            }
+        }
+
+        if dry_run {
+            results.extend(planned.iter().map(|planned| ImportResult {
+                entry_id: planned.overwrite.then_some(planned.id),
+                excerpt: Some(excerpt(&planned.entry)),
+                ..ImportResult::for_entry(&planned.entry, planned.status(), None)
+            }));
+            return Ok(results);
+        }
+
+        let author = user.id;
+        let hide_metadata = user.hide_metadata;
+        let user_key = user_key.clone();
+        let sealed = spawn_blocking(move || {
+            planned
+                .into_iter()
+                .map(|planned| {
+                    let position = i64::try_from(planned.entry.position).unwrap_or(i64::MAX);
+                    let entry = EncryptedEntry::seal(
+                        planned.id,
+                        author,
+                        planned.entry.date,
+                        planned.entry.emotion_scale,
+                        planned.entry.text.as_deref(),
+                        &user_key,
+                        hide_metadata,
+                    )
+                    .map(|entry| EncryptedEntry {
+                        created_at: imported_at + TimeDelta::microseconds(position),
+                        ..entry
+                    });
+                    (planned, entry)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|why| JrnlError::EntryEncryptionFailed(why.into()))?;
+
+        let mut inserts = Vec::new();
+        let mut overwrites = Vec::new();
+        for (planned, entry) in sealed {
+            match entry {
+                Ok(entry) if planned.overwrite => overwrites.push((planned, entry)),
+                Ok(entry) => inserts.push((planned, entry)),
+                Err(_) => results.push(ImportResult::for_entry(
+                    &planned.entry,
+                    ImportStatus::Failed,
+                    Some("failed to encrypt entry"),
+                )),
+            }
+        }
+
+        let mut transaction = self.0.begin().await?;
+        results.extend(Self::insert_planned(&mut transaction, author, &inserts).await?);
+        results.extend(Self::overwrite_planned(&mut transaction, author, &overwrites).await?);
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn insert_planned(
+        connection: &mut PgConnection,
+        author: Uuid,
+        inserts: &[(PlannedEntry, EncryptedEntry)],
+    ) -> Result<Vec<ImportResult>, Error> {
+        let entries = inserts.iter().map(|(_, entry)| entry).collect::<Vec<_>>();
+        let inserted = EntryService::insert_new_entries(connection, author, &entries).await?;
+
+        Ok(inserts
+            .iter()
+            .map(|(planned, entry)| {
+                // ids are new, so this only misses if one was somehow taken already
+                if inserted.contains(&entry.id) {
+                    ImportResult {
+                        entry_id: Some(entry.id),
+                        ..ImportResult::for_entry(&planned.entry, ImportStatus::Imported, None)
+                    }
+                } else {
+                    ImportResult::for_entry(
+                        &planned.entry,
+                        ImportStatus::Failed,
+                        Some("an entry with the same id already exists"),
+                    )
+                }
+            })
+            .collect())
+    }
+
+    async fn overwrite_planned(
+        connection: &mut PgConnection,
+        author: Uuid,
+        overwrites: &[(PlannedEntry, EncryptedEntry)],
+    ) -> Result<Vec<ImportResult>, Error> {
+        let mut results = Vec::with_capacity(overwrites.len());
+
+        for (planned, entry) in overwrites {
+            EntryRevisionService::snapshot_entry(&mut *connection, author, entry.id).await?;
+            let updated = EntryService::update_encrypted_entry_query(entry)
+                .execute(&mut *connection)
+                .await?
+                .rows_affected();
+
+            if updated == 0 {
+                results.push(ImportResult::for_entry(
+                    &planned.entry,
+                    ImportStatus::Failed,
+                    Some("the existing entry was deleted during the import"),
+                ));
+                continue;
+            }
+
+            // the maintenance task indexes it again
+            EntryService::replace_search_tokens(&mut *connection, entry, None).await?;
+            results.push(ImportResult {
+                entry_id: Some(entry.id),
+                ..ImportResult::for_entry(&planned.entry, ImportStatus::Overwritten, None)
+            });
+        }
+
+        if !overwrites.is_empty() {
+            EntryRevisionService::prune(connection, author, None).await?;
+        }
+
+        Ok(results)
+    }
+}
+
+impl PlannedEntry {
+    const fn status(&self) -> ImportStatus {
+        if self.overwrite {
+            ImportStatus::Overwritten
+        } else {
+            ImportStatus::Imported
+        }
+    }
+}
@@ -1,8 +1,18 @@
 #![allow(clippy::crate_in_macro_def)]
 
+pub mod attachment_service;
 pub mod auth_service;
+pub mod checkin_service;
+pub mod entry_revision_service;
 pub mod entry_service;
+pub mod export_service;
 pub mod group_service;
+pub mod import_service;
+pub mod prompt_service;
+pub mod stats_service;
+pub mod tag_service;
+pub mod tracker_service;
+pub mod user_key_service;
 pub mod user_service;
 
 #[macro_export]
@@ -0,0 +1,118 @@
+use crate::{
+    impl_service,
+    schemas::{prompt::EncryptedPrompt, user::User},
+};
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+pub struct PromptService(PgPool);
+impl_service!(PromptService);
+
+impl PromptService {
+    // oldest first, the daily rotation relies on the order staying put
+    pub async fn get_prompts(&self, user: &User) -> Result<Vec<EncryptedPrompt>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM prompts WHERE author = $1 ORDER BY created_at, id",
+        )
+        .bind(user.id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_prompt(
+        &self,
+        user: &User,
+        id: &Uuid,
+    ) -> Result<Option<EncryptedPrompt>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM prompts WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    pub async fn get_prompts_count(&self, user: &User) -> Result<i64, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT COUNT(*) FROM prompts WHERE author = $1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // the text is sealed against the id, so the id is picked before the row is written
+    pub async fn create_prompt(
+        &self,
+        user: &User,
+        id: Uuid,
+        encrypted_text: &[u8],
+    ) -> Result<EncryptedPrompt, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "INSERT INTO prompts (id, author, encrypted_text) VALUES ($1, $2, $3) RETURNING *",
+        )
+        .bind(id)
+        .bind(user.id)
+        .bind(encrypted_text)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    pub async fn update_prompt(
+        &self,
+        user: &User,
+        id: &Uuid,
+        encrypted_text: &[u8],
+    ) -> Result<Option<EncryptedPrompt>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "UPDATE prompts SET encrypted_text = $1 WHERE id = $2 AND author = $3 RETURNING *",
+        )
+        .bind(encrypted_text)
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // entries that answered it keep everything but the prompt
+    pub async fn delete_prompt(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        let deleted = sqlx::query(
+            // language=postgresql
+            "DELETE FROM prompts WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected()
+            != 0;
+
+        if !deleted {
+            return Ok(false);
+        }
+
+        for query in [
+            // language=postgresql
+            "UPDATE entries SET prompt_id = NULL WHERE author = $1 AND prompt_id = $2",
+            // language=postgresql
+            "UPDATE active_entries SET prompt_id = NULL WHERE author = $1 AND prompt_id = $2",
+        ] {
+            sqlx::query(query)
+                .bind(user.id)
+                .bind(id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
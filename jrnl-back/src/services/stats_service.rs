@@ -0,0 +1,850 @@
+use crate::{
+    crypto::envelope::{EntryBinding, EnvelopePurpose},
+    error::{JrnlError, JrnlResult},
+    impl_service,
+    schemas::{user::User, user_key::UserKey},
+    services::entry_service::reveal_emotion_scale,
+};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::Serialize;
+use sqlx::{Error, FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+pub struct StatsService(PgPool);
+impl_service!(StatsService);
+
+// both rolling windows end on the day they're reported for, so the 7 day average covers that day and the six before it
+const SHORT_ROLLING_WINDOW: u64 = 7;
+const LONG_ROLLING_WINDOW: u64 = 30;
+
+// inclusive, no start covers everything up to `to`
+#[derive(Debug, Clone, Copy)]
+pub struct StatsRange {
+    pub from: Option<NaiveDate>,
+    pub to: NaiveDate,
+    // the long rolling window reaches back before the range, so its first days still average over a full window
+    pub window_start: Option<NaiveDate>,
+}
+
+impl StatsRange {
+    // a start so early that the window can't reach back before it is as invalid as one after the end
+    pub fn new(from: Option<NaiveDate>, to: NaiveDate) -> JrnlResult<Self> {
+        if from.is_some_and(|from| from > to) {
+            return Err(JrnlError::InvalidDateRange);
+        }
+
+        let window_start = from
+            .map(|from| {
+                from.checked_sub_days(Days::new(LONG_ROLLING_WINDOW - 1))
+                    .ok_or(JrnlError::InvalidDateRange)
+            })
+            .transpose()?;
+
+        Ok(Self {
+            from,
+            to,
+            window_start,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoodStats {
+    pub from: Option<NaiveDate>,
+    pub to: NaiveDate,
+    pub count: i64,
+    pub average: Option<f64>,
+    pub median: Option<f64>,
+    // population variance, so a single entry has a variance of 0
+    pub variance: Option<f64>,
+    // monday first, every weekday is there even without entries
+    pub weekdays: Vec<WeekdayStats>,
+    pub rolling_averages: Vec<RollingAverage>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WeekdayStats {
+    // iso weekday, 1 is monday
+    pub weekday: i32,
+    pub count: i64,
+    pub average: Option<f64>,
+}
+
+// one per day with an entry, `emotion_scale` is that day's summary
+#[derive(Debug, Serialize, FromRow)]
+pub struct RollingAverage {
+    pub date: NaiveDate,
+    pub emotion_scale: f64,
+    pub average_7: f64,
+    pub average_30: f64,
+}
+
+// streaks count days with an entry, frozen and skipped days keep a streak going without adding to it
+#[derive(Debug, Default, Serialize)]
+pub struct Streaks {
+    // still alive until today is over, even if today hasn't been written yet
+    pub current: i64,
+    pub current_start: Option<NaiveDate>,
+    pub longest: i64,
+    pub longest_start: Option<NaiveDate>,
+    pub longest_end: Option<NaiveDate>,
+    pub written_today: bool,
+    // missed days within the current streak that a freeze covered
+    pub frozen_days: Vec<NaiveDate>,
+    // for the current month
+    pub freezes_left: i32,
+}
+
+#[derive(Clone)]
+struct StreakRun {
+    start: NaiveDate,
+    end: NaiveDate,
+    days: i64,
+    frozen_days: Vec<NaiveDate>,
+}
+
+// freezes are handed out per calendar month and spent oldest gap first
+struct StreakFreezes<'a> {
+    per_month: i32,
+    used: HashMap<(i32, u32), i32>,
+    skipped_days: &'a HashSet<NaiveDate>,
+}
+
+impl StreakFreezes<'_> {
+    // the missed days strictly between two dates if they can all be covered, a gap is covered whole or not at all
+    fn bridge(&mut self, from: NaiveDate, to: NaiveDate) -> Option<Vec<NaiveDate>> {
+        let missed = from
+            .iter_days()
+            .skip(1)
+            .take_while(|day| *day < to)
+            .filter(|day| !self.skipped_days.contains(day))
+            .collect::<Vec<_>>();
+
+        let mut needed = HashMap::<(i32, u32), i32>::new();
+        for day in &missed {
+            *needed.entry((day.year(), day.month())).or_default() += 1;
+        }
+
+        let affordable = needed.iter().all(|(month, needed)| {
+            self.used.get(month).copied().unwrap_or_default() + needed <= self.per_month
+        });
+
+        if !affordable {
+            return None;
+        }
+
+        for (month, needed) in needed {
+            *self.used.entry(month).or_default() += needed;
+        }
+
+        Some(missed)
+    }
+}
+
+impl Streaks {
+    // `dates` are the days with an entry, sorted and without duplicates
+    pub fn compute(
+        dates: &[NaiveDate],
+        skipped_days: &HashSet<NaiveDate>,
+        freezes_per_month: i32,
+        today: NaiveDate,
+    ) -> Self {
+        let mut freezes = StreakFreezes {
+            per_month: freezes_per_month,
+            used: HashMap::new(),
+            skipped_days,
+        };
+
+        let mut longest: Option<StreakRun> = None;
+        let mut run: Option<StreakRun> = None;
+
+        for &date in dates {
+            let bridged = run.as_ref().and_then(|run| freezes.bridge(run.end, date));
+
+            run = match (run, bridged) {
+                (Some(mut run), Some(frozen_days)) => {
+                    run.end = date;
+                    run.days += 1;
+                    run.frozen_days.extend(frozen_days);
+                    Some(run)
+                }
+                _ => Some(StreakRun {
+                    start: date,
+                    end: date,
+                    days: 1,
+                    frozen_days: Vec::new(),
+                }),
+            };
+
+            if longest
+                .as_ref()
+                .is_none_or(|longest| run.as_ref().is_some_and(|run| run.days > longest.days))
+            {
+                longest.clone_from(&run);
+            }
+        }
+
+        // a timezone change can leave the newest entry dated after today
+        let current = run.and_then(|mut run| {
+            let frozen_days = freezes.bridge(run.end, today.max(run.end))?;
+            run.frozen_days.extend(frozen_days);
+            Some(run)
+        });
+
+        let used_this_month = freezes
+            .used
+            .get(&(today.year(), today.month()))
+            .copied()
+            .unwrap_or_default();
+
+        Self {
+            current: current.as_ref().map_or(0, |run| run.days),
+            current_start: current.as_ref().map(|run| run.start),
+            longest: longest.as_ref().map_or(0, |run| run.days),
+            longest_start: longest.as_ref().map(|run| run.start),
+            longest_end: longest.as_ref().map(|run| run.end),
+            written_today: dates.binary_search(&today).is_ok(),
+            frozen_days: current.map(|run| run.frozen_days).unwrap_or_default(),
+            freezes_left: (freezes_per_month - used_this_month).max(0),
+        }
+    }
+}
+
+// one slot per day from `start` to `end`, days without an entry are left empty
+#[derive(Debug, Serialize)]
+pub struct Calendar {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: Vec<Option<CalendarDay>>,
+}
+
+// `entry_ids` are in the order the entries were written
+#[derive(Debug, Serialize)]
+pub struct CalendarDay {
+    pub emotion_scale: f32,
+    pub entry_ids: Vec<Uuid>,
+}
+
+impl Calendar {
+    // `entries` are every entry's date, id and scale, in the order they should be listed within a day
+    pub fn new(start: NaiveDate, end: NaiveDate, entries: Vec<(NaiveDate, Uuid, f32)>) -> Self {
+        let mut days = start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .map(|_| Vec::new())
+            .collect::<Vec<_>>();
+
+        for (date, id, emotion_scale) in entries {
+            let slot = usize::try_from((date - start).num_days())
+                .ok()
+                .and_then(|index| days.get_mut(index));
+
+            if let Some(slot) = slot {
+                slot.push((id, emotion_scale));
+            }
+        }
+
+        let days = days
+            .into_iter()
+            .map(|entries| {
+                let scales = entries.iter().map(|(_, scale)| *scale).collect::<Vec<_>>();
+                day_summary(&scales).map(|emotion_scale| CalendarDay {
+                    emotion_scale,
+                    entry_ids: entries.into_iter().map(|(id, _)| id).collect(),
+                })
+            })
+            .collect();
+
+        Self { start, end, days }
+    }
+}
+
+#[derive(FromRow)]
+struct SummaryRow {
+    count: i64,
+    average: Option<f64>,
+    median: Option<f64>,
+    variance: Option<f64>,
+}
+
+#[derive(FromRow)]
+pub struct ScaleRow {
+    pub id: Uuid,
+    pub author: Uuid,
+    pub date: NaiveDate,
+    pub emotion_scale: Option<f32>,
+    pub encrypted_emotion_scale: Option<Vec<u8>>,
+}
+
+impl ScaleRow {
+    pub fn reveal(&self, user_key: Option<&UserKey>) -> anyhow::Result<f32> {
+        reveal_emotion_scale(
+            self.emotion_scale,
+            self.encrypted_emotion_scale.as_deref(),
+            &EntryBinding {
+                purpose: EnvelopePurpose::EmotionScale,
+                id: &self.id,
+                author: &self.author,
+                date: &self.date,
+            },
+            user_key,
+        )
+    }
+
+    pub const fn metadata_hidden(&self) -> bool {
+        self.encrypted_emotion_scale.is_some()
+    }
+}
+
+impl StatsService {
+    // sealed scales can't be aggregated in sql, if there's any in the range everything is computed after decrypting
+    pub async fn has_hidden_scales(&self, user: &User, range: StatsRange) -> Result<bool, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "
+                SELECT EXISTS (
+                    SELECT 1 FROM entries
+                    WHERE author = $1 AND emotion_scale IS NULL
+                    AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                ) OR EXISTS (
+                    SELECT 1 FROM active_entries
+                    WHERE author = $1 AND NOT ephemeral AND emotion_scale IS NULL
+                    AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                )
+            ",
+        )
+        .bind(user.id)
+        .bind(range.window_start)
+        .bind(range.to)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // today's entries count as soon as they're written, ephemeral ones never do. every number is over days,
+    // a day with several entries counts once with its summary scale, see `day_summaries`
+    pub async fn get_mood_stats(&self, user: &User, range: StatsRange) -> Result<MoodStats, Error> {
+        let summary = sqlx::query_as::<_, SummaryRow>(
+            // language=postgresql
+            "
+                WITH scales AS (
+                    SELECT date, emotion_scale FROM entries
+                    WHERE author = $1 AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                    UNION ALL
+                    SELECT date, emotion_scale FROM active_entries
+                    WHERE author = $1 AND NOT ephemeral AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                ), days AS (
+                    SELECT date, AVG(emotion_scale)::float4 AS emotion_scale FROM scales GROUP BY date
+                )
+                SELECT COUNT(emotion_scale) AS count,
+                       AVG(emotion_scale)::float8 AS average,
+                       percentile_cont(0.5) WITHIN GROUP (ORDER BY emotion_scale) AS median,
+                       var_pop(emotion_scale)::float8 AS variance
+                FROM days
+            ",
+        )
+        .bind(user.id)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_one(&self.0)
+        .await?;
+
+        let weekdays = sqlx::query_as::<_, WeekdayStats>(
+            // language=postgresql
+            "
+                WITH scales AS (
+                    SELECT date, emotion_scale FROM entries
+                    WHERE author = $1 AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                    UNION ALL
+                    SELECT date, emotion_scale FROM active_entries
+                    WHERE author = $1 AND NOT ephemeral AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                ), days AS (
+                    SELECT date, AVG(emotion_scale)::float4 AS emotion_scale FROM scales GROUP BY date
+                )
+                SELECT EXTRACT(ISODOW FROM date)::int4 AS weekday,
+                       COUNT(emotion_scale) AS count,
+                       AVG(emotion_scale)::float8 AS average
+                FROM days
+                GROUP BY 1
+            ",
+        )
+        .bind(user.id)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all(&self.0)
+        .await?;
+
+        let rolling_averages = sqlx::query_as::<_, RollingAverage>(
+            // language=postgresql
+            "
+                WITH scales AS (
+                    SELECT date, emotion_scale FROM entries
+                    WHERE author = $1 AND date >= COALESCE($2, '-infinity'::date) AND date <= $4
+                    UNION ALL
+                    SELECT date, emotion_scale FROM active_entries
+                    WHERE author = $1 AND NOT ephemeral AND date >= COALESCE($2, '-infinity'::date) AND date <= $4
+                ), days AS (
+                    SELECT date, AVG(emotion_scale)::float4 AS emotion_scale FROM scales GROUP BY date
+                ), rolling AS (
+                    SELECT date,
+                           emotion_scale::float8 AS emotion_scale,
+                           AVG(emotion_scale) OVER (
+                               ORDER BY date RANGE BETWEEN INTERVAL '6 days' PRECEDING AND CURRENT ROW
+                           )::float8 AS average_7,
+                           AVG(emotion_scale) OVER (
+                               ORDER BY date RANGE BETWEEN INTERVAL '29 days' PRECEDING AND CURRENT ROW
+                           )::float8 AS average_30
+                    FROM days
+                )
+                SELECT * FROM rolling
+                WHERE date >= COALESCE($3, '-infinity'::date)
+                ORDER BY date
+            ",
+        )
+        .bind(user.id)
+        .bind(range.window_start)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all(&self.0)
+        .await?;
+
+        Ok(MoodStats {
+            from: range.from,
+            to: range.to,
+            count: summary.count,
+            average: summary.average,
+            median: summary.median,
+            variance: summary.variance,
+            weekdays: every_weekday(weekdays),
+            rolling_averages,
+        })
+    }
+
+    // inclusive and one row per entry, hidden scales are left for the caller to reveal
+    pub async fn get_scale_rows(
+        &self,
+        user: &User,
+        from: Option<NaiveDate>,
+        to: NaiveDate,
+    ) -> Result<Vec<ScaleRow>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                SELECT id, author, date, emotion_scale, encrypted_emotion_scale, created_at FROM entries
+                WHERE author = $1 AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                UNION ALL
+                SELECT id, author, date, emotion_scale, encrypted_emotion_scale, created_at FROM active_entries
+                WHERE author = $1 AND NOT ephemeral AND date >= COALESCE($2, '-infinity'::date) AND date <= $3
+                ORDER BY date, created_at, id
+            ",
+        )
+        .bind(user.id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    // a random sample of entries that may be at or above the scale, hidden scales are left for the caller to reveal
+    pub async fn get_random_scale_rows(
+        &self,
+        user: &User,
+        min_scale: f32,
+        limit: i64,
+    ) -> Result<Vec<ScaleRow>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                SELECT id, author, date, emotion_scale, encrypted_emotion_scale FROM entries
+                WHERE author = $1 AND (encrypted_emotion_scale IS NOT NULL OR emotion_scale >= $2)
+                ORDER BY random()
+                LIMIT $3
+            ",
+        )
+        .bind(user.id)
+        .bind(min_scale)
+        .bind(limit)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_entry_dates(&self, user: &User) -> Result<Vec<NaiveDate>, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "
+                SELECT date FROM entries WHERE author = $1
+                UNION
+                SELECT date FROM active_entries WHERE author = $1 AND NOT ephemeral
+                ORDER BY date
+            ",
+        )
+        .bind(user.id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_streak_skipped_days(&self, user: &User) -> Result<HashSet<NaiveDate>, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT date FROM streak_skipped_days WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .fetch_all(&self.0)
+        .await
+        .map(|days| days.into_iter().collect())
+    }
+}
+
+impl MoodStats {
+    // the same numbers `get_mood_stats` gets out of postgres, for journals whose scales are sealed.
+    // `scales` holds one per entry
+    pub fn from_scales(range: StatsRange, scales: Vec<(NaiveDate, f32)>) -> Self {
+        let scales = day_summaries(scales);
+
+        let in_range = |date: &NaiveDate| range.from.is_none_or(|from| *date >= from);
+        let mut values = scales
+            .iter()
+            .filter(|(date, _)| in_range(date))
+            .map(|(_, scale)| f64::from(*scale))
+            .collect::<Vec<_>>();
+        values.sort_by(f64::total_cmp);
+
+        let average = mean(&values);
+        let median = match values.len() {
+            0 => None,
+            len if len % 2 == 0 => Some(f64::midpoint(values[len / 2 - 1], values[len / 2])),
+            len => Some(values[len / 2]),
+        };
+        let variance = average.and_then(|average| {
+            mean(
+                &values
+                    .iter()
+                    .map(|value| (value - average).powi(2))
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        let weekdays = (1..=7)
+            .map(|weekday: i32| {
+                let values = scales
+                    .iter()
+                    .filter(|(date, _)| {
+                        in_range(date)
+                            && i64::from(date.weekday().number_from_monday()) == i64::from(weekday)
+                    })
+                    .map(|(_, scale)| f64::from(*scale))
+                    .collect::<Vec<_>>();
+
+                WeekdayStats {
+                    weekday,
+                    count: i64::try_from(values.len()).unwrap_or(i64::MAX),
+                    average: mean(&values),
+                }
+            })
+            .collect();
+
+        let rolling_averages = scales
+            .iter()
+            .filter(|(date, _)| in_range(date))
+            .map(|(date, scale)| RollingAverage {
+                date: *date,
+                emotion_scale: f64::from(*scale),
+                average_7: rolling_mean(&scales, *date, SHORT_ROLLING_WINDOW),
+                average_30: rolling_mean(&scales, *date, LONG_ROLLING_WINDOW),
+            })
+            .collect();
+
+        Self {
+            from: range.from,
+            to: range.to,
+            count: i64::try_from(values.len()).unwrap_or(i64::MAX),
+            average,
+            median,
+            variance,
+            weekdays,
+            rolling_averages,
+        }
+    }
+}
+
+// a day's summary scale is the mean of its entries' scales. kept at the precision scales are stored in,
+// so it comes out the same whether postgres or the app worked it out
+#[allow(clippy::cast_possible_truncation)]
+pub fn day_summary(scales: &[f32]) -> Option<f32> {
+    let values = scales
+        .iter()
+        .map(|scale| f64::from(*scale))
+        .collect::<Vec<_>>();
+
+    mean(&values).map(|mean| mean as f32)
+}
+
+// one per date, sorted by date
+pub fn day_summaries(mut scales: Vec<(NaiveDate, f32)>) -> Vec<(NaiveDate, f32)> {
+    scales.sort_by_key(|(date, _)| *date);
+
+    scales
+        .chunk_by(|(a, _), (b, _)| a == b)
+        .filter_map(|day| {
+            let values = day.iter().map(|(_, scale)| *scale).collect::<Vec<_>>();
+            Some((day.first()?.0, day_summary(&values)?))
+        })
+        .collect()
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+// `scales` has to be sorted by date, the window always holds at least the day itself
+fn rolling_mean(scales: &[(NaiveDate, f32)], date: NaiveDate, window: u64) -> f64 {
+    let start = date
+        .checked_sub_days(Days::new(window - 1))
+        .unwrap_or(NaiveDate::MIN);
+    let first = scales.partition_point(|(day, _)| *day < start);
+    let last = scales.partition_point(|(day, _)| *day <= date);
+
+    let values = scales[first..last]
+        .iter()
+        .map(|(_, scale)| f64::from(*scale))
+        .collect::<Vec<_>>();
+
+    mean(&values).unwrap_or_default()
+}
+
+fn every_weekday(mut weekdays: Vec<WeekdayStats>) -> Vec<WeekdayStats> {
+    for weekday in 1..=7 {
+        if !weekdays.iter().any(|stats| stats.weekday == weekday) {
+            weekdays.push(WeekdayStats {
+                weekday,
+                count: 0,
+                average: None,
+            });
+        }
+    }
+
+    weekdays.sort_by_key(|stats| stats.weekday);
+    weekdays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    #[test]
+    fn streaks_spend_freezes_on_whole_gaps() {
+        let streaks = Streaks::compute(
+            &[day(1, 1), day(1, 2), day(1, 4), day(1, 5)],
+            &HashSet::new(),
+            1,
+            day(1, 5),
+        );
+        assert_eq!(streaks.current, 4);
+        assert_eq!(streaks.current_start, Some(day(1, 1)));
+        assert_eq!(streaks.frozen_days, [day(1, 3)]);
+        assert_eq!(streaks.freezes_left, 0);
+        assert!(streaks.written_today);
+
+        // two missed days with one freeze left breaks the streak and spends nothing
+        let streaks = Streaks::compute(
+            &[day(1, 1), day(1, 2), day(1, 5)],
+            &HashSet::new(),
+            1,
+            day(1, 5),
+        );
+        assert_eq!(streaks.current, 1);
+        assert_eq!(streaks.longest, 2);
+        assert!(streaks.frozen_days.is_empty());
+        assert_eq!(streaks.freezes_left, 1);
+    }
+
+    #[test]
+    fn streaks_spend_freezes_against_the_month_of_the_missed_day() {
+        let streaks = Streaks::compute(
+            &[day(1, 30), day(2, 1), day(2, 3)],
+            &HashSet::new(),
+            1,
+            day(2, 3),
+        );
+        assert_eq!(streaks.current, 3);
+        assert_eq!(streaks.frozen_days, [day(1, 31), day(2, 2)]);
+        assert_eq!(streaks.freezes_left, 0);
+    }
+
+    #[test]
+    fn streaks_stay_alive_until_today_is_over() {
+        let streaks = Streaks::compute(&[day(1, 1), day(1, 2)], &HashSet::new(), 0, day(1, 3));
+        assert_eq!(streaks.current, 2);
+        assert!(!streaks.written_today);
+
+        let streaks = Streaks::compute(&[day(1, 1), day(1, 2)], &HashSet::new(), 0, day(1, 4));
+        assert_eq!(streaks.current, 0);
+        assert_eq!(streaks.current_start, None);
+        assert_eq!(streaks.longest, 2);
+        assert_eq!(
+            (streaks.longest_start, streaks.longest_end),
+            (Some(day(1, 1)), Some(day(1, 2)))
+        );
+    }
+
+    #[test]
+    fn streaks_step_over_days_skipped_by_a_timezone_change() {
+        let skipped_days = HashSet::from([day(1, 3)]);
+        let streaks = Streaks::compute(
+            &[day(1, 1), day(1, 2), day(1, 4)],
+            &skipped_days,
+            0,
+            day(1, 4),
+        );
+        assert_eq!(streaks.current, 3);
+        assert!(streaks.frozen_days.is_empty());
+
+        // a skipped day next to a missed one still needs a freeze for the missed one
+        let streaks = Streaks::compute(
+            &[day(1, 1), day(1, 2), day(1, 5)],
+            &skipped_days,
+            1,
+            day(1, 5),
+        );
+        assert_eq!(streaks.current, 3);
+        assert_eq!(streaks.frozen_days, [day(1, 4)]);
+    }
+
+    #[test]
+    fn streaks_keep_entries_dated_after_today() {
+        // moving west can leave today's entry a day ahead of the new today
+        let streaks = Streaks::compute(
+            &[day(1, 1), day(1, 2), day(1, 3)],
+            &HashSet::new(),
+            0,
+            day(1, 2),
+        );
+        assert_eq!(streaks.current, 3);
+        assert_eq!(streaks.current_start, Some(day(1, 1)));
+        assert!(streaks.written_today);
+        assert!(streaks.frozen_days.is_empty());
+    }
+
+    #[test]
+    fn stats_range_reaches_back_a_long_window() {
+        let range = StatsRange::new(Some(day(2, 1)), day(2, 28)).unwrap();
+        assert_eq!(range.window_start, Some(day(1, 3)));
+
+        assert!(StatsRange::new(None, day(2, 28))
+            .unwrap()
+            .window_start
+            .is_none());
+        assert!(StatsRange::new(Some(day(3, 1)), day(2, 28)).is_err());
+        assert!(StatsRange::new(Some(NaiveDate::MIN), day(2, 28)).is_err());
+    }
+
+    #[test]
+    fn mood_stats_summarize_each_day_in_range() {
+        // 2025-01-06 is a monday, the 1st is outside the range but inside the rolling windows
+        let range = StatsRange::new(Some(day(1, 6)), day(1, 12)).unwrap();
+        let stats = MoodStats::from_scales(
+            range,
+            vec![
+                (day(1, 8), 7.0),
+                (day(1, 1), 10.0),
+                (day(1, 6), 2.0),
+                (day(1, 6), 4.0),
+                (day(1, 7), 5.0),
+            ],
+        );
+
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.average, Some(5.0));
+        assert_eq!(stats.median, Some(5.0));
+        assert!((stats.variance.unwrap() - 8.0 / 3.0).abs() < 1e-9);
+
+        let weekdays = stats
+            .weekdays
+            .iter()
+            .map(|weekday| (weekday.weekday, weekday.count, weekday.average))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            weekdays,
+            [
+                (1, 1, Some(3.0)),
+                (2, 1, Some(5.0)),
+                (3, 1, Some(7.0)),
+                (4, 0, None),
+                (5, 0, None),
+                (6, 0, None),
+                (7, 0, None),
+            ]
+        );
+
+        let rolling = stats
+            .rolling_averages
+            .iter()
+            .map(|day| (day.date, day.emotion_scale, day.average_7, day.average_30))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rolling,
+            [
+                (day(1, 6), 3.0, 6.5, 6.5),
+                (day(1, 7), 5.0, 6.0, 6.0),
+                (day(1, 8), 7.0, 5.0, 6.25),
+            ]
+        );
+    }
+
+    #[test]
+    fn mood_stats_median_of_an_even_count_is_the_midpoint() {
+        let range = StatsRange::new(None, day(1, 31)).unwrap();
+        let stats = MoodStats::from_scales(
+            range,
+            vec![
+                (day(1, 1), 1.0),
+                (day(1, 2), 2.0),
+                (day(1, 3), 3.0),
+                (day(1, 4), 4.0),
+            ],
+        );
+        assert_eq!(stats.median, Some(2.5));
+
+        let stats = MoodStats::from_scales(range, Vec::new());
+        assert_eq!(stats.count, 0);
+        assert_eq!(
+            (stats.average, stats.median, stats.variance),
+            (None, None, None)
+        );
+        assert_eq!(stats.weekdays.len(), 7);
+        assert!(stats.rolling_averages.is_empty());
+    }
+
+    #[test]
+    fn calendar_has_a_slot_for_every_day() {
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let calendar = Calendar::new(
+            day(2, 1),
+            day(2, 28),
+            vec![
+                (day(2, 3), second, 4.0),
+                (day(2, 3), first, 8.0),
+                (day(2, 28), third, 5.0),
+                // outside the period, dropped
+                (day(1, 31), Uuid::new_v4(), 1.0),
+                (day(3, 1), Uuid::new_v4(), 1.0),
+            ],
+        );
+
+        assert_eq!(calendar.days.len(), 28);
+        assert_eq!(calendar.days.iter().flatten().count(), 2);
+
+        let third_of_feb = calendar.days[2].as_ref().unwrap();
+        assert!((third_of_feb.emotion_scale - 6.0).abs() < f32::EPSILON);
+        assert_eq!(third_of_feb.entry_ids, [second, first]);
+
+        let last = calendar.days[27].as_ref().unwrap();
+        assert_eq!(last.entry_ids, [third]);
+        assert!(calendar.days[0].is_none());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    impl_service,
+    schemas::{tag::EncryptedTag, user::User},
+};
+use sqlx::{Error, PgConnection, PgPool};
+use uuid::Uuid;
+
+pub struct TagService(PgPool);
+impl_service!(TagService);
+
+impl TagService {
+    pub async fn get_tags(&self, user: &User) -> Result<Vec<EncryptedTag>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM tags WHERE author = $1 ORDER BY created_at, id",
+        )
+        .bind(user.id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_tags_count(&self, user: &User) -> Result<i64, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT COUNT(*) FROM tags WHERE author = $1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // the name is sealed against the id, so the id is picked before the row is written
+    pub async fn create_tag(
+        &self,
+        user: &User,
+        id: Uuid,
+        encrypted_name: &[u8],
+    ) -> Result<EncryptedTag, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "INSERT INTO tags (id, author, encrypted_name) VALUES ($1, $2, $3) RETURNING *",
+        )
+        .bind(id)
+        .bind(user.id)
+        .bind(encrypted_name)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    pub async fn rename_tag(
+        &self,
+        user: &User,
+        id: &Uuid,
+        encrypted_name: &[u8],
+    ) -> Result<Option<EncryptedTag>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "UPDATE tags SET encrypted_name = $1 WHERE id = $2 AND author = $3 RETURNING *",
+        )
+        .bind(encrypted_name)
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // entries keep everything but the tag, entry_tags rows go with it
+    pub async fn delete_tag(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM tags WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    pub async fn get_entry_tag_ids(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+    ) -> Result<Vec<Uuid>, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT tag_id FROM entry_tags WHERE author = $1 AND entry_id = $2 ORDER BY tag_id",
+        )
+        .bind(user.id)
+        .bind(entry_id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    // replaces the entry's whole tag set. past entries and today's entry can both be tagged, ephemeral
+    // entries can't since they're thrown away at the end of the day. returns false if the entry or any tag
+    // doesn't belong to the user
+    pub async fn set_entry_tags(
+        &self,
+        user: &User,
+        entry_id: &Uuid,
+        tag_ids: &[Uuid],
+    ) -> Result<bool, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        let entry_exists = sqlx::query_scalar::<_, bool>(
+            // language=postgresql
+            "
+                SELECT EXISTS (SELECT 1 FROM entries WHERE id = $1 AND author = $2)
+                    OR EXISTS (SELECT 1 FROM active_entries WHERE id = $1 AND author = $2 AND NOT ephemeral)
+            ",
+        )
+        .bind(entry_id)
+        .bind(user.id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        if !entry_exists {
+            return Ok(false);
+        }
+
+        Self::delete_entry_tags(&mut transaction, user.id, &[*entry_id]).await?;
+
+        let inserted = sqlx::query(
+            // language=postgresql
+            "
+                INSERT INTO entry_tags (entry_id, tag_id, author)
+                SELECT $1, id, author FROM tags
+                WHERE author = $2 AND id = ANY($3)
+            ",
+        )
+        .bind(entry_id)
+        .bind(user.id)
+        .bind(tag_ids)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+
+        if usize::try_from(inserted).ok() != Some(tag_ids.len()) {
+            return Ok(false);
+        }
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn delete_entry_tags(
+        connection: &mut PgConnection,
+        author: Uuid,
+        entry_ids: &[Uuid],
+    ) -> Result<(), Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM entry_tags WHERE author = $1 AND entry_id = ANY($2)",
+        )
+        .bind(author)
+        .bind(entry_ids)
+        .execute(connection)
+        .await
+        .map(|_| ())
+    }
+}
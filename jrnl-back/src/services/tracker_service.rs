@@ -0,0 +1,270 @@
+use crate::{
+    impl_service,
+    schemas::{
+        tracker::{EncryptedTracker, EncryptedTrackerValue, Tracker, TrackerDayValue, TrackerKind},
+        user::User,
+        user_key::UserKey,
+    },
+    services::stats_service::mean,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::{Error, PgPool};
+use std::collections::HashMap;
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+pub struct TrackerService(PgPool);
+impl_service!(TrackerService);
+
+impl TrackerService {
+    // oldest first, the order they were added in
+    pub async fn get_trackers(&self, user: &User) -> Result<Vec<EncryptedTracker>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM trackers WHERE author = $1 ORDER BY created_at, id",
+        )
+        .bind(user.id)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_tracker(
+        &self,
+        user: &User,
+        id: &Uuid,
+    ) -> Result<Option<EncryptedTracker>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM trackers WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    pub async fn get_trackers_count(&self, user: &User) -> Result<i64, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT COUNT(*) FROM trackers WHERE author = $1",
+        )
+        .bind(user.id)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    // the definition is sealed against the id, so the id is picked before the row is written
+    pub async fn create_tracker(
+        &self,
+        user: &User,
+        id: Uuid,
+        encrypted_definition: &[u8],
+    ) -> Result<EncryptedTracker, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "INSERT INTO trackers (id, author, encrypted_definition) VALUES ($1, $2, $3) RETURNING *",
+        )
+        .bind(id)
+        .bind(user.id)
+        .bind(encrypted_definition)
+        .fetch_one(&self.0)
+        .await
+    }
+
+    pub async fn update_tracker(
+        &self,
+        user: &User,
+        id: &Uuid,
+        encrypted_definition: &[u8],
+    ) -> Result<Option<EncryptedTracker>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "UPDATE trackers SET encrypted_definition = $1 WHERE id = $2 AND author = $3 RETURNING *",
+        )
+        .bind(encrypted_definition)
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // its values go with it
+    pub async fn delete_tracker(&self, user: &User, id: &Uuid) -> Result<bool, Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM trackers WHERE id = $1 AND author = $2",
+        )
+        .bind(id)
+        .bind(user.id)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    // both ends inclusive, oldest first
+    pub async fn get_tracker_values(
+        &self,
+        user: &User,
+        tracker_id: &Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<EncryptedTrackerValue>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                SELECT * FROM tracker_values
+                WHERE tracker_id = $1 AND author = $2 AND date >= $3 AND date <= $4
+                ORDER BY date
+            ",
+        )
+        .bind(tracker_id)
+        .bind(user.id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn get_day_values(
+        &self,
+        user: &User,
+        date: NaiveDate,
+    ) -> Result<Vec<EncryptedTrackerValue>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM tracker_values WHERE author = $1 AND date = $2",
+        )
+        .bind(user.id)
+        .bind(date)
+        .fetch_all(&self.0)
+        .await
+    }
+
+    pub async fn set_tracker_value(
+        &self,
+        user: &User,
+        tracker_id: &Uuid,
+        date: NaiveDate,
+        encrypted_value: &[u8],
+    ) -> Result<Option<EncryptedTrackerValue>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "
+                INSERT INTO tracker_values (tracker_id, author, date, encrypted_value)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (tracker_id, date) DO UPDATE
+                    SET encrypted_value = EXCLUDED.encrypted_value, updated_at = NOW()
+                    WHERE tracker_values.author = $2
+                RETURNING *
+            ",
+        )
+        .bind(tracker_id)
+        .bind(user.id)
+        .bind(date)
+        .bind(encrypted_value)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    pub async fn delete_tracker_value(
+        &self,
+        user: &User,
+        tracker_id: &Uuid,
+        date: NaiveDate,
+    ) -> Result<bool, Error> {
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM tracker_values WHERE tracker_id = $1 AND author = $2 AND date = $3",
+        )
+        .bind(tracker_id)
+        .bind(user.id)
+        .bind(date)
+        .execute(&self.0)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+pub async fn decrypt_trackers(
+    trackers: Vec<EncryptedTracker>,
+    user_key: UserKey,
+) -> anyhow::Result<Vec<Tracker>> {
+    spawn_blocking(move || {
+        trackers
+            .iter()
+            .map(|tracker| tracker.decrypt(&user_key))
+            .collect()
+    })
+    .await?
+}
+
+// values of trackers that aren't in `kinds` are left out
+pub async fn decrypt_tracker_values(
+    values: Vec<EncryptedTrackerValue>,
+    kinds: HashMap<Uuid, TrackerKind>,
+    user_key: UserKey,
+) -> anyhow::Result<Vec<TrackerDayValue>> {
+    spawn_blocking(move || {
+        values
+            .iter()
+            .filter_map(|value| {
+                let kind = kinds.get(&value.tracker_id)?;
+                Some(value.decrypt(*kind, &user_key))
+            })
+            .collect()
+    })
+    .await?
+}
+
+// values are always sealed, so the summary is worked out after they're opened.
+// for booleans `total` is the number of days done and `average` the share of them
+#[derive(Debug, Serialize)]
+pub struct TrackerHistory {
+    pub tracker: Tracker,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    // days with a value
+    pub days: i64,
+    pub total: f64,
+    pub average: Option<f64>,
+    // left out for booleans
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub values: Vec<TrackerDayValue>,
+}
+
+impl TrackerHistory {
+    pub fn new(
+        tracker: Tracker,
+        from: NaiveDate,
+        to: NaiveDate,
+        values: Vec<TrackerDayValue>,
+    ) -> Self {
+        let numbers = values
+            .iter()
+            .map(|value| value.value.as_f64())
+            .collect::<Vec<_>>();
+
+        let (min, max) = if tracker.kind == TrackerKind::Boolean {
+            (None, None)
+        } else {
+            (
+                numbers.iter().copied().reduce(f64::min),
+                numbers.iter().copied().reduce(f64::max),
+            )
+        };
+
+        Self {
+            tracker,
+            from,
+            to,
+            days: i64::try_from(values.len()).unwrap_or(i64::MAX),
+            total: numbers.iter().sum(),
+            average: mean(&numbers),
+            min,
+            max,
+            values,
+        }
+    }
+}
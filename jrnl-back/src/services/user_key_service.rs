@@ -0,0 +1,86 @@
+use crate::{
+    crypto::key_provider::KeyProvider,
+    impl_service,
+    schemas::{
+        user::User,
+        user_key::{UserKey, WrappedUserKey},
+    },
+};
+use anyhow::Context;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+pub struct UserKeyService(PgPool);
+impl_service!(UserKeyService);
+
+impl UserKeyService {
+    pub async fn get_or_create_user_key(
+        &self,
+        user: &User,
+        key_provider: &dyn KeyProvider,
+    ) -> anyhow::Result<UserKey> {
+        let mut connection = self.0.acquire().await?;
+
+        Self::get_or_create_user_keys(&mut connection, &[user.id], key_provider)
+            .await?
+            .remove(&user.id)
+            .context("user key missing after creation")
+    }
+
+    // users without a key get one generated, racing inserts just fall back to whichever key landed first
+    pub async fn get_or_create_user_keys(
+        connection: &mut PgConnection,
+        user_ids: &[Uuid],
+        key_provider: &dyn KeyProvider,
+    ) -> anyhow::Result<HashMap<Uuid, UserKey>> {
+        let mut wrapped_keys = Self::get_wrapped_user_keys(&mut *connection, user_ids).await?;
+
+        let missing = user_ids
+            .iter()
+            .filter(|id| !wrapped_keys.iter().any(|key| key.user_id == **id))
+            .collect::<HashSet<_>>();
+
+        if !missing.is_empty() {
+            for id in missing {
+                let key = WrappedUserKey::generate(*id, key_provider).await?;
+
+                sqlx::query(
+                    // language=postgresql
+                    "
+                        INSERT INTO user_keys (user_id, wrapped_key, key_version)
+                        VALUES ($1, $2, $3)
+                        ON CONFLICT (user_id) DO NOTHING
+                    ",
+                )
+                .bind(key.user_id)
+                .bind(&key.wrapped_key)
+                .bind(key.key_version)
+                .execute(&mut *connection)
+                .await?;
+            }
+
+            wrapped_keys = Self::get_wrapped_user_keys(&mut *connection, user_ids).await?;
+        }
+
+        let mut user_keys = HashMap::with_capacity(wrapped_keys.len());
+        for key in wrapped_keys {
+            user_keys.insert(key.user_id, key.unwrap(key_provider).await?);
+        }
+
+        Ok(user_keys)
+    }
+
+    async fn get_wrapped_user_keys(
+        connection: &mut PgConnection,
+        user_ids: &[Uuid],
+    ) -> Result<Vec<WrappedUserKey>, sqlx::Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM user_keys WHERE user_id = ANY($1)",
+        )
+        .bind(user_ids)
+        .fetch_all(connection)
+        .await
+    }
+}
@@ -1,10 +1,27 @@
-use crate::{impl_service, schemas::user::User};
-use sqlx::{Error, PgPool};
+use crate::{
+    error::{JrnlError, JrnlResult},
+    impl_service,
+    schemas::{e2e_key_material::E2eKeyMaterial, user::User},
+    services::entry_revision_service::EntryRevisionService,
+};
+use sqlx::{Error, PgConnection, PgPool};
+use std::collections::HashSet;
 use uuid::Uuid;
 
 pub struct UserService(PgPool);
 impl_service!(UserService);
 
+// fields left as none are kept as they are
+pub struct UserUpdate<'a> {
+    pub theme: Option<&'a str>,
+    pub tz: Option<&'a str>,
+    pub has_had_tour: Option<bool>,
+    pub has_seen_app_push: Option<bool>,
+    pub hide_metadata: Option<bool>,
+    pub revision_limit: Option<i32>,
+    pub streak_freezes: Option<i32>,
+}
+
 impl UserService {
     pub async fn create_or_get_user(
         &self,
@@ -28,32 +45,75 @@ impl UserService {
         .await
     }
 
-    pub async fn update_user(
-        &self,
-        user: &User,
-        theme: Option<&str>,
-        tz: Option<&str>,
-        has_had_tour: Option<bool>,
-        has_seen_app_push: Option<bool>,
-    ) -> Result<User, Error> {
-        sqlx::query_as(
+    pub async fn update_user(&self, user: &User, update: &UserUpdate<'_>) -> Result<User, Error> {
+        let mut transaction = self.0.begin().await?;
+        let previous_date = user.current_date_by_timezone();
+
+        let user = sqlx::query_as::<_, User>(
             // language=postgresql
             "
                 UPDATE users SET
                 timezone = COALESCE($1, timezone),
                 theme = COALESCE($2, theme),
                 has_had_tour = COALESCE($3, has_had_tour),
-                has_seen_app_push = COALESCE($4, has_seen_app_push)
-                WHERE id = $3 RETURNING *
+                has_seen_app_push = COALESCE($4, has_seen_app_push),
+                hide_metadata = COALESCE($5, hide_metadata),
+                revision_limit = COALESCE($6, revision_limit),
+                streak_freezes = COALESCE($7, streak_freezes)
+                WHERE id = $8 RETURNING *
             ",
         )
-        .bind(tz)
-        .bind(theme)
+        .bind(update.tz)
+        .bind(update.theme)
+        .bind(update.has_had_tour)
+        .bind(update.has_seen_app_push)
+        .bind(update.hide_metadata)
+        .bind(update.revision_limit)
+        .bind(update.streak_freezes)
         .bind(user.id)
-        .bind(has_had_tour)
-        .bind(has_seen_app_push)
-        .fetch_one(&self.0)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        // a lower limit applies to history that already exists too
+        if update.revision_limit.is_some() {
+            EntryRevisionService::prune(&mut transaction, user.id, None).await?;
+        }
+
+        // moving east can jump past the rest of today, or even a whole day, before it could be written
+        let current_date = user.current_date_by_timezone();
+        if current_date > previous_date {
+            sqlx::query(
+                // language=postgresql
+                "
+                    INSERT INTO streak_skipped_days (user_id, date)
+                    SELECT $1, day::date FROM generate_series($2::date, $3::date - 1, INTERVAL '1 day') AS day
+                    ON CONFLICT DO NOTHING
+                ",
+            )
+            .bind(user.id)
+            .bind(previous_date)
+            .bind(current_date)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(user)
+    }
+
+    // for background jobs that seal entries of many authors at once
+    pub async fn get_users_hiding_metadata(
+        connection: &mut PgConnection,
+        user_ids: &[Uuid],
+    ) -> Result<HashSet<Uuid>, Error> {
+        sqlx::query_scalar(
+            // language=postgresql
+            "SELECT id FROM users WHERE id = ANY($1) AND hide_metadata",
+        )
+        .bind(user_ids)
+        .fetch_all(connection)
         .await
+        .map(|ids| ids.into_iter().collect())
     }
 
     pub async fn get_user_by_id(&self, id: &Uuid) -> Result<User, Error> {
@@ -65,4 +125,124 @@ impl UserService {
         .fetch_one(&self.0)
         .await
     }
+
+    // dropping the user key crypto-shreds whatever it wrapped, including copies sitting in backups.
+    // rows the maintenance task hasn't moved under the user key yet are still wrapped by a master key
+    // (or are legacy plaintext active entries) and would survive in backups, so refuse until it has
+    pub async fn delete_user(&self, user: &User) -> JrnlResult<()> {
+        let mut transaction = self.0.begin().await?;
+
+        let has_unshreddable_rows = sqlx::query_scalar::<_, bool>(
+            // language=postgresql
+            "
+                SELECT EXISTS (SELECT 1 FROM entries WHERE author = $1 AND key_version IS NOT NULL)
+                    OR EXISTS (SELECT 1 FROM entry_revisions WHERE author = $1 AND key_version IS NOT NULL)
+                    OR EXISTS (SELECT 1 FROM active_entries WHERE author = $1 AND text IS NOT NULL)
+            ",
+        )
+        .bind(user.id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        if has_unshreddable_rows {
+            return Err(JrnlError::AccountDeletionPending);
+        }
+
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM user_keys WHERE user_id = $1",
+        )
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        // groups don't cascade on their owner, hand them off to another member or drop them
+        sqlx::query(
+            // language=postgresql
+            "
+                UPDATE groups SET owner_id = (
+                    SELECT user_id FROM group_memberships
+                    WHERE group_id = groups.id AND user_id <> $1
+                    LIMIT 1
+                )
+                WHERE owner_id = $1
+                AND EXISTS (
+                    SELECT 1 FROM group_memberships
+                    WHERE group_id = groups.id AND user_id <> $1
+                )
+            ",
+        )
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM groups WHERE owner_id = $1",
+        )
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query(
+            // language=postgresql
+            "DELETE FROM users WHERE id = $1",
+        )
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_e2e_key_material(&self, user: &User) -> Result<Option<E2eKeyMaterial>, Error> {
+        sqlx::query_as(
+            // language=postgresql
+            "SELECT * FROM e2e_key_material WHERE user_id = $1 LIMIT 1",
+        )
+        .bind(user.id)
+        .fetch_optional(&self.0)
+        .await
+    }
+
+    // also used to re-wrap the data key after a passphrase change, past ciphertext is untouched
+    pub async fn set_e2e_key_material(
+        &self,
+        user: &User,
+        key_material: &E2eKeyMaterial,
+    ) -> Result<E2eKeyMaterial, Error> {
+        let mut transaction = self.0.begin().await?;
+
+        let key_material = sqlx::query_as(
+            // language=postgresql
+            "
+                INSERT INTO e2e_key_material (user_id, kdf_algorithm, kdf_params, salt, wrapped_data_key, wrapped_recovery_key)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (user_id)
+                DO UPDATE SET kdf_algorithm = $2, kdf_params = $3, salt = $4,
+                              wrapped_data_key = $5, wrapped_recovery_key = $6, updated_at = NOW()
+                RETURNING *
+            ",
+        )
+            .bind(user.id)
+            .bind(&key_material.kdf_algorithm)
+            .bind(&key_material.kdf_params)
+            .bind(&key_material.salt)
+            .bind(&key_material.wrapped_data_key)
+            .bind(&key_material.wrapped_recovery_key)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        sqlx::query(
+            // language=postgresql
+            "UPDATE users SET e2e_enabled = TRUE WHERE id = $1",
+        )
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(key_material)
+    }
 }
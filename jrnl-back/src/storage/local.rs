@@ -0,0 +1,127 @@
+use crate::storage::{BlobStorage, BlobStream, BlobWriter};
+use anyhow::{bail, Context};
+use axum::{async_trait, body::Bytes};
+use futures_util::{stream, StreamExt};
+use std::{
+    env,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+const DEFAULT_BLOB_DIR: &str = "/app/cache/attachments";
+const READ_CHUNK_LEN: usize = 64 * 1024;
+
+// blobs as plain files below one directory, keys map straight to relative paths
+pub struct LocalBlobStorage {
+    root: PathBuf,
+}
+
+struct LocalBlobWriter {
+    file: File,
+    partial_path: PathBuf,
+    path: PathBuf,
+}
+
+impl LocalBlobStorage {
+    // BLOB_DIR defaults to the cache volume the compose file mounts
+    pub async fn from_env() -> anyhow::Result<Self> {
+        let root = PathBuf::from(env::var("BLOB_DIR").unwrap_or_else(|_| DEFAULT_BLOB_DIR.into()));
+
+        // fail on startup instead of on the first upload if the directory can't be written to
+        fs::create_dir_all(&root)
+            .await
+            .with_context(|| format!("failed to create blob directory {}", root.display()))?;
+
+        Ok(Self { root })
+    }
+
+    // keys are generated by us, but nothing should ever be able to point outside of the root
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let valid_segment = |segment: &str| {
+            !segment.is_empty()
+                && !segment.starts_with('.')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+
+        if !key.split('/').all(valid_segment) {
+            bail!("invalid blob key {key:?}");
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStorage for LocalBlobStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn create(&self, key: &str) -> anyhow::Result<Box<dyn BlobWriter>> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let partial_path = path.with_extension("partial");
+        let file = File::create(&partial_path).await?;
+
+        Ok(Box::new(LocalBlobWriter {
+            file,
+            partial_path,
+            path,
+        }))
+    }
+
+    async fn open(&self, key: &str) -> anyhow::Result<BlobStream> {
+        let file = File::open(self.path(key)?).await?;
+
+        Ok(stream::try_unfold(file, |mut file| async move {
+            let mut chunk = vec![0; READ_CHUNK_LEN];
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            chunk.truncate(read);
+            Ok(Some((Bytes::from(chunk), file)))
+        })
+        .boxed())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        remove_file(&self.path(key)?).await
+    }
+}
+
+#[async_trait]
+impl BlobWriter for LocalBlobWriter {
+    async fn write(&mut self, chunk: &[u8]) -> anyhow::Result<()> {
+        self.file.write_all(chunk).await.map_err(Into::into)
+    }
+
+    async fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.file.sync_all().await?;
+        fs::rename(&self.partial_path, &self.path).await?;
+
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) -> anyhow::Result<()> {
+        drop(self.file);
+        remove_file(&self.partial_path).await
+    }
+}
+
+async fn remove_file(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path).await {
+        Err(why) if why.kind() != ErrorKind::NotFound => Err(why.into()),
+        _ => Ok(()),
+    }
+}
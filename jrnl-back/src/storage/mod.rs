@@ -0,0 +1,44 @@
+use anyhow::bail;
+use axum::{async_trait, body::Bytes};
+use futures_util::stream::BoxStream;
+use local::LocalBlobStorage;
+use std::{env, sync::Arc};
+
+pub mod local;
+
+pub type BlobStream = BoxStream<'static, anyhow::Result<Bytes>>;
+
+// where attachment blobs end up. blobs are only ever sealed before they get here, so a backend never sees
+// plaintext and can be anything from a local directory to object storage
+#[async_trait]
+pub trait BlobStorage: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // nothing is readable under the key until the writer is finished
+    async fn create(&self, key: &str) -> anyhow::Result<Box<dyn BlobWriter>>;
+
+    async fn open(&self, key: &str) -> anyhow::Result<BlobStream>;
+
+    // deleting a blob that doesn't exist isn't an error, deletions are retried until they succeed
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+pub trait BlobWriter: Send {
+    async fn write(&mut self, chunk: &[u8]) -> anyhow::Result<()>;
+
+    async fn finish(self: Box<Self>) -> anyhow::Result<()>;
+
+    // throws away everything written so far
+    async fn abort(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+// BLOB_STORAGE picks the backend, defaulting to the local cache directory
+pub async fn blob_storage_from_env() -> anyhow::Result<Arc<dyn BlobStorage>> {
+    let storage: Arc<dyn BlobStorage> = match env::var("BLOB_STORAGE").as_deref() {
+        Ok("local") | Err(_) => Arc::new(LocalBlobStorage::from_env().await?),
+        Ok(other) => bail!("unknown BLOB_STORAGE {other}"),
+    };
+
+    Ok(storage)
+}
@@ -1,65 +1,364 @@
-use base64::{engine::general_purpose::STANDARD, Engine};
+use crate::error::{JrnlError, JrnlResult};
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::NaiveDate;
-use serde::{Deserialize, Deserializer, Serialize};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::env;
 use uuid::Uuid;
 
-#[derive(Debug)]
-pub struct Cursor {
-    pub date: NaiveDate,
-    pub id: Uuid,
+type HmacSha256 = Hmac<Sha256>;
+
+// cursors are opaque to clients, they're signed so a forged or edited one is rejected instead of paged from
+const TAG_LEN: usize = 16;
+
+// derived from the jwt secret so cursors don't need a secret of their own, a cursor tag is never a valid jwt
+// signature or the other way around
+#[derive(Clone)]
+pub struct CursorSigner(HmacSha256);
+
+// the list a cursor was handed out for, it can't be used to page any other
+#[derive(Debug, Clone, Copy)]
+pub enum CursorList {
+    Entries,
+    Search,
+    Groups,
 }
 
-impl Default for Cursor {
-    fn default() -> Self {
-        Self {
-            date: NaiveDate::MAX,
-            id: Uuid::max(),
+// signs and checks cursors for one user's list
+pub struct CursorScope<'a> {
+    signer: &'a CursorSigner,
+    user_id: Uuid,
+    list: CursorList,
+}
+
+impl CursorSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        let key = <HmacSha256 as Mac>::new_from_slice(secret)
+            .expect("hmac takes keys of any length")
+            .chain_update(b"jrnl:cursor:")
+            .finalize()
+            .into_bytes();
+
+        Self(<HmacSha256 as Mac>::new_from_slice(&key).expect("hmac takes keys of any length"))
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        let secret = env::var("JWT_SECRET").context("JWT_SECRET must be set")?;
+        Ok(Self::new(secret.as_bytes()))
+    }
+
+    pub const fn scope(&self, user_id: Uuid, list: CursorList) -> CursorScope<'_> {
+        CursorScope {
+            signer: self,
+            user_id,
+            list,
+        }
+    }
+}
+
+impl CursorList {
+    const fn label(self) -> &'static [u8] {
+        match self {
+            Self::Entries => b"entries:",
+            Self::Search => b"search:",
+            Self::Groups => b"groups:",
         }
     }
 }
 
-impl Cursor {
-    fn encode(&self) -> String {
-        STANDARD.encode(format!("{}:{}", self.date, self.id))
+// whatever a page is ordered by, the id breaks ties so every ordering is total
+pub trait CursorKey: Sized {
+    fn encode_key(&self) -> String;
+    fn decode_key(s: &str) -> Option<Self>;
+}
+
+impl CursorKey for NaiveDate {
+    fn encode_key(&self) -> String {
+        self.to_string()
     }
 
-    fn decode(s: &str) -> Option<Self> {
-        STANDARD
-            .decode(s)
-            .ok()
-            .and_then(|b| String::from_utf8(b).ok())
-            .and_then(|c| {
-                let (date, id) = c.split_once(':')?;
-                Some(Self {
-                    date: NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
-                    id: Uuid::parse_str(id).ok()?,
-                })
-            })
+    fn decode_key(s: &str) -> Option<Self> {
+        Self::parse_from_str(s, "%Y-%m-%d").ok()
     }
 }
 
-impl<'de> Deserialize<'de> for Cursor {
-    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let s = String::deserialize(deserializer)?;
-        Self::decode(&s).ok_or_else(|| serde::de::Error::custom("invalid cursor"))
+// forward pages continue past the cursor in the list's own order, backward pages lead up to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor<K = NaiveDate> {
+    pub key: K,
+    pub id: Uuid,
+    pub direction: Direction,
+}
+
+impl<K> Cursor<K> {
+    pub const fn forward(key: K, id: Uuid) -> Self {
+        Self {
+            key,
+            id,
+            direction: Direction::Forward,
+        }
+    }
+
+    pub const fn backward(key: K, id: Uuid) -> Self {
+        Self {
+            key,
+            id,
+            direction: Direction::Backward,
+        }
+    }
+
+    pub fn is_backward(&self) -> bool {
+        self.direction == Direction::Backward
     }
 }
 
-impl Serialize for Cursor {
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        self.encode().serialize(serializer)
+impl CursorScope<'_> {
+    fn mac(&self) -> HmacSha256 {
+        self.signer
+            .0
+            .clone()
+            .chain_update(self.list.label())
+            .chain_update(self.user_id.as_bytes())
+    }
+
+    pub fn seal<K: CursorKey>(&self, cursor: &Cursor<K>) -> String {
+        let direction = match cursor.direction {
+            Direction::Forward => 'f',
+            Direction::Backward => 'b',
+        };
+
+        let mut bytes =
+            format!("{direction}:{}:{}", cursor.key.encode_key(), cursor.id).into_bytes();
+        let tag = self.mac().chain_update(&bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag[..TAG_LEN]);
+
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    // no cursor is the first page, one that was forged, edited or handed out elsewhere is rejected
+    pub fn open<K: CursorKey>(&self, cursor: Option<&str>) -> JrnlResult<Option<Cursor<K>>> {
+        cursor
+            .map(|cursor| self.decode(cursor).ok_or(JrnlError::InvalidCursor))
+            .transpose()
+    }
+
+    // the id never contains a colon, the key might
+    fn decode<K: CursorKey>(&self, s: &str) -> Option<Cursor<K>> {
+        let bytes = URL_SAFE_NO_PAD.decode(s).ok()?;
+        let payload_len = bytes.len().checked_sub(TAG_LEN)?;
+        let (payload, tag) = bytes.split_at(payload_len);
+
+        self.mac()
+            .chain_update(payload)
+            .verify_truncated_left(tag)
+            .ok()?;
+
+        let payload = std::str::from_utf8(payload).ok()?;
+        let (direction, rest) = payload.split_once(':')?;
+        let (key, id) = rest.rsplit_once(':')?;
+
+        Some(Cursor {
+            key: K::decode_key(key)?,
+            id: Uuid::parse_str(id).ok()?,
+            direction: match direction {
+                "f" => Direction::Forward,
+                "b" => Direction::Backward,
+                _ => return None,
+            },
+        })
     }
 }
 
+// `at` starts the first page at the given date instead of the start of the list, for lists ordered by date
 #[derive(Debug, Deserialize)]
 pub struct CursorParams {
-    pub cursor: Option<Cursor>,
+    pub cursor: Option<String>,
+    pub at: Option<NaiveDate>,
     pub limit: Option<u32>,
 }
 
+// cursors are handed out already signed
 #[derive(Debug, Serialize)]
 pub struct CursorPaginatedResponse<T> {
     pub items: Vec<T>,
-    pub next_cursor: Option<Cursor>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
     pub has_more: bool,
 }
+
+impl<T> CursorPaginatedResponse<T> {
+    pub const fn empty() -> Self {
+        Self {
+            items: Vec::new(),
+            next_cursor: None,
+            prev_cursor: None,
+            has_more: false,
+        }
+    }
+
+    // `items` come in the order they were fetched, so a backward page is reversed back into list order.
+    // one more than the limit means there's another page in the direction that was paged in, the other
+    // direction is assumed to have one whenever the page started from a cursor
+    pub fn new<K: CursorKey>(
+        mut items: Vec<T>,
+        limit: usize,
+        cursor: Option<&Cursor<K>>,
+        scope: &CursorScope,
+        key: impl Fn(&T) -> (K, Uuid),
+    ) -> Self {
+        let more = items.len() > limit;
+        if more {
+            items.truncate(limit);
+        }
+
+        let backward = cursor.is_some_and(Cursor::is_backward);
+        if backward {
+            items.reverse();
+        }
+
+        let (has_prev, has_more) = if backward {
+            (more, true)
+        } else {
+            (cursor.is_some(), more)
+        };
+
+        let next_cursor = items
+            .last()
+            .filter(|_| has_more)
+            .map(&key)
+            .map(|(key, id)| scope.seal(&Cursor::forward(key, id)));
+
+        let prev_cursor = items
+            .first()
+            .filter(|_| has_prev)
+            .map(&key)
+            .map(|(key, id)| scope.seal(&Cursor::backward(key, id)));
+
+        Self {
+            has_more: next_cursor.is_some(),
+            items,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+
+    // for pages cut short by a bounded scan, paging on in the same direction picks up where the scan stopped
+    // instead of after the last item
+    pub fn continue_from<K: CursorKey>(mut self, cursor: &Cursor<K>, scope: &CursorScope) -> Self {
+        let sealed = Some(scope.seal(cursor));
+        if cursor.is_backward() {
+            self.prev_cursor = sealed;
+        } else {
+            self.next_cursor = sealed;
+            self.has_more = true;
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor() -> Cursor {
+        Cursor::backward(
+            NaiveDate::from_ymd_opt(2025, 1, 28).unwrap(),
+            Uuid::new_v4(),
+        )
+    }
+
+    #[test]
+    fn opens_what_was_sealed() {
+        let signer = CursorSigner::new(b"secret");
+        let scope = signer.scope(Uuid::new_v4(), CursorList::Entries);
+        let cursor = cursor();
+
+        let opened = scope
+            .open::<NaiveDate>(Some(&scope.seal(&cursor)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(opened.key, cursor.key);
+        assert_eq!(opened.id, cursor.id);
+        assert!(opened.is_backward());
+
+        assert!(scope.open::<NaiveDate>(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_cursors_from_elsewhere() {
+        let signer = CursorSigner::new(b"secret");
+        let user_id = Uuid::new_v4();
+        let sealed = signer.scope(user_id, CursorList::Entries).seal(&cursor());
+
+        for scope in [
+            signer.scope(Uuid::new_v4(), CursorList::Entries),
+            signer.scope(user_id, CursorList::Search),
+        ] {
+            assert!(scope.open::<NaiveDate>(Some(&sealed)).is_err());
+        }
+
+        let other_signer = CursorSigner::new(b"other secret");
+        assert!(other_signer
+            .scope(user_id, CursorList::Entries)
+            .open::<NaiveDate>(Some(&sealed))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_edited_cursors() {
+        let signer = CursorSigner::new(b"secret");
+        let scope = signer.scope(Uuid::new_v4(), CursorList::Entries);
+
+        let mut bytes = URL_SAFE_NO_PAD.decode(scope.seal(&cursor())).unwrap();
+        bytes[0] = b'f';
+        let edited = URL_SAFE_NO_PAD.encode(bytes);
+
+        assert!(scope.open::<NaiveDate>(Some(&edited)).is_err());
+        assert!(scope.open::<NaiveDate>(Some("not a cursor")).is_err());
+    }
+
+    #[test]
+    fn short_pages_continue_from_where_the_scan_stopped() {
+        let signer = CursorSigner::new(b"secret");
+        let scope = signer.scope(Uuid::new_v4(), CursorList::Entries);
+        let date = NaiveDate::from_ymd_opt(2025, 1, 28).unwrap();
+        let page = || {
+            CursorPaginatedResponse::new(
+                vec![(date, Uuid::new_v4())],
+                20,
+                None::<&Cursor>,
+                &scope,
+                |&item| item,
+            )
+        };
+        assert!(!page().has_more);
+
+        let stopped = Cursor::forward(date.pred_opt().unwrap(), Uuid::new_v4());
+        let response = page().continue_from(&stopped, &scope);
+        assert!(response.has_more);
+        let next = scope
+            .open::<NaiveDate>(response.next_cursor.as_deref())
+            .unwrap()
+            .unwrap();
+        assert_eq!((next.key, next.id), (stopped.key, stopped.id));
+
+        let stopped = Cursor::backward(date.pred_opt().unwrap(), Uuid::new_v4());
+        let response = page().continue_from(&stopped, &scope);
+        let prev = scope
+            .open::<NaiveDate>(response.prev_cursor.as_deref())
+            .unwrap()
+            .unwrap();
+        assert!(prev.is_backward());
+        assert_eq!(prev.id, stopped.id);
+    }
+}
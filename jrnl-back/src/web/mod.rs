@@ -1,6 +1,8 @@
 use serde::{Deserialize, Deserializer};
 
+pub mod base64_bytes;
 pub mod cursor;
+pub mod zip_stream;
 
 #[allow(clippy::unnecessary_wraps)]
 pub fn deserialize_empty_string<'de, D: Deserializer<'de>>(
@@ -0,0 +1,187 @@
+use anyhow::Context;
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use flate2::{write::DeflateEncoder, Compression};
+use std::io::Write;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+const VERSION: u16 = 20;
+// names are always utf-8
+const FLAGS: u16 = 1 << 11;
+const METHOD_DEFLATE: u16 = 8;
+
+// writes a zip archive front to back without ever seeking, so it can be streamed as it's built.
+// every file is compressed in memory first, which keeps sizes and checksums in the local headers
+pub struct ZipStreamWriter {
+    offset: u32,
+    files: u16,
+    central_directory: Vec<u8>,
+}
+
+impl ZipStreamWriter {
+    pub const fn new() -> Self {
+        Self {
+            offset: 0,
+            files: 0,
+            central_directory: Vec::new(),
+        }
+    }
+
+    // returns the bytes to send for this file
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        contents: &[u8],
+        modified: NaiveDateTime,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents)?;
+        let compressed = encoder.finish()?;
+
+        let crc = crc32fast::hash(contents);
+        let compressed_size = u32::try_from(compressed.len()).context("file is too large")?;
+        let size = u32::try_from(contents.len()).context("file is too large")?;
+        let name_len = u16::try_from(name.len()).context("file name is too long")?;
+        let (time, date) = dos_date_time(modified);
+
+        let mut header = Vec::with_capacity(30 + name.len() + compressed.len());
+        header.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&FLAGS.to_le_bytes());
+        header.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+        header.extend_from_slice(&time.to_le_bytes());
+        header.extend_from_slice(&date.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&compressed_size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&name_len.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&compressed);
+
+        let directory = &mut self.central_directory;
+        directory.extend_from_slice(&CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        directory.extend_from_slice(&VERSION.to_le_bytes());
+        directory.extend_from_slice(&VERSION.to_le_bytes());
+        directory.extend_from_slice(&FLAGS.to_le_bytes());
+        directory.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+        directory.extend_from_slice(&time.to_le_bytes());
+        directory.extend_from_slice(&date.to_le_bytes());
+        directory.extend_from_slice(&crc.to_le_bytes());
+        directory.extend_from_slice(&compressed_size.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&name_len.to_le_bytes());
+        // extra field, comment, disk number, internal and external attributes
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&self.offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+
+        // anything past these limits would need zip64
+        self.offset = u32::try_from(header.len())
+            .ok()
+            .and_then(|len| self.offset.checked_add(len))
+            .context("archive is too large")?;
+        self.files = self.files.checked_add(1).context("too many files")?;
+
+        Ok(header)
+    }
+
+    pub fn finish(self) -> anyhow::Result<Vec<u8>> {
+        let directory_size =
+            u32::try_from(self.central_directory.len()).context("archive is too large")?;
+
+        let mut end = self.central_directory;
+        end.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        // this disk and the disk the directory starts on
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&self.files.to_le_bytes());
+        end.extend_from_slice(&self.files.to_le_bytes());
+        end.extend_from_slice(&directory_size.to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+
+        Ok(end)
+    }
+}
+
+// dos timestamps start in 1980 and only have two second precision
+fn dos_date_time(date_time: NaiveDateTime) -> (u16, u16) {
+    let year = u32::try_from(date_time.year().clamp(1980, 2107) - 1980).unwrap_or_default();
+    let date = (year << 9) | (date_time.month() << 5) | date_time.day();
+    let time = (date_time.hour() << 11) | (date_time.minute() << 5) | (date_time.second() / 2);
+
+    // every field is already within its bit range
+    (
+        u16::try_from(time).unwrap_or_default(),
+        u16::try_from(date).unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    fn modified() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, 28)
+            .unwrap()
+            .and_hms_opt(21, 14, 37)
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_a_zip_reader() {
+        let files: [(&str, Vec<u8>); 3] = [
+            ("entries.json", br#"[{"text":"dear diary"}]"#.to_vec()),
+            (
+                "entries/2025-01-28 – café.md",
+                "dear diary ".repeat(500).into_bytes(),
+            ),
+            ("empty.txt", Vec::new()),
+        ];
+
+        let mut writer = ZipStreamWriter::new();
+        let mut archive = Vec::new();
+        for (name, contents) in &files {
+            archive.extend(writer.add_file(name, contents, modified()).unwrap());
+        }
+        archive.extend(writer.finish().unwrap());
+
+        let mut reader = ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(reader.len(), files.len());
+
+        for (index, (name, contents)) in files.iter().enumerate() {
+            let mut file = reader.by_index(index).unwrap();
+            assert_eq!(file.name(), *name);
+            assert_eq!(file.size(), contents.len() as u64);
+
+            let modified = file.last_modified();
+            assert_eq!(
+                (modified.year(), modified.month(), modified.day()),
+                (2025, 1, 28)
+            );
+            assert_eq!(
+                (modified.hour(), modified.minute(), modified.second()),
+                (21, 14, 36)
+            );
+
+            // the reader checks the crc once the file has been read to the end
+            let mut read = Vec::new();
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(&read, contents);
+        }
+    }
+
+    #[test]
+    fn dos_timestamps_start_in_1980() {
+        let early = NaiveDate::from_ymd_opt(1970, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(dos_date_time(early), (0, (1 << 5) | 1));
+    }
+}